@@ -1,15 +1,28 @@
 extern crate image;
 
 use anyhow::Context;
-use image::png::PngEncoder;
-use image::ColorType;
 use log::info;
 use parser::FileReaderFactory;
+use renderer::aov::{self, Aov};
+use renderer::film::{Filter, FilterType};
+use renderer::framebuffer::FrameBuffer;
+use renderer::integrator::IntegratorType;
+use renderer::options::{AdaptiveSampling, CropWindow, RenderOptions, TileOrder};
+use renderer::post::denoise::{DenoiseFeatures, Denoiser};
+use renderer::post::tonemap::{ToneMapOperator, ToneMapping};
+use renderer::post::{heatmap, preview};
+use renderer::progress::Progress;
+use renderer::progressive::{self, PassReport, ProgressiveOptions};
+use renderer::sampler::SamplerType;
 use renderer::ComputeEnv;
-use std::fs::File;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use writer::{ImageLayer, ImageWriterFactory};
 
 pub mod parser;
 pub mod renderer;
+pub mod writer;
 
 /// The usage string on which docopt will base the argument
 /// parsing.
@@ -23,19 +36,95 @@ Usage: rustyrender [options] <source> <dest>
 
 A simple physically based monte-carlo ray tracing renderer written in rust. 
 
+The output format is chosen by the extension of <dest>: png, or exr, hdr
+and pfm for high dynamic range images.
+
 Supported compute environments are:
     naive       A naive compute implementation.
     multicore   Parallelizes across cores on the CPU
     cuda        GP GPU based renderer using CUDA. Only supported on nVidia.
     opencl      OpenGL based renderer.
 
+Supported tone mapping operators for png output are:
+    linear              Clamps anything brighter than white.
+    reinhard            The Reinhard curve, x / (1 + x).
+    extended-reinhard   Reinhard with a white point, see --white-point.
+    aces                A fit of the ACES filmic curve.
+    hable               The Uncharted 2 filmic curve.
+    legacy              The gamma 2.0 approximation of older versions.
+
+Supported tile orders are:
+    hilbert     Along a Hilbert curve, which keeps nearby tiles together.
+    spiral      From the center of the image outward.
+    scanline    Row by row from the top left.
+
+Supported samplers are:
+    independent   Uniformly random numbers, like white noise.
+    stratified    Jittered samples in shuffled strata.
+    halton        The Halton sequence, shifted per pixel.
+    sobol         The Owen scrambled Sobol sequence.
+
+Supported reconstruction filters are:
+    box               Averages the samples of each pixel.
+    gaussian          A soft Gaussian, radius 1.5 by default.
+    mitchell          The Mitchell-Netravali cubic, radius 2 by default.
+    lanczos           A windowed sinc, sharp but rings, radius 3 by default.
+    blackman-harris   Close to a Gaussian but sharper, radius 1.5 by default.
+
+Supported integrators are:
+    path                The full path tracer, for final frames.
+    ambient-occlusion   How open the surfaces are to the sky, see --ao-distance.
+    direct              Only the light straight from the lights and the sky.
+    whitted             Mirrors, glass and shadow rays to the lights.
+
+Supported AOVs are:
+    normal             The shading normals of the first hits.
+    geometric-normal   The normals of the actual surfaces, without smoothing.
+    depth              The distance from the camera.
+    albedo             The colors of the surfaces, without the lighting.
+    object-id          The index of the object in the scene plus one.
+    material-id        A number for each distinct material.
+    uv                 The texture coordinates.
+    alpha              1 where there's an object, 0 for the background.
+    direct-diffuse     The light straight from the lights or sky on diffuse surfaces.
+    indirect-diffuse   The light bounced off of other surfaces onto diffuse surfaces.
+    specular           What the mirrors and glass reflect and refract.
+    emission           The light given off by the surfaces the camera sees.
+    background         The background the camera sees.
+
+With an exr <dest>, the AOVs are layers of the image file, named like
+depth.Z. The other formats write a file per AOV, named <dest>.<aov>.<ext>.
+
 Options:
-    -h, --help          Show this message.
-    --compute <arg>     The environment to use on this machine.
-    --width <arg>       The width of the output image. [default: 1920]
-    --height <arg>      The height of the output image. [default: 1080]
-    --samples <arg>     The number of antialiasing samples per pixel. [default: 10]
-    --depth <arg>       The maximum depth of the ray recursion. [default: 50]
+    -h, --help            Show this message.
+    --compute <arg>       The environment to use on this machine.
+    --width <arg>         The width of the output image. [default: 1920]
+    --height <arg>        The height of the output image. [default: 1080]
+    --samples <arg>       The number of antialiasing samples per pixel. [default: 10]
+    --depth <arg>         The most bounces of a path, most end sooner by Russian roulette. [default: 50]
+    --tonemap <arg>       The tone mapping operator, overrides the scene file.
+    --exposure <arg>      The exposure adjustment in stops, overrides the scene file.
+    --white-point <arg>   The value that maps to white for extended-reinhard.
+    --tile-size <arg>     The size of the square tiles the image is rendered in. [default: 32]
+    --tile-order <arg>    The order to render the tiles in. [default: hilbert]
+    --crop <arg>          Only render the pixels in the window x0,y0,x1,y1.
+    --time <arg>          Render progressively for this long, like 90s, 10m or 1h30m.
+    --noise <arg>         Render progressively until the relative noise is below this.
+    --pass-samples <arg>  The samples per pixel of each progressive pass. [default: 4]
+    --max-samples <arg>   The most samples per pixel of a progressive render.
+    --sampler <arg>       The sampler for the random numbers of the samples. [default: sobol]
+    --seed <arg>          The seed of the random numbers, the same seed renders the same image. [default: 0]
+    --filter <arg>        The filter that reconstructs the pixels from the samples. [default: box]
+    --filter-radius <arg>  The radius of the filter in pixels, instead of its default.
+    --integrator <arg>    The integrator for the rays, overrides the scene file.
+    --ao-distance <arg>   How far objects occlude for ambient-occlusion, overrides the scene file.
+    --adaptive <arg>      Stop sampling a pixel once its relative error is below this.
+    --min-samples <arg>   The samples every pixel gets with --adaptive. [default: 4]
+    --heatmap <arg>       Write an image of the samples taken per pixel to this file.
+    --aov <arg>           Also render these comma separated AOVs, see below.
+    --denoise             Denoise the image, guided by the albedo, normals and depth.
+    --noisy <arg>         With --denoise, also write the image before denoising to this file.
+    --quiet               Don't show the progress bar.
 ";
 
 /// The struct definition for deserializing the data.
@@ -52,6 +141,98 @@ pub struct Args {
     flag_height: isize,
     flag_samples: isize,
     flag_depth: isize,
+    flag_tonemap: Option<ToneMapOperator>,
+    flag_exposure: Option<f32>,
+    flag_white_point: Option<f32>,
+    flag_tile_size: isize,
+    flag_tile_order: TileOrder,
+    flag_crop: Option<String>,
+    flag_time: Option<String>,
+    flag_noise: Option<f32>,
+    flag_pass_samples: isize,
+    flag_max_samples: Option<isize>,
+    flag_sampler: SamplerType,
+    flag_seed: u64,
+    flag_filter: FilterType,
+    flag_filter_radius: Option<f32>,
+    flag_integrator: Option<IntegratorType>,
+    flag_ao_distance: Option<f32>,
+    flag_adaptive: Option<f32>,
+    flag_min_samples: isize,
+    flag_heatmap: Option<String>,
+    flag_aov: Option<String>,
+    flag_denoise: bool,
+    flag_noisy: Option<String>,
+    flag_quiet: bool,
+}
+
+/// How often the image of a progressive render is written out.
+const WRITE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The width of the progress bar in characters.
+const PROGRESS_BAR_WIDTH: usize = 40;
+
+/// Draws the progress of the render as a bar on stderr, overwriting the
+/// previous one.
+fn print_progress(progress: &Progress) {
+    let filled = (progress.fraction() * PROGRESS_BAR_WIDTH as f32) as usize;
+    let bar = format!(
+        "{}{}",
+        "=".repeat(filled),
+        " ".repeat(PROGRESS_BAR_WIDTH - filled)
+    );
+    let eta = match progress.eta {
+        Some(eta) => format!("{}s", eta.as_secs()),
+        None => "-".to_string(),
+    };
+
+    let mut stderr = std::io::stderr();
+    // failing to draw the bar isn't worth stopping the render over
+    let _ = write!(
+        stderr,
+        "\r[{}] {:3.0}% {}/{} tiles, ETA {}   ",
+        bar,
+        progress.fraction() * 100.0,
+        progress.tiles_done,
+        progress.tiles_total,
+        eta
+    );
+    if progress.tiles_done == progress.tiles_total {
+        let _ = writeln!(stderr);
+    }
+    let _ = stderr.flush();
+}
+
+/// Draws the state of a progressive render on stderr, overwriting the
+/// previous one.
+fn print_pass(report: &PassReport) {
+    let noise = match report.noise {
+        Some(noise) => format!("{:.2}%", noise * 100.0),
+        None => "-".to_string(),
+    };
+
+    let mut stderr = std::io::stderr();
+    let _ = write!(
+        stderr,
+        "\rpass {}, {} samples per pixel, noise {}, {}s elapsed   ",
+        report.passes,
+        report.samples,
+        noise,
+        report.elapsed.as_secs()
+    );
+    let _ = stderr.flush();
+}
+
+/// The name of the image file of an output variable, next to the output
+/// file and in the same format, e.g. out/image.depth.exr for out/image.exr.
+fn aov_file_name(dest: &str, aov: Aov) -> String {
+    let path = std::path::Path::new(dest);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}.{}.{}", stem, aov.name(), ext),
+        None => format!("{}.{}", stem, aov.name()),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
 }
 
 /// The run function is called from 'main()'.
@@ -69,6 +250,87 @@ pub fn run(args: &Args) -> anyhow::Result<()> {
     let imgy: u32 = args.flag_height as u32;
     let samples_per_pixel = args.flag_samples as u32;
     let max_depth = args.flag_depth as u32;
+    let crop = match &args.flag_crop {
+        Some(crop) => Some(crop.parse::<CropWindow>().map_err(anyhow::Error::msg)?),
+        None => None,
+    };
+    let options = RenderOptions {
+        tile_size: args.flag_tile_size as u32,
+        tile_order: args.flag_tile_order,
+        crop,
+        progress: if args.flag_quiet {
+            None
+        } else {
+            Some(Arc::new(print_progress))
+        },
+        cancel: None,
+        sampler: args.flag_sampler,
+        first_sample: 0,
+        seed: args.flag_seed,
+        filter: match args.flag_filter_radius {
+            Some(radius) => Filter::new(args.flag_filter).with_radius(radius),
+            None => Filter::new(args.flag_filter),
+        },
+        adaptive: args.flag_adaptive.map(|threshold| AdaptiveSampling {
+            min_samples: args.flag_min_samples as u32,
+            threshold,
+        }),
+    };
+    let time_limit = match &args.flag_time {
+        Some(time) => Some(progressive::parse_duration(time).map_err(anyhow::Error::msg)?),
+        None => None,
+    };
+    let progressive = ProgressiveOptions {
+        samples_per_pass: args.flag_pass_samples as u32,
+        time_limit,
+        noise_target: args.flag_noise,
+        max_samples: args.flag_max_samples.map(|max| max as u32),
+    };
+    if let Some(noise) = progressive.noise_target {
+        if !noise.is_finite() || noise <= 0.0 {
+            anyhow::bail!("--noise must be greater than 0, not {}.", noise);
+        }
+        // the target may never be reached, the render needs another limit
+        if progressive.time_limit.is_none() && progressive.max_samples.is_none() {
+            anyhow::bail!("--noise needs --time or --max-samples as well.");
+        }
+    }
+    let aovs = match &args.flag_aov {
+        Some(aovs) => aov::parse_aovs(aovs).map_err(anyhow::Error::msg)?,
+        None => Vec::new(),
+    };
+    if !aovs.is_empty() && progressive.is_bounded() {
+        anyhow::bail!("AOVs can't be written with a progressive render.");
+    }
+    if args.flag_adaptive.is_some() && progressive.is_bounded() {
+        anyhow::bail!("A progressive render can't be sampled adaptively.");
+    }
+    if args.flag_adaptive.is_some() && args.flag_min_samples < 2 {
+        anyhow::bail!("--min-samples must be at least 2 with --adaptive.");
+    }
+    if args.flag_adaptive.is_some() && args.flag_min_samples > args.flag_samples {
+        anyhow::bail!("--min-samples can't be more than --samples.");
+    }
+    if args.flag_heatmap.is_some() && progressive.is_bounded() {
+        anyhow::bail!("A progressive render has no sample heatmap.");
+    }
+    if args.flag_denoise && progressive.is_bounded() {
+        anyhow::bail!("A progressive render can't be denoised.");
+    }
+    if args.flag_noisy.is_some() && !args.flag_denoise {
+        anyhow::bail!("--noisy only makes sense with --denoise.");
+    }
+
+    // the denoiser needs its features, even when they aren't written out
+    let mut render_aovs = aovs.clone();
+    if args.flag_denoise {
+        for feature in Denoiser::FEATURES {
+            if !render_aovs.contains(&feature) {
+                render_aovs.push(feature);
+            }
+        }
+    }
+
     let compute_env = match args.flag_compute {
         Some(s) => s,
         None => ComputeEnv::Multicore,
@@ -76,10 +338,10 @@ pub fn run(args: &Args) -> anyhow::Result<()> {
 
     let file_parser = FileReaderFactory::get_file_processor(&args.arg_source);
 
-    let world = match file_parser {
+    let mut world = match file_parser {
         Err(_) => {
             info!("Unable to find input file, creating default scene");
-            renderer::scene::world_builder::random_scene()
+            renderer::scene::world_builder::random_scene(args.flag_seed)
         }
         Ok(file) => file
             .process_file()
@@ -87,19 +349,63 @@ pub fn run(args: &Args) -> anyhow::Result<()> {
     };
     info!("World successfully built!");
 
-    info!("Output image buffer created.");
+    // the command line takes precedence over the scene file
+    if let Some(operator) = args.flag_tonemap {
+        world.tone_mapping.operator = operator;
+    }
+    if let Some(exposure) = args.flag_exposure {
+        world.tone_mapping.exposure = exposure;
+    }
+    if let Some(white_point) = args.flag_white_point {
+        world.tone_mapping.white_point = white_point;
+    }
+    if !world.tone_mapping.is_valid() {
+        anyhow::bail!(
+            "The white point must be greater than 0, not {}.",
+            world.tone_mapping.white_point
+        );
+    }
+    if let Some(integrator) = args.flag_integrator {
+        world.integrator.kind = integrator;
+    }
+    if let Some(ao_distance) = args.flag_ao_distance {
+        world.integrator.ao_distance = Some(ao_distance);
+    }
 
-    let mut pixels = vec![0; (imgx as usize) * (imgy as usize) * 3];
+    // find out about an unsupported output format before rendering
+    let image_writer = ImageWriterFactory::get_file_writer(&args.arg_dest, &world.tone_mapping)
+        .context(format!("Unable to write output file: {}", args.arg_dest))?;
 
-    renderer::render(
-        compute_env,
-        samples_per_pixel,
-        max_depth,
-        &world,
-        &mut pixels,
-        (imgx, imgy),
-    )
-    .context("Error encountered while rendering image")?;
+    let heatmap_writer = match &args.flag_heatmap {
+        Some(heatmap_file) => Some(
+            ImageWriterFactory::get_file_writer(heatmap_file, &ToneMapping::default())
+                .context(format!("Unable to write output file: {}", heatmap_file))?,
+        ),
+        None => None,
+    };
+
+    let noisy_writer = match &args.flag_noisy {
+        Some(noisy_file) => Some(
+            ImageWriterFactory::get_file_writer(noisy_file, &world.tone_mapping)
+                .context(format!("Unable to write output file: {}", noisy_file))?,
+        ),
+        None => None,
+    };
+
+    // the formats with layers hold the output variables themselves, the
+    // others get a file for each
+    let mut aov_writers = Vec::new();
+    for aov in aovs.iter().filter(|_| !image_writer.supports_layers()) {
+        let aov_file = aov_file_name(&args.arg_dest, *aov);
+        let writer = ImageWriterFactory::get_file_writer(&aov_file, &ToneMapping::default())
+            .context(format!("Unable to write output file: {}", aov_file))?;
+        aov_writers.push((aov_file, writer));
+    }
+
+    info!("Output image buffer created.");
+
+    let mut pixels = vec![0.0; (imgx as usize) * (imgy as usize) * 3];
+    let mut aov_buffers = vec![vec![0.0; pixels.len()]; render_aovs.len()];
 
     // create any directories for the output
     let path = std::path::Path::new(&args.arg_dest);
@@ -108,11 +414,132 @@ pub fn run(args: &Args) -> anyhow::Result<()> {
         std::fs::create_dir_all(prefix).unwrap();
     }
 
+    if progressive.is_bounded() {
+        // the progress bar would start over every pass
+        let options = RenderOptions {
+            progress: None,
+            ..options
+        };
+        let mut last_write = Instant::now();
+
+        let (report, reason) = renderer::render_progressive(
+            compute_env,
+            max_depth,
+            &world,
+            &mut pixels,
+            (imgx, imgy),
+            &options,
+            &progressive,
+            |report, pixels| {
+                if !args.flag_quiet {
+                    print_pass(report);
+                }
+                if last_write.elapsed() >= WRITE_INTERVAL {
+                    if let Err(e) = image_writer.write_image(pixels, (imgx, imgy)) {
+                        log::warn!("Unable to write the intermediate image: {}", e);
+                    }
+                    last_write = Instant::now();
+                }
+            },
+        )
+        .context("Error encountered while rendering image")?;
+
+        if !args.flag_quiet {
+            eprintln!();
+        }
+        if report.passes == 0 {
+            log::warn!("The time ran out before the first pass finished.");
+        }
+        info!(
+            "Progressive render stopped ({:?}) after {} passes, {} samples per pixel.",
+            reason, report.passes, report.samples
+        );
+    } else {
+        let mut sample_counts = vec![0; (imgx as usize) * (imgy as usize)];
+        let mut frame = FrameBuffer::new(&mut pixels, (imgx, imgy));
+        if args.flag_heatmap.is_some() {
+            frame = frame.with_sample_counts(&mut sample_counts);
+        }
+        for (aov, buffer) in render_aovs.iter().zip(aov_buffers.iter_mut()) {
+            frame = frame.with_aov(*aov, buffer);
+        }
+
+        renderer::render_frame(
+            compute_env,
+            samples_per_pixel,
+            max_depth,
+            &world,
+            &mut frame,
+            &options,
+        )
+        .context("Error encountered while rendering image")?;
+
+        if let (Some(heatmap_file), Some(writer)) = (&args.flag_heatmap, heatmap_writer) {
+            info!("Saving the sample heatmap to {}", heatmap_file);
+            let heatmap = heatmap::sample_heatmap(&sample_counts, samples_per_pixel);
+            writer
+                .write_image(&heatmap, (imgx, imgy))
+                .context(format!("Error saving image file {}", heatmap_file))?;
+        }
+
+        if args.flag_denoise {
+            if let (Some(noisy_file), Some(writer)) = (&args.flag_noisy, noisy_writer) {
+                info!("Saving the image before denoising to {}", noisy_file);
+                writer
+                    .write_image(&pixels, (imgx, imgy))
+                    .context(format!("Error saving image file {}", noisy_file))?;
+            }
+
+            let feature = |aov: Aov| {
+                let index = render_aovs.iter().position(|a| *a == aov).unwrap();
+                aov_buffers[index].as_slice()
+            };
+            let features = DenoiseFeatures {
+                albedo: feature(Aov::Albedo),
+                normal: feature(Aov::Normal),
+                depth: feature(Aov::Depth),
+                emission: feature(Aov::Emission),
+            };
+
+            let start = Instant::now();
+            pixels = Denoiser::default().denoise(&pixels, &features, (imgx, imgy));
+            info!("Denoising time: {:?}", start.elapsed());
+        }
+
+        for ((aov, buffer), (aov_file, aov_writer)) in
+            aovs.iter().zip(aov_buffers.iter()).zip(aov_writers)
+        {
+            info!("Saving the {} AOV to {}", aov.name(), aov_file);
+            let preview;
+            let buffer = if writer::is_hdr(&aov_file) {
+                buffer
+            } else {
+                preview = preview::aov_preview(*aov, buffer);
+                &preview
+            };
+            aov_writer
+                .write_image(buffer, (imgx, imgy))
+                .context(format!("Error saving image file {}", aov_file))?;
+        }
+    }
+
+    let layers: Vec<ImageLayer> = if image_writer.supports_layers() {
+        aovs.iter()
+            .zip(aov_buffers.iter())
+            .map(|(aov, buffer)| ImageLayer {
+                name: aov.name(),
+                channels: aov.channels(),
+                pixels: buffer,
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
     info!("Saving output to {}", args.arg_dest);
-    let output = File::create(&args.arg_dest)
+    image_writer
+        .write_layers(&pixels, &layers, (imgx, imgy))
         .context(format!("Error saving image file {}", args.arg_dest))?;
-    let encoder = PngEncoder::new(output);
-    encoder.encode(&pixels, imgx as u32, imgy as u32, ColorType::Rgb8)?;
 
     Ok(())
 }
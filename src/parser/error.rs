@@ -15,6 +15,14 @@ pub enum ParserError {
     #[error("Parser error. Check that the file meets the format requirements.")]
     FormatCorrupted { source: serde_json::error::Error },
 
+    /// A line in a text based scene file that couldn't be parsed.
+    #[error("Syntax error in {file} on line {line}: {message}")]
+    Syntax {
+        file: String,
+        line: usize,
+        message: String,
+    },
+
     /// Represents a file read error
     #[error(
         "Read error. Check that the file exists and that there are permissions to open the file."
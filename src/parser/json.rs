@@ -34,7 +34,7 @@ impl JSONSceneLoader {
 /// * `FileExtensionError` - A file extension that we have yet to add support for.
 /// * `SceneCorruptedError` - The scene file doesn't match the schema for scene specification.
 /// * `FormatCorruptedError` - The file cannot be parsed by the specific file specification.
-/// For example, fbx, json, xml, etc.
+///   For example, fbx, json, xml, etc.
 /// * `ReadError` - Represents a file read error
 /// * `IOError` - Represents any other io error
 impl SceneLoader for JSONSceneLoader {
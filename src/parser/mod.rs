@@ -1,10 +1,12 @@
 pub mod error;
 mod json;
+mod obj;
 
 use crate::parser::error::ParserError;
 use crate::renderer::scene::world::Region;
 
 use json::JSONSceneLoader;
+use obj::ObjSceneLoader;
 
 use log::{error, info};
 use std::ffi::OsStr;
@@ -16,7 +18,7 @@ type BoxResult<T> = Result<T, ParserError>;
 /// add a new arm to the extension matching block to add support for
 /// different file types.
 ///
-/// This module only supports a json file input.
+/// This module supports the custom json scene format and Wavefront obj files.
 ///
 /// # Arguments
 ///
@@ -36,7 +38,8 @@ type BoxResult<T> = Result<T, ParserError>;
 /// * `FileExtensionError` - A file extension that we have yet to add support for.
 /// * `SceneCorruptedError` - The scene file doesn't match the schema for scene specification.
 /// * `FormatCorruptedError` - The file cannot be parsed by the specific file specification.
-/// For example, fbx, json, xml, etc.
+///   For example, fbx, json, xml, etc.
+/// * `SyntaxError` - A line in a text format like obj or mtl couldn't be parsed.
 /// * `ReadError` - Represents a file read error
 /// * `IOError` - Represents any other io error
 ///
@@ -48,6 +51,7 @@ impl FileReaderFactory {
 
         match Path::new(filename).extension().and_then(OsStr::to_str) {
             Some("json") => Ok(Box::new(JSONSceneLoader::new(filename))),
+            Some("obj") => Ok(Box::new(ObjSceneLoader::new(filename))),
             _ => {
                 error!("Unknown file extension on the input file ");
                 Err(ParserError::FileExtension)
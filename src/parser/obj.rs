@@ -0,0 +1,651 @@
+use super::SceneLoader;
+
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::color::Color;
+use crate::renderer::core::vector::{self, Point3, Vec3};
+use crate::renderer::scene::camera::CameraConfig;
+use crate::renderer::scene::materials::Material;
+use crate::renderer::scene::objects::mesh::TriangleMesh;
+use crate::renderer::scene::world::Region;
+
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::parser::error::ParserError;
+use crate::parser::BoxResult;
+
+/// The sky color used for scenes loaded from obj files since they
+/// don't specify one.
+const DEFAULT_BACKGROUND: Color = Color {
+    x: 0.5,
+    y: 0.7,
+    z: 1.0,
+};
+
+/// The albedo used for faces without a material.
+const DEFAULT_ALBEDO: Color = Color {
+    x: 0.8,
+    y: 0.8,
+    z: 0.8,
+};
+
+/// The vertical field of view of the generated camera.
+const DEFAULT_FOV: f32 = 40.0;
+
+pub struct ObjSceneLoader {
+    filename: String,
+}
+
+impl ObjSceneLoader {
+    pub fn new(filename: &str) -> ObjSceneLoader {
+        ObjSceneLoader {
+            filename: filename.to_string(),
+        }
+    }
+}
+
+/// Scene parser for a Wavefront obj file and its mtl material libraries.
+///
+/// Each object, group, and material change in the file becomes its own
+/// triangle mesh. Polygons are triangulated as fans. Since obj files have
+/// no camera, one is placed to frame the bounds of the geometry.
+///
+/// The mtl materials are mapped onto the renderer's materials:
+/// * Materials with a `Ke` emission become emissive lights.
+/// * Transparent materials (`d` < 1, `Tr` > 0, or illum 4, 6, 7) become
+///   dielectrics with the `Ni` index of refraction.
+/// * Reflective materials (illum 3, 5, or a `Ks` brighter than `Kd`) become
+///   metal with the `Ks` albedo and a fuzz derived from the `Ns` exponent.
+/// * Everything else is a lambert with the `Kd` albedo.
+///
+/// # Errors
+///
+/// * `FileNotFound` - The obj file doesn't exist.
+/// * `Syntax` - A line in the obj or mtl file couldn't be parsed. The error
+///   contains the file name and line number.
+/// * `SceneCorrupted` - The file contained no faces.
+/// * `IOError` - Represents any other io error
+impl SceneLoader for ObjSceneLoader {
+    fn process_file(&self) -> BoxResult<Region> {
+        info!("Parsing obj filename {}", self.filename);
+        let path = Path::new(&self.filename);
+        if !path.exists() {
+            error!("World input file does not exist at {}", self.filename);
+            return Err(ParserError::FileNotFound);
+        }
+
+        let contents = fs::read_to_string(path)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        parse_obj(&contents, &self.filename, |mtllib| {
+            let mtl_path = base_dir.join(mtllib);
+            let mtl_name = mtl_path.to_string_lossy().to_string();
+            match fs::read_to_string(&mtl_path) {
+                Ok(contents) => parse_mtl(&contents, &mtl_name).map(Some),
+                Err(e) => {
+                    warn!("Unable to read material library {}: {}", mtl_name, e);
+                    Ok(None)
+                }
+            }
+        })
+    }
+}
+
+/// The mtl properties used to choose a material.
+#[derive(Clone, Debug)]
+struct MtlDesc {
+    diffuse: Color,
+    specular: Color,
+    emission: Color,
+    shininess: f32,
+    ior: f32,
+    dissolve: f32,
+    illum: u32,
+}
+
+impl Default for MtlDesc {
+    fn default() -> Self {
+        MtlDesc {
+            diffuse: DEFAULT_ALBEDO,
+            specular: Color::new(0.0, 0.0, 0.0),
+            emission: Color::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            ior: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+}
+
+impl MtlDesc {
+    fn to_material(&self) -> Material {
+        let transparent = self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7);
+        let reflective = matches!(self.illum, 3 | 5)
+            || max_component(&self.specular) > max_component(&self.diffuse);
+
+        if max_component(&self.emission) > 0.0 {
+            Material::Emissive {
+                color: self.emission,
+                intensity: 1.0,
+            }
+        } else if transparent {
+            Material::Dielectric { ior: self.ior }
+        } else if reflective {
+            // map the phong exponent to a roughness, higher is shinier
+            let fuzz = (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt();
+            Material::Metal {
+                albedo: self.specular.into(),
+                fuzz: fuzz.into(),
+            }
+        } else {
+            Material::Lambert {
+                albedo: self.diffuse.into(),
+            }
+        }
+    }
+}
+
+fn max_component(c: &Color) -> f32 {
+    c.x.max(c.y).max(c.z)
+}
+
+/// A face corner referencing the position, uv, and normal buffers.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+struct FaceVertex {
+    v: usize,
+    vt: Option<usize>,
+    vn: Option<usize>,
+}
+
+/// The triangles sharing the same group and material.
+#[derive(Default)]
+struct FaceGroup {
+    name: String,
+    material: Option<String>,
+    triangles: Vec<[FaceVertex; 3]>,
+}
+
+fn syntax_error(file: &str, line: usize, message: String) -> ParserError {
+    ParserError::Syntax {
+        file: file.to_string(),
+        line,
+        message,
+    }
+}
+
+/// Parses the floats following the keyword, requiring between min and
+/// max values.
+fn parse_floats(
+    tokens: &[&str],
+    min: usize,
+    max: usize,
+    file: &str,
+    line: usize,
+) -> BoxResult<Vec<f32>> {
+    if tokens.len() < min || tokens.len() > max {
+        return Err(syntax_error(
+            file,
+            line,
+            format!("expected {} to {} values, found {}", min, max, tokens.len()),
+        ));
+    }
+
+    tokens
+        .iter()
+        .map(|t| {
+            t.parse::<f32>()
+                .map_err(|_| syntax_error(file, line, format!("invalid number '{}'", t)))
+        })
+        .collect()
+}
+
+/// Resolves a one based, possibly negative (relative to the end), obj index.
+fn resolve_index(token: &str, count: usize, file: &str, line: usize) -> BoxResult<usize> {
+    let index: i64 = token
+        .parse()
+        .map_err(|_| syntax_error(file, line, format!("invalid index '{}'", token)))?;
+
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as i64 + index
+    };
+
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(syntax_error(
+            file,
+            line,
+            format!("index {} is out of range", index),
+        ));
+    }
+
+    Ok(resolved as usize)
+}
+
+/// Parses the obj file contents into a region. The material libraries are
+/// loaded through `load_mtl`, which returns None for libraries it can't find.
+fn parse_obj<F>(contents: &str, file: &str, mut load_mtl: F) -> BoxResult<Region>
+where
+    F: FnMut(&str) -> BoxResult<Option<HashMap<String, MtlDesc>>>,
+{
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut uvs: Vec<(f32, f32)> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut materials: HashMap<String, MtlDesc> = HashMap::new();
+
+    let mut groups: Vec<FaceGroup> = Vec::new();
+    let mut current = FaceGroup::default();
+
+    for (n, raw_line) in contents.lines().enumerate() {
+        let line = n + 1;
+        let content = raw_line.split('#').next().unwrap_or("");
+        let tokens: Vec<&str> = content.split_whitespace().collect();
+        let (keyword, args) = match tokens.split_first() {
+            Some((k, a)) => (*k, a),
+            None => continue,
+        };
+
+        match keyword {
+            "v" => {
+                let v = parse_floats(args, 3, 4, file, line)?;
+                positions.push(Point3::new(v[0], v[1], v[2]));
+            }
+            "vt" => {
+                let v = parse_floats(args, 1, 3, file, line)?;
+                uvs.push((v[0], *v.get(1).unwrap_or(&0.0)));
+            }
+            "vn" => {
+                let v = parse_floats(args, 3, 3, file, line)?;
+                normals.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(syntax_error(
+                        file,
+                        line,
+                        "a face needs at least 3 vertices".to_string(),
+                    ));
+                }
+
+                let mut corners = Vec::with_capacity(args.len());
+                for arg in args.iter() {
+                    let mut parts = arg.split('/');
+                    let v = resolve_index(parts.next().unwrap_or(""), positions.len(), file, line)?;
+                    let vt = match parts.next() {
+                        Some(t) if !t.is_empty() => Some(resolve_index(t, uvs.len(), file, line)?),
+                        _ => None,
+                    };
+                    let vn = match parts.next() {
+                        Some(t) if !t.is_empty() => {
+                            Some(resolve_index(t, normals.len(), file, line)?)
+                        }
+                        _ => None,
+                    };
+                    corners.push(FaceVertex { v, vt, vn });
+                }
+
+                // triangulate the polygon as a fan around the first corner
+                for i in 1..corners.len() - 1 {
+                    current
+                        .triangles
+                        .push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "o" | "g" => {
+                let material = current.material.clone();
+                groups.push(std::mem::take(&mut current));
+                current.name = args.join(" ");
+                current.material = material;
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                if !materials.contains_key(&name) {
+                    warn!(
+                        "{}:{}: unknown material '{}', using the default",
+                        file, line, name
+                    );
+                }
+                let group_name = current.name.clone();
+                groups.push(std::mem::take(&mut current));
+                current.name = group_name;
+                current.material = Some(name);
+            }
+            "mtllib" => {
+                for lib in args.iter() {
+                    if let Some(lib_materials) = load_mtl(lib)? {
+                        materials.extend(lib_materials);
+                    }
+                }
+            }
+            // smoothing groups, lines, and points don't affect the render.
+            "s" | "l" | "p" => {}
+            _ => warn!(
+                "{}:{}: ignoring unsupported keyword '{}'",
+                file, line, keyword
+            ),
+        }
+    }
+    groups.push(current);
+
+    let mut world = Region::new(DEFAULT_BACKGROUND);
+    let mut bounds = Aabb::empty();
+
+    for group in groups.iter().filter(|g| !g.triangles.is_empty()) {
+        let desc = group
+            .material
+            .as_ref()
+            .and_then(|m| materials.get(m))
+            .cloned()
+            .unwrap_or_default();
+
+        let mesh = build_mesh(group, &positions, &uvs, &normals, desc.to_material()).map_err(
+            |message| {
+                error!("Unable to build group '{}': {}", group.name, message);
+                ParserError::SceneCorrupted
+            },
+        )?;
+
+        info!(
+            "Loaded group '{}' with {} triangles",
+            group.name,
+            mesh.len()
+        );
+
+        let mut mesh_bounds = Aabb::empty();
+        for p in mesh.positions.iter() {
+            mesh_bounds.expand_point(*p);
+        }
+        bounds.expand(mesh_bounds);
+
+        world.push(Box::new(mesh));
+    }
+
+    if world.objects.is_empty() {
+        error!("No faces found in {}", file);
+        return Err(ParserError::SceneCorrupted);
+    }
+
+    world.camera_config = frame_bounds(&bounds);
+    world.recalculate_bounds();
+
+    Ok(world)
+}
+
+/// Builds an indexed mesh for the group, merging the face corners that
+/// reference the same position, uv, and normal into a single vertex. If any
+/// corner lacks a normal, the whole mesh is flat shaded.
+fn build_mesh(
+    group: &FaceGroup,
+    positions: &[Point3],
+    uvs: &[(f32, f32)],
+    normals: &[Vec3],
+    material: Material,
+) -> Result<TriangleMesh, String> {
+    let smooth = group.triangles.iter().flatten().all(|c| c.vn.is_some());
+    let textured = group.triangles.iter().flatten().any(|c| c.vt.is_some());
+
+    let mut vertex_map: HashMap<FaceVertex, u32> = HashMap::new();
+    let mut mesh_positions = Vec::new();
+    let mut mesh_normals = Vec::new();
+    let mut mesh_uvs = Vec::new();
+    let mut indices = Vec::with_capacity(group.triangles.len());
+
+    for triangle in group.triangles.iter() {
+        let mut tri_indices = [0_u32; 3];
+        for (i, corner) in triangle.iter().enumerate() {
+            let key = FaceVertex {
+                v: corner.v,
+                vt: if textured { corner.vt } else { None },
+                vn: if smooth { corner.vn } else { None },
+            };
+
+            tri_indices[i] = *vertex_map.entry(key).or_insert_with(|| {
+                mesh_positions.push(positions[key.v]);
+                if smooth {
+                    mesh_normals.push(normals[key.vn.unwrap_or_default()]);
+                }
+                if textured {
+                    mesh_uvs.push(key.vt.map(|vt| uvs[vt]).unwrap_or((0.0, 0.0)));
+                }
+                (mesh_positions.len() - 1) as u32
+            });
+        }
+        indices.push(tri_indices);
+    }
+
+    TriangleMesh::new(mesh_positions, mesh_normals, mesh_uvs, indices, material)
+}
+
+/// Creates a camera looking at the center of the bounds from the front and
+/// slightly above, far enough back to fit the whole bounding sphere.
+fn frame_bounds(bounds: &Aabb) -> CameraConfig {
+    let center = bounds.centroid();
+    let radius = f32::max(0.5 * (bounds.box_max - bounds.box_min).length(), 1e-3);
+
+    let half_fov = 0.5 * DEFAULT_FOV.to_radians();
+    let distance = 1.1 * radius / half_fov.sin();
+
+    let direction = vector::unit_vector(&Vec3::new(0.0, 0.25, 1.0));
+
+    CameraConfig {
+        look_from: center + distance * direction,
+        look_at: center,
+        up: Vec3::new(0.0, 1.0, 0.0),
+        vertical_fov: DEFAULT_FOV,
+        aperture: 0.0,
+        focal_distance: distance,
+        shutter_open: 0.0,
+        shutter_close: 0.0,
+    }
+}
+
+/// Parses the contents of a mtl material library.
+fn parse_mtl(contents: &str, file: &str) -> BoxResult<HashMap<String, MtlDesc>> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlDesc)> = None;
+
+    for (n, raw_line) in contents.lines().enumerate() {
+        let line = n + 1;
+        let content = raw_line.split('#').next().unwrap_or("");
+        let tokens: Vec<&str> = content.split_whitespace().collect();
+        let (keyword, args) = match tokens.split_first() {
+            Some((k, a)) => (*k, a),
+            None => continue,
+        };
+
+        if keyword == "newmtl" {
+            if let Some((name, desc)) = current.take() {
+                materials.insert(name, desc);
+            }
+            current = Some((args.join(" "), MtlDesc::default()));
+            continue;
+        }
+
+        let desc = match current.as_mut() {
+            Some((_, desc)) => desc,
+            None => {
+                return Err(syntax_error(
+                    file,
+                    line,
+                    format!("'{}' found before newmtl", keyword),
+                ))
+            }
+        };
+
+        match keyword {
+            "Kd" => {
+                let v = parse_floats(args, 3, 3, file, line)?;
+                desc.diffuse = Color::new(v[0], v[1], v[2]);
+            }
+            "Ks" => {
+                let v = parse_floats(args, 3, 3, file, line)?;
+                desc.specular = Color::new(v[0], v[1], v[2]);
+            }
+            "Ke" => {
+                let v = parse_floats(args, 3, 3, file, line)?;
+                desc.emission = Color::new(v[0], v[1], v[2]);
+            }
+            "Ns" => desc.shininess = parse_floats(args, 1, 1, file, line)?[0],
+            "Ni" => desc.ior = parse_floats(args, 1, 1, file, line)?[0],
+            "d" => desc.dissolve = parse_floats(args, 1, 1, file, line)?[0],
+            "Tr" => desc.dissolve = 1.0 - parse_floats(args, 1, 1, file, line)?[0],
+            "illum" => {
+                desc.illum = args
+                    .first()
+                    .and_then(|t| t.parse().ok())
+                    .ok_or_else(|| syntax_error(file, line, "invalid illum".to_string()))?;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some((name, desc)) = current {
+        materials.insert(name, desc);
+    }
+
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::FileReaderFactory;
+    use crate::renderer::core::ray::Ray;
+    use crate::renderer::scene::hittable::Hittable;
+    use crate::renderer::scene::textures::Texture;
+
+    fn no_mtl(_: &str) -> BoxResult<Option<HashMap<String, MtlDesc>>> {
+        Ok(None)
+    }
+
+    #[test]
+    fn test_parse_quad() {
+        let obj = "
+            # a quad split into two triangles
+            v -1 -1 -1
+            v 1 -1 -1
+            v 1 1 -1
+            v -1 1 -1
+            vn 0 0 1
+            f 1//1 2//1 3//1 4//1
+        ";
+
+        let world = parse_obj(obj, "quad.obj", no_mtl).unwrap();
+        assert_eq!(world.objects.len(), 1);
+
+        let r = Ray::new(Point3::new(0.5, -0.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = world.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert_eq!(rec.t, 1.0);
+        assert_eq!(rec.normal.z, 1.0);
+
+        // the camera has to look at the center of the quad
+        let c = world.camera_config;
+        assert_eq!(c.look_at.x, 0.0);
+        assert_eq!(c.look_at.y, 0.0);
+        assert_eq!(c.look_at.z, -1.0);
+        assert!(c.look_from.z > 0.0);
+    }
+
+    #[test]
+    fn test_parse_groups_and_negative_indices() {
+        let obj = "
+            o first
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            f -3 -2 -1
+            g second
+            v 0 0 1
+            v 1 0 1
+            v 0 1 1
+            f 4 5 6
+        ";
+
+        let world = parse_obj(obj, "groups.obj", no_mtl).unwrap();
+        assert_eq!(world.objects.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n";
+        match parse_obj(obj, "bad.obj", no_mtl) {
+            Err(ParserError::Syntax { file, line, .. }) => {
+                assert_eq!(file, "bad.obj");
+                assert_eq!(line, 4);
+            }
+            _ => panic!("expected a syntax error"),
+        }
+
+        let obj = "v 0 0 0\nv 1 zero 0\n";
+        match parse_obj(obj, "bad.obj", no_mtl) {
+            Err(ParserError::Syntax { line, .. }) => assert_eq!(line, 2),
+            _ => panic!("expected a syntax error"),
+        }
+
+        assert!(matches!(
+            parse_obj("v 0 0 0\n", "empty.obj", no_mtl),
+            Err(ParserError::SceneCorrupted)
+        ));
+    }
+
+    #[test]
+    fn test_parse_mtl() {
+        let mtl = "
+            newmtl red
+            Kd 0.8 0.1 0.1
+            Ks 0.1 0.1 0.1
+            newmtl chrome
+            Kd 0.1 0.1 0.1
+            Ks 0.9 0.9 0.9
+            Ns 1000
+            newmtl glass
+            Ni 1.33
+            d 0.2
+        ";
+
+        let materials = parse_mtl(mtl, "test.mtl").unwrap();
+        assert_eq!(materials.len(), 3);
+
+        match materials["red"].to_material() {
+            Material::Lambert {
+                albedo: Texture::Constant(albedo),
+            } => assert_eq!(albedo.x, 0.8),
+            m => panic!("unexpected material {:?}", m),
+        }
+        match materials["chrome"].to_material() {
+            Material::Metal {
+                albedo: Texture::Constant(albedo),
+                fuzz: Texture::Scalar(fuzz),
+            } => {
+                assert_eq!(albedo.x, 0.9);
+                assert!(fuzz < 0.1);
+            }
+            m => panic!("unexpected material {:?}", m),
+        }
+        match materials["glass"].to_material() {
+            Material::Dielectric { ior } => assert_eq!(ior, 1.33),
+            m => panic!("unexpected material {:?}", m),
+        }
+
+        match parse_mtl("Kd 1 1 1\n", "orphan.mtl") {
+            Err(ParserError::Syntax { line, .. }) => assert_eq!(line, 1),
+            _ => panic!("expected a syntax error"),
+        }
+    }
+
+    #[test]
+    fn test_load_obj_file() {
+        let file_parser = FileReaderFactory::get_file_processor("test_input/cube.obj").unwrap();
+        let world = file_parser.process_file().unwrap();
+
+        // one mesh per material
+        assert_eq!(world.objects.len(), 2);
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = world.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert_eq!(rec.t, 4.0);
+        assert!(matches!(rec.material, Material::Metal { .. }));
+    }
+}
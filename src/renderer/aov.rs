@@ -0,0 +1,269 @@
+//! Arbitrary output variables (AOVs), the extra images a render can write
+//! besides the final color, like the normals or the depth of the surfaces
+//! the camera sees. Most are taken from the first hit of every camera ray,
+//! so they're free of noise and useful for finding problems with the
+//! geometry of a scene, or for compositing. The light components split the
+//! final color up by the way the light got to the camera, as worked out by
+//! the integrator.
+//!
+//! The `Aov` enum is the registry of the variables: the names they go by
+//! on the command line and in the files, their channels and how their
+//! values are found.
+
+use std::str::FromStr;
+
+use crate::renderer::core::color::{self, Color};
+use crate::renderer::core::ray::Ray;
+use crate::renderer::integrator::LightComponents;
+use crate::renderer::scene::hittable::HitRecord;
+use crate::renderer::scene::world::Region;
+
+/// The output variables to choose from. All of them are three floats per
+/// pixel, and 0 where the camera ray doesn't hit anything.
+///
+/// * `Normal` - The shading normal, facing the camera.
+/// * `GeometricNormal` - The normal of the actual surface, facing the
+///   camera. Differs from the shading normal on smooth shaded meshes.
+/// * `Depth` - The distance from the camera, in all three channels.
+/// * `Albedo` - The color of the surface, regardless of the lighting.
+/// * `ObjectId` - The index of the object in the scene plus one, in all
+///   three channels.
+/// * `MaterialId` - The id of the material, see `Material::id()`, in all
+///   three channels.
+/// * `Uv` - The texture coordinates, in the red and green channels.
+/// * `Alpha` - 1 where the camera ray hits something, in all three
+///   channels. Filtered, so the edges of the objects are in between.
+/// * `DirectDiffuse`, `IndirectDiffuse`, `Specular`, `Emission`,
+///   `Background` - The components of the final color, see
+///   `LightComponents`. Unlike the others, they're noisy like the color.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Aov {
+    Normal,
+    GeometricNormal,
+    Depth,
+    Albedo,
+    ObjectId,
+    MaterialId,
+    Uv,
+    Alpha,
+    DirectDiffuse,
+    IndirectDiffuse,
+    Specular,
+    Emission,
+    Background,
+}
+
+impl Aov {
+    /// All of the output variables.
+    pub const ALL: [Aov; 13] = [
+        Aov::Normal,
+        Aov::GeometricNormal,
+        Aov::Depth,
+        Aov::Albedo,
+        Aov::ObjectId,
+        Aov::MaterialId,
+        Aov::Uv,
+        Aov::Alpha,
+        Aov::DirectDiffuse,
+        Aov::IndirectDiffuse,
+        Aov::Specular,
+        Aov::Emission,
+        Aov::Background,
+    ];
+
+    /// The name of the variable, as used on the command line and in the
+    /// names of the image files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Normal => "normal",
+            Aov::GeometricNormal => "geometric-normal",
+            Aov::Depth => "depth",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "object-id",
+            Aov::MaterialId => "material-id",
+            Aov::Uv => "uv",
+            Aov::Alpha => "alpha",
+            Aov::DirectDiffuse => "direct-diffuse",
+            Aov::IndirectDiffuse => "indirect-diffuse",
+            Aov::Specular => "specular",
+            Aov::Emission => "emission",
+            Aov::Background => "background",
+        }
+    }
+
+    /// The names of the channels of the variable when it's a layer of a
+    /// multi-layer image, for the first values of the pixels. The others
+    /// are left out.
+    pub fn channels(self) -> &'static [&'static str] {
+        match self {
+            Aov::Normal | Aov::GeometricNormal => &["X", "Y", "Z"],
+            Aov::Depth => &["Z"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+            Aov::Uv => &["U", "V"],
+            Aov::Alpha => &["A"],
+            _ => &["R", "G", "B"],
+        }
+    }
+
+    /// Whether the variable is taken from the first hit of the camera ray,
+    /// rather than the light the integrator found.
+    pub fn needs_hit(self) -> bool {
+        !matches!(
+            self,
+            Aov::DirectDiffuse
+                | Aov::IndirectDiffuse
+                | Aov::Specular
+                | Aov::Emission
+                | Aov::Background
+        )
+    }
+
+    /// Whether the samples are weighted into the pixels with the
+    /// reconstruction filter like the final color. The ids aren't, a blend
+    /// of two ids is meaningless, so their pixels keep the id of the sample
+    /// closest to the center.
+    pub fn is_filtered(self) -> bool {
+        !matches!(self, Aov::ObjectId | Aov::MaterialId)
+    }
+
+    /// The value of the variable for a camera ray.
+    ///
+    /// Parameters:
+    /// * `r` - The camera ray.
+    /// * `hit` - The index of the object the ray hit first and the hit, or
+    ///   None if it didn't hit anything. Only needed if `needs_hit()`.
+    /// * `light` - The light of the ray, split up by the integrator.
+    /// * `world` - The scene, which has the ids of the materials.
+    pub fn value(
+        self,
+        r: &Ray,
+        hit: Option<&(usize, HitRecord)>,
+        light: &LightComponents,
+        world: &Region,
+    ) -> Color {
+        match self {
+            Aov::DirectDiffuse => return light.direct_diffuse,
+            Aov::IndirectDiffuse => return light.indirect_diffuse,
+            Aov::Specular => return light.specular,
+            Aov::Emission => return light.emission,
+            Aov::Background => return light.background,
+            _ => {}
+        }
+
+        let (object, rec) = match hit {
+            Some((object, rec)) => (*object, rec),
+            None => return color::BLACK,
+        };
+
+        match self {
+            Aov::Normal => rec.normal,
+            Aov::GeometricNormal => rec.geometric_normal,
+            Aov::Depth => {
+                let depth = rec.t * r.dir.length();
+                Color::new(depth, depth, depth)
+            }
+            Aov::Albedo => rec.material.albedo(rec),
+            Aov::ObjectId => {
+                let id = (object + 1) as f32;
+                Color::new(id, id, id)
+            }
+            Aov::MaterialId => {
+                let id = world.material_id(rec.material) as f32;
+                Color::new(id, id, id)
+            }
+            Aov::Uv => Color::new(rec.uv.0, rec.uv.1, 0.0),
+            Aov::Alpha => color::WHITE,
+            _ => unreachable!("{:?} doesn't come from the hit", self),
+        }
+    }
+}
+
+impl FromStr for Aov {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Aov, String> {
+        Aov::ALL
+            .iter()
+            .find(|aov| aov.name() == s.trim())
+            .copied()
+            .ok_or_else(|| {
+                let names: Vec<&str> = Aov::ALL.iter().map(|aov| aov.name()).collect();
+                format!("Unknown AOV {}, expected one of {}", s, names.join(", "))
+            })
+    }
+}
+
+/// Parses a comma separated list of output variables, like
+/// `normal,depth,albedo`.
+pub fn parse_aovs(s: &str) -> Result<Vec<Aov>, String> {
+    let mut aovs = Vec::new();
+    for aov in s.split(',').map(|name| name.parse::<Aov>()) {
+        let aov = aov?;
+        if !aovs.contains(&aov) {
+            aovs.push(aov);
+        }
+    }
+    Ok(aovs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::vector::{Point3, Vec3};
+    use crate::renderer::scene::materials::Material;
+
+    #[test]
+    fn test_parse_aovs() {
+        assert_eq!(
+            parse_aovs("normal, depth,normal"),
+            Ok(vec![Aov::Normal, Aov::Depth])
+        );
+        assert_eq!(parse_aovs("object-id"), Ok(vec![Aov::ObjectId]));
+        assert!(parse_aovs("normals").is_err());
+        assert!(parse_aovs("").is_err());
+
+        for aov in Aov::ALL {
+            assert_eq!(aov.name().parse::<Aov>(), Ok(aov));
+        }
+    }
+
+    #[test]
+    fn test_aov_value() {
+        let material = Material::Lambert {
+            albedo: Color::new(0.2, 0.4, 0.6).into(),
+        };
+        let rec = HitRecord {
+            p: Point3::new(0.0, 0.0, -4.0),
+            normal: Vec3::new(0.0, 0.6, 0.8),
+            geometric_normal: Vec3::new(0.0, 0.0, 1.0),
+            t: 2.0,
+            front_face: true,
+            uv: (0.25, 0.75),
+            material: &material,
+        };
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -2.0));
+        let hit = Some((3, rec));
+        let light = LightComponents {
+            specular: Color::new(0.5, 0.5, 0.5),
+            ..LightComponents::default()
+        };
+        let world = Region::new(Color::new(0.5, 0.7, 1.0));
+        let value = |aov: Aov| aov.value(&r, hit.as_ref(), &light, &world);
+
+        assert_eq!(value(Aov::Normal).y, 0.6);
+        assert_eq!(value(Aov::GeometricNormal).z, 1.0);
+        assert_eq!(value(Aov::Depth).x, 4.0);
+        assert_eq!(value(Aov::Albedo).z, 0.6);
+        assert_eq!(value(Aov::ObjectId).x, 4.0);
+        assert_eq!(value(Aov::MaterialId).x, material.id() as f32);
+        assert_eq!(value(Aov::Uv).y, 0.75);
+        assert_eq!(value(Aov::Alpha).x, 1.0);
+        assert_eq!(value(Aov::Specular).x, 0.5);
+        assert_eq!(value(Aov::DirectDiffuse).x, 0.0);
+
+        let no_light = LightComponents::default();
+        for aov in Aov::ALL {
+            assert_eq!(aov.value(&r, None, &no_light, &world).x, 0.0);
+        }
+    }
+}
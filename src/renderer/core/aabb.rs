@@ -34,10 +34,89 @@ impl Aabb {
         self.box_max.z = max!(other.box_max.z, self.box_max.z);
     }
 
+    /// Returns the box where this box and the other overlap, which is
+    /// inverted if they don't overlap.
+    pub fn intersect(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            box_min: Point3::new(
+                max!(self.box_min.x, other.box_min.x),
+                max!(self.box_min.y, other.box_min.y),
+                max!(self.box_min.z, other.box_min.z),
+            ),
+            box_max: Point3::new(
+                min!(self.box_max.x, other.box_max.x),
+                min!(self.box_max.y, other.box_max.y),
+                min!(self.box_max.z, other.box_max.z),
+            ),
+        }
+    }
+
+    /// Creates an empty (inverted) bounding box. Expanding an empty box by
+    /// another box results in the other box.
+    pub fn empty() -> Aabb {
+        Aabb {
+            box_min: Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            box_max: Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        }
+    }
+
+    /// Creates a bounding box around all of space, for objects like
+    /// infinite planes that can't be bounded.
+    pub fn infinite() -> Aabb {
+        Aabb {
+            box_min: Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+            box_max: Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+        }
+    }
+
+    /// Returns whether all of the corners of the box are finite. Infinite
+    /// and empty boxes can't be put into acceleration structures.
+    pub fn is_finite(&self) -> bool {
+        self.box_min.x.is_finite()
+            && self.box_min.y.is_finite()
+            && self.box_min.z.is_finite()
+            && self.box_max.x.is_finite()
+            && self.box_max.y.is_finite()
+            && self.box_max.z.is_finite()
+    }
+
+    /// Expands the bounding box to include the point p.
+    pub fn expand_point(&mut self, p: Point3) {
+        self.expand(Aabb::new(p, p));
+    }
+
+    /// Returns the center point of the box.
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.box_min + self.box_max)
+    }
+
+    /// Returns the surface area of the box, or 0 if the box is empty. Used
+    /// by the surface area heuristic when building acceleration structures.
+    pub fn surface_area(&self) -> f32 {
+        let d = self.box_max - self.box_min;
+        if d.x < 0.0 || d.y < 0.0 || d.z < 0.0 {
+            return 0.0;
+        }
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// Returns the index of the axis along which the box is the longest.
+    /// 0 is x, 1 is y, and 2 is z.
+    pub fn longest_axis(&self) -> usize {
+        let d = self.box_max - self.box_min;
+        if d.x > d.y && d.x > d.z {
+            0
+        } else if d.y > d.z {
+            1
+        } else {
+            2
+        }
+    }
+
     /// Returns whether the ray will intersect the bounding box.
     pub fn hit(&self, r: &Ray, t: f32) -> bool {
-        let tx1 = (self.box_min.x - r.orig.y) * r.invdir.y;
-        let tx2 = (self.box_max.x - r.orig.y) * r.invdir.y;
+        let tx1 = (self.box_min.x - r.orig.x) * r.invdir.x;
+        let tx2 = (self.box_max.x - r.orig.x) * r.invdir.x;
 
         let mut tmin = min!(tx1, tx2);
         let mut tmax = max!(tx1, tx2);
@@ -108,5 +187,44 @@ mod tests {
         assert!(b1.hit(&r, f32::INFINITY));
         let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(10.0, 10.0, -1.0));
         assert!(!b1.hit(&r, f32::INFINITY));
+
+        // the x slab has to use the x components of the ray
+        let b1 = Aabb::new(Point3::new(2.0, -1.0, -1.0), Point3::new(3.0, 1.0, 1.0));
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(b1.hit(&r, f32::INFINITY));
+        assert!(!b1.hit(&r, 1.0));
+        let r = Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(!b1.hit(&r, f32::INFINITY));
+    }
+
+    #[test]
+    fn test_box_metrics() {
+        let mut b = Aabb::empty();
+        assert_eq!(b.surface_area(), 0.0);
+
+        b.expand_point(Point3::new(1.0, 2.0, 3.0));
+        b.expand_point(Point3::new(-1.0, 0.0, 7.0));
+
+        assert_eq!(b.surface_area(), 2.0 * (2.0 * 2.0 + 2.0 * 4.0 + 4.0 * 2.0));
+        assert_eq!(b.longest_axis(), 2);
+
+        let c = b.centroid();
+        assert_eq!(c.x, 0.0);
+        assert_eq!(c.y, 1.0);
+        assert_eq!(c.z, 5.0);
+        assert!(b.is_finite());
+        assert!(!Aabb::empty().is_finite());
+        assert!(!Aabb::infinite().is_finite());
+
+        let overlap = b.intersect(&Aabb::new(
+            Point3::new(0.0, -5.0, 4.0),
+            Point3::new(5.0, 1.0, 5.0),
+        ));
+        assert_eq!(overlap.box_min.x, 0.0);
+        assert_eq!(overlap.box_max.y, 1.0);
+        assert_eq!(overlap.box_min.z, 4.0);
+        assert_eq!(overlap.box_max.z, 5.0);
+        let everywhere = b.intersect(&Aabb::infinite());
+        assert_eq!(everywhere.surface_area(), b.surface_area());
     }
 }
@@ -24,6 +24,13 @@ pub fn lerp(from: Color, to: Color, t: f32) -> Color {
     (1.0 - t) * from + t * to
 }
 
+/// The relative luminance of a linear rgb color, with the Rec. 709
+/// primaries.
+#[inline]
+pub fn luminance(c: Color) -> f32 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
 /// Default trait implmentation for a color. Returns white.
 impl Default for Color {
     fn default() -> Self {
@@ -69,4 +76,15 @@ mod tests {
         assert!(approx_equal(c1.y, 0.25, 4));
         assert!(approx_equal(c1.z, 0.5, 4));
     }
+
+    #[test]
+    fn test_luminance() {
+        assert!(approx_equal(luminance(WHITE), 1.0, 4));
+        assert_eq!(luminance(BLACK), 0.0);
+        assert!(approx_equal(
+            luminance(Color::new(0.0, 1.0, 0.0)),
+            0.7152,
+            4
+        ));
+    }
 }
@@ -4,6 +4,8 @@
 pub mod color;
 /// A library containing a mathematical ray with an origin and direction.
 pub mod ray;
+/// A library containing 4x4 matrices and affine transforms.
+pub mod transform;
 /// A library containing the representation of a 3D vector object.
 pub mod vector;
 
@@ -34,7 +36,9 @@ pub fn clamp(x: f32, min: f32, max: f32) -> f32 {
     x
 }
 
-/// Clamps the pixel and converts it to linear space.
+/// Clamps the pixel and applies an approximate gamma of 2.0. This is the
+/// legacy conversion, `post::tonemap::ToneMapping` does the full tone
+/// mapping and sRGB conversion.
 #[inline]
 pub fn convert_pixel(pixel: vector::Vec3) -> (u8, u8, u8) {
     (
@@ -44,14 +48,14 @@ pub fn convert_pixel(pixel: vector::Vec3) -> (u8, u8, u8) {
     )
 }
 
-/// Writes a pixel to a buffer location
+/// Writes a linear floating point pixel to a rgb buffer location
 macro_rules! write_pixel {
     ($pixel:expr, $out:expr, $location:expr) => {{
-        let (r, g, b) = convert_pixel($pixel);
+        let pixel = $pixel;
 
-        $out[$location * 3] = r;
-        $out[$location * 3 + 1] = g;
-        $out[$location * 3 + 2] = b;
+        $out[$location * 3] = pixel.x;
+        $out[$location * 3 + 1] = pixel.y;
+        $out[$location * 3 + 2] = pixel.z;
     }};
 }
 
@@ -1,21 +1,31 @@
 use super::vector::{Point3, Vec3};
 
-/// A simple 3D ray struct.
+/// A simple 3D ray struct. The time is when during the exposure the ray
+/// is traced, for objects that move while the shutter is open.
 #[derive(Copy, Clone)]
 pub struct Ray {
     pub orig: Point3,
     pub dir: Vec3,
     pub invdir: Vec3,
+    pub time: f32,
 }
 
 impl Ray {
+    /// Creates a ray at time 0.
     pub fn new(orig: Point3, dir: Vec3) -> Ray {
+        Ray::with_time(orig, dir, 0.0)
+    }
+
+    /// Creates a ray at the time. Rays scattered off of a hit carry on
+    /// with the time of the ray that hit.
+    pub fn with_time(orig: Point3, dir: Vec3, time: f32) -> Ray {
         let invdir = 1.0 / dir;
 
         Ray {
             orig,
             dir,
             invdir,
+            time,
         }
     }
 
@@ -45,5 +55,8 @@ mod tests {
         assert_eq!(-1.0, v.x);
         assert_eq!(-2.0, v.y);
         assert_eq!(-3.0, v.z);
+
+        assert_eq!(r.time, 0.0);
+        assert_eq!(Ray::with_time(r.orig, r.dir, 0.5).time, 0.5);
     }
 }
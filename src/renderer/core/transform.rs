@@ -0,0 +1,764 @@
+use std::convert::TryFrom;
+use std::ops;
+
+use serde::{Deserialize, Serialize};
+
+use super::aabb::Aabb;
+use super::ray::Ray;
+use super::vector::{self, Point3, Vec3};
+
+/// A 4x4 matrix in row major order, acting on column vectors. The last
+/// row is expected to be 0, 0, 0, 1 for the affine transforms used by the
+/// renderer.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Matrix4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Matrix4 {
+    /// The matrix that leaves everything where it is.
+    pub fn identity() -> Matrix4 {
+        Matrix4 {
+            m: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Returns the transpose of the matrix.
+    pub fn transpose(&self) -> Matrix4 {
+        let mut t = Matrix4::identity();
+        for (i, row) in t.m.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = self.m[j][i];
+            }
+        }
+        t
+    }
+
+    /// Returns the inverse of the matrix by Gauss-Jordan elimination with
+    /// partial pivoting, or None if the matrix is singular.
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut a = self.m;
+        let mut inv = Matrix4::identity().m;
+
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))
+                .unwrap();
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+
+            for row in (0..4).filter(|row| *row != col) {
+                let factor = a[row][col];
+                if factor != 0.0 {
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[col][j];
+                        inv[row][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+
+        Some(Matrix4 { m: inv })
+    }
+
+    /// The determinant of the upper left 3x3, the linear part of an affine
+    /// transform. It's how much the transform scales volumes.
+    pub fn determinant3(&self) -> f32 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// Transforms a point, including the translation.
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        let m = &self.m;
+        Point3::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3],
+        )
+    }
+
+    /// Transforms a direction, which isn't affected by the translation.
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+}
+
+impl ops::Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, other: Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Matrix4 { m }
+    }
+}
+
+/// An affine transform from the space of an object into the world, kept
+/// with its inverse so both directions are cheap.
+///
+/// In the scene file, a transform is either a matrix, as four rows of four
+/// numbers, or a list of steps applied to the object in order, e.g.
+/// `[{"scale": {...}}, {"rotate": {"axis": {...}, "degrees": 90.0}},
+/// {"translate": {...}}]`.
+///
+/// Fields:
+/// * `matrix` - From object space into world space.
+/// * `inverse` - From world space into object space.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "TransformData", into = "Matrix4")]
+pub struct Transform {
+    pub matrix: Matrix4,
+    pub inverse: Matrix4,
+}
+
+/// A step of a transform in the scene file.
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TransformStep {
+    Translate(Vec3),
+    Scale(Vec3),
+    Rotate { axis: Vec3, degrees: f32 },
+    Matrix(Matrix4),
+}
+
+/// The serialized form of a transform.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TransformData {
+    Matrix(Matrix4),
+    Steps(Vec<TransformStep>),
+}
+
+impl TryFrom<TransformData> for Transform {
+    type Error = String;
+
+    fn try_from(data: TransformData) -> Result<Self, Self::Error> {
+        match data {
+            TransformData::Matrix(m) => Transform::new(m),
+            TransformData::Steps(steps) => {
+                let mut transform = Transform::identity();
+                for step in steps {
+                    let next = match step {
+                        TransformStep::Translate(v) => Transform::translate(v),
+                        TransformStep::Scale(v) => Transform::scale(v)?,
+                        TransformStep::Rotate { axis, degrees } => {
+                            Transform::rotate(axis, degrees)?
+                        }
+                        TransformStep::Matrix(m) => Transform::new(m)?,
+                    };
+                    transform = transform.then(&next);
+                }
+                Ok(transform)
+            }
+        }
+    }
+}
+
+impl From<Transform> for Matrix4 {
+    fn from(transform: Transform) -> Matrix4 {
+        transform.matrix
+    }
+}
+
+impl Transform {
+    /// Creates the transform of a matrix.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem if the matrix can't be
+    /// inverted, e.g. when it scales an axis to 0.
+    pub fn new(matrix: Matrix4) -> Result<Transform, String> {
+        match matrix.inverse() {
+            Some(inverse) => Ok(Transform { matrix, inverse }),
+            None => Err(format!("Transform matrix {:?} is singular", matrix.m)),
+        }
+    }
+
+    /// The transform that leaves everything where it is.
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    /// Moves everything by the offset.
+    pub fn translate(offset: Vec3) -> Transform {
+        let mut matrix = Matrix4::identity();
+        let mut inverse = Matrix4::identity();
+        for axis in 0..3 {
+            matrix.m[axis][3] = offset[axis];
+            inverse.m[axis][3] = -offset[axis];
+        }
+        Transform { matrix, inverse }
+    }
+
+    /// Scales everything along the axes by the factors, around the origin.
+    /// Negative factors mirror.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem if a factor is 0.
+    pub fn scale(factors: Vec3) -> Result<Transform, String> {
+        if factors.x == 0.0 || factors.y == 0.0 || factors.z == 0.0 {
+            return Err(format!("Can't scale by 0, got {:?}", factors));
+        }
+
+        let mut matrix = Matrix4::identity();
+        let mut inverse = Matrix4::identity();
+        for axis in 0..3 {
+            matrix.m[axis][axis] = factors[axis];
+            inverse.m[axis][axis] = 1.0 / factors[axis];
+        }
+        Ok(Transform { matrix, inverse })
+    }
+
+    /// Rotates everything counter-clockwise around the axis through the
+    /// origin, looking down the axis toward the origin.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem if the axis is 0.
+    pub fn rotate(axis: Vec3, degrees: f32) -> Result<Transform, String> {
+        if axis.near_zero() {
+            return Err("Can't rotate around a zero axis".to_string());
+        }
+
+        let a = vector::unit_vector(&axis);
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let matrix = Matrix4 {
+            m: [
+                [
+                    a.x * a.x + (1.0 - a.x * a.x) * cos,
+                    a.x * a.y * (1.0 - cos) - a.z * sin,
+                    a.x * a.z * (1.0 - cos) + a.y * sin,
+                    0.0,
+                ],
+                [
+                    a.x * a.y * (1.0 - cos) + a.z * sin,
+                    a.y * a.y + (1.0 - a.y * a.y) * cos,
+                    a.y * a.z * (1.0 - cos) - a.x * sin,
+                    0.0,
+                ],
+                [
+                    a.x * a.z * (1.0 - cos) - a.y * sin,
+                    a.y * a.z * (1.0 - cos) + a.x * sin,
+                    a.z * a.z + (1.0 - a.z * a.z) * cos,
+                    0.0,
+                ],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        };
+
+        // a rotation is undone by its transpose
+        Ok(Transform {
+            matrix,
+            inverse: matrix.transpose(),
+        })
+    }
+
+    /// Returns the transform that applies this one and then the other.
+    pub fn then(&self, other: &Transform) -> Transform {
+        Transform {
+            matrix: other.matrix * self.matrix,
+            inverse: self.inverse * other.inverse,
+        }
+    }
+
+    /// Transforms a point from object space into world space.
+    pub fn point(&self, p: &Point3) -> Point3 {
+        self.matrix.transform_point(p)
+    }
+
+    /// Transforms a direction from object space into world space.
+    pub fn vector(&self, v: &Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    /// Transforms a surface normal from object space into world space. The
+    /// normals go through the inverse transpose to stay perpendicular to
+    /// the surface, and need to be normalized again afterwards.
+    pub fn normal(&self, n: &Vec3) -> Vec3 {
+        self.inverse.transpose().transform_vector(n)
+    }
+
+    /// Transforms a ray from world space into object space. The direction
+    /// isn't normalized, so the t of a hit is the same in both spaces.
+    pub fn ray_to_object(&self, r: &Ray) -> Ray {
+        Ray::with_time(
+            self.inverse.transform_point(&r.orig),
+            self.inverse.transform_vector(&r.dir),
+            r.time,
+        )
+    }
+
+    /// Returns the world space box around an object space box, the box
+    /// around its eight transformed corners.
+    pub fn bounds(&self, b: &Aabb) -> Aabb {
+        // the corners of an infinite box would turn into NaNs
+        if !b.is_finite() {
+            return Aabb::infinite();
+        }
+
+        let mut out = Aabb::empty();
+        for p in corners(b).iter() {
+            out.expand_point(self.point(p));
+        }
+        out
+    }
+}
+
+/// Returns the eight corners of a box.
+fn corners(b: &Aabb) -> [Point3; 8] {
+    let mut corners = [b.box_min; 8];
+    for (i, p) in corners.iter_mut().enumerate() {
+        *p = Point3::new(
+            if i & 1 == 0 { b.box_min.x } else { b.box_max.x },
+            if i & 2 == 0 { b.box_min.y } else { b.box_max.y },
+            if i & 4 == 0 { b.box_min.z } else { b.box_max.z },
+        );
+    }
+    corners
+}
+
+/// How many steps the motion of an animated transform is split into to
+/// find the box around it.
+const MOTION_BOUND_STEPS: usize = 64;
+
+/// A rotation as a unit quaternion, for interpolating between rotations.
+#[derive(Copy, Clone, Debug)]
+struct Quaternion {
+    w: f32,
+    v: Vec3,
+}
+
+impl Quaternion {
+    /// Converts a rotation matrix into a quaternion, starting from the
+    /// largest of the components for precision.
+    fn from_matrix(m: &Matrix4) -> Quaternion {
+        let m = &m.m;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        if trace > 0.0 {
+            let s = 2.0 * (trace + 1.0).sqrt();
+            return Quaternion {
+                w: 0.25 * s,
+                v: Vec3::new(
+                    (m[2][1] - m[1][2]) / s,
+                    (m[0][2] - m[2][0]) / s,
+                    (m[1][0] - m[0][1]) / s,
+                ),
+            };
+        }
+
+        // the largest diagonal element i, with j and k after it
+        let i = if m[0][0] >= m[1][1] && m[0][0] >= m[2][2] {
+            0
+        } else if m[1][1] >= m[2][2] {
+            1
+        } else {
+            2
+        };
+        let (j, k) = ((i + 1) % 3, (i + 2) % 3);
+        let s = 2.0 * (1.0 + m[i][i] - m[j][j] - m[k][k]).sqrt();
+        let mut v = [0.0; 3];
+        v[i] = 0.25 * s;
+        v[j] = (m[j][i] + m[i][j]) / s;
+        v[k] = (m[k][i] + m[i][k]) / s;
+        Quaternion {
+            w: (m[k][j] - m[j][k]) / s,
+            v: Vec3::new(v[0], v[1], v[2]),
+        }
+    }
+
+    /// Converts the quaternion back into a rotation matrix.
+    fn to_matrix(self) -> Matrix4 {
+        let (w, x, y, z) = (self.w, self.v.x, self.v.y, self.v.z);
+        Matrix4 {
+            m: [
+                [
+                    1.0 - 2.0 * (y * y + z * z),
+                    2.0 * (x * y - z * w),
+                    2.0 * (x * z + y * w),
+                    0.0,
+                ],
+                [
+                    2.0 * (x * y + z * w),
+                    1.0 - 2.0 * (x * x + z * z),
+                    2.0 * (y * z - x * w),
+                    0.0,
+                ],
+                [
+                    2.0 * (x * z - y * w),
+                    2.0 * (y * z + x * w),
+                    1.0 - 2.0 * (x * x + y * y),
+                    0.0,
+                ],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Interpolates along the shortest arc between the rotations, at a
+    /// constant angular speed.
+    fn slerp(self, other: Quaternion, t: f32) -> Quaternion {
+        let mut cos = self.w * other.w + vector::dot(&self.v, &other.v);
+
+        // q and -q are the same rotation, the other way around
+        let other = if cos < 0.0 {
+            cos = -cos;
+            Quaternion {
+                w: -other.w,
+                v: -other.v,
+            }
+        } else {
+            other
+        };
+
+        let (a, b) = if cos > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+
+        let w = a * self.w + b * other.w;
+        let v = a * self.v + b * other.v;
+        let length = (w * w + v.length_squared()).sqrt();
+        Quaternion {
+            w: w / length,
+            v: v / length,
+        }
+    }
+}
+
+/// A transform split into a translation, a rotation and what's left of
+/// the linear part, the scale, which may also shear.
+#[derive(Copy, Clone, Debug)]
+struct Decomposed {
+    translation: Vec3,
+    rotation: Quaternion,
+    scale: Matrix4,
+}
+
+impl Decomposed {
+    /// Splits the transform with the polar decomposition of its linear
+    /// part, which finds the closest rotation by averaging the matrix with
+    /// its inverse transpose until it stops changing.
+    fn new(transform: &Transform) -> Decomposed {
+        let m = &transform.matrix;
+        let translation = Vec3::new(m.m[0][3], m.m[1][3], m.m[2][3]);
+        let mut linear = *m;
+        for row in linear.m.iter_mut().take(3) {
+            row[3] = 0.0;
+        }
+
+        let mut rotation = linear;
+        for _ in 0..100 {
+            let inverse_transpose = match rotation.inverse() {
+                Some(inverse) => inverse.transpose(),
+                None => break,
+            };
+
+            let mut next = rotation;
+            let mut change: f32 = 0.0;
+            for i in 0..3 {
+                for j in 0..3 {
+                    next.m[i][j] = 0.5 * (rotation.m[i][j] + inverse_transpose.m[i][j]);
+                    change = change.max((next.m[i][j] - rotation.m[i][j]).abs());
+                }
+            }
+            rotation = next;
+            if change < 1e-6 {
+                break;
+            }
+        }
+
+        // a mirror is a rotation times a scale by -1
+        if rotation.determinant3() < 0.0 {
+            for row in rotation.m.iter_mut().take(3) {
+                for v in row.iter_mut().take(3) {
+                    *v = -*v;
+                }
+            }
+        }
+
+        // the rotation is undone by its transpose
+        let scale = rotation.transpose() * linear;
+        Decomposed {
+            translation,
+            rotation: Quaternion::from_matrix(&rotation),
+            scale,
+        }
+    }
+}
+
+/// A transform that moves from a start transform to an end transform
+/// between two times, for objects that move while the shutter is open.
+/// Before the start time it stays at the start, and after the end time it
+/// stays at the end.
+///
+/// Like pbrt's animated transforms, the two transforms are split into a
+/// translation, a rotation and a scale, which are interpolated separately,
+/// so a spinning object keeps its shape halfway through the turn instead
+/// of shrinking like it would with the matrices blended together.
+#[derive(Copy, Clone, Debug)]
+pub struct AnimatedTransform {
+    pub start: Transform,
+    pub end: Transform,
+    pub start_time: f32,
+    pub end_time: f32,
+    start_parts: Decomposed,
+    end_parts: Decomposed,
+}
+
+impl AnimatedTransform {
+    /// Creates the transform moving from start to end between the times.
+    pub fn new(
+        start: Transform,
+        end: Transform,
+        start_time: f32,
+        end_time: f32,
+    ) -> AnimatedTransform {
+        AnimatedTransform {
+            start,
+            end,
+            start_time,
+            end_time,
+            start_parts: Decomposed::new(&start),
+            end_parts: Decomposed::new(&end),
+        }
+    }
+
+    /// Returns the transform at the time.
+    pub fn at(&self, time: f32) -> Transform {
+        if time <= self.start_time || self.end_time <= self.start_time {
+            return self.start;
+        }
+        if time >= self.end_time {
+            return self.end;
+        }
+
+        let t = (time - self.start_time) / (self.end_time - self.start_time);
+        let (a, b) = (&self.start_parts, &self.end_parts);
+
+        let mut scale = a.scale;
+        for (i, row) in scale.m.iter_mut().enumerate().take(3) {
+            for (j, v) in row.iter_mut().enumerate().take(3) {
+                *v = (1.0 - t) * a.scale.m[i][j] + t * b.scale.m[i][j];
+            }
+        }
+        let mut matrix = a.rotation.slerp(b.rotation, t).to_matrix() * scale;
+        let translation = (1.0 - t) * a.translation + t * b.translation;
+        for axis in 0..3 {
+            matrix.m[axis][3] = translation[axis];
+        }
+
+        // blending a scale through 0, like a mirror turning around, has no
+        // inverse halfway
+        Transform::new(matrix).unwrap_or(if t < 0.5 { self.start } else { self.end })
+    }
+
+    /// Returns the world space box around an object space box over the
+    /// whole motion. The box is transformed at many times along the way,
+    /// and grown by half of the furthest a corner moves between two of
+    /// them, which covers the curve a corner takes in between.
+    pub fn bounds(&self, b: &Aabb) -> Aabb {
+        if !b.is_finite() {
+            return Aabb::infinite();
+        }
+
+        let corners = corners(b);
+        let mut out = Aabb::empty();
+        let mut previous: Option<[Point3; 8]> = None;
+        let mut furthest: f32 = 0.0;
+        for step in 0..=MOTION_BOUND_STEPS {
+            let t = step as f32 / MOTION_BOUND_STEPS as f32;
+            let transform = self.at(self.start_time + t * (self.end_time - self.start_time));
+
+            let mut moved = corners;
+            for p in moved.iter_mut() {
+                *p = transform.point(p);
+                out.expand_point(*p);
+            }
+            if let Some(previous) = previous {
+                for (a, b) in previous.iter().zip(moved.iter()) {
+                    furthest = furthest.max((*a - *b).length());
+                }
+            }
+            previous = Some(moved);
+        }
+
+        let padding = 0.5 * furthest;
+        Aabb::new(out.box_min - padding, out.box_max + padding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &Vec3, b: &Vec3) {
+        assert!((*a - *b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_transforms() {
+        let t = Transform::scale(Vec3::new(2.0, 2.0, 2.0))
+            .unwrap()
+            .then(&Transform::rotate(Vec3::new(0.0, 0.0, 1.0), 90.0).unwrap())
+            .then(&Transform::translate(Vec3::new(1.0, 0.0, 0.0)));
+
+        // scaled, then x turned into y, then moved
+        let p = t.point(&Point3::new(1.0, 0.0, 0.0));
+        assert_close(&p, &Point3::new(1.0, 2.0, 0.0));
+        assert_close(&t.inverse.transform_point(&p), &Point3::new(1.0, 0.0, 0.0));
+        assert_close(
+            &t.vector(&Vec3::new(1.0, 0.0, 0.0)),
+            &Vec3::new(0.0, 2.0, 0.0),
+        );
+
+        // the inverse of the general matrix agrees with the composed one
+        let inverse = t.matrix.inverse().unwrap();
+        for (row, expected) in inverse.m.iter().zip(t.inverse.m.iter()) {
+            for (a, b) in row.iter().zip(expected.iter()) {
+                assert!((a - b).abs() < 1e-5);
+            }
+        }
+        assert!((t.matrix.determinant3() - 8.0).abs() < 1e-4);
+
+        // normals stay perpendicular to a squashed surface
+        let t = Transform::scale(Vec3::new(1.0, 2.0, 1.0)).unwrap();
+        let n = vector::unit_vector(&t.normal(&Vec3::new(1.0, 1.0, 0.0)));
+        let tangent = t.vector(&Vec3::new(1.0, -1.0, 0.0));
+        assert!(vector::dot(&n, &tangent).abs() < 1e-5);
+
+        assert!(Transform::scale(Vec3::new(1.0, 0.0, 1.0)).is_err());
+        assert!(Transform::new(Matrix4 { m: [[0.0; 4]; 4] }).is_err());
+    }
+
+    #[test]
+    fn test_transform_bounds() {
+        let t = Transform::rotate(Vec3::new(0.0, 1.0, 0.0), 45.0).unwrap();
+        let b = t.bounds(&Aabb::new(
+            Point3::new(-1.0, -1.0, -1.0),
+            Point3::new(1.0, 1.0, 1.0),
+        ));
+        let half_diagonal = 2.0_f32.sqrt();
+        assert!((b.box_max.x - half_diagonal).abs() < 1e-5);
+        assert!((b.box_min.z + half_diagonal).abs() < 1e-5);
+        assert!((b.box_max.y - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_animated_transform() {
+        let start = Transform::translate(Vec3::new(1.0, 0.0, 0.0));
+        let end = Transform::scale(Vec3::new(2.0, 2.0, 2.0))
+            .unwrap()
+            .then(&Transform::rotate(Vec3::new(0.0, 0.0, 1.0), 90.0).unwrap())
+            .then(&Transform::translate(Vec3::new(0.0, 3.0, 0.0)));
+        let motion = AnimatedTransform::new(start, end, 0.0, 2.0);
+
+        // the ends and past them
+        let p = Point3::new(1.0, 0.0, 0.0);
+        assert_close(&motion.at(-1.0).point(&p), &Point3::new(2.0, 0.0, 0.0));
+        assert_close(&motion.at(0.0).point(&p), &Point3::new(2.0, 0.0, 0.0));
+        assert_close(&motion.at(2.0).point(&p), &Point3::new(0.0, 5.0, 0.0));
+        assert_close(&motion.at(3.0).point(&p), &Point3::new(0.0, 5.0, 0.0));
+
+        // halfway, turned 45 degrees and scaled by 1.5 instead of being
+        // squashed by blending the matrices
+        let half = motion.at(1.0);
+        let s = 1.5 / 2.0_f32.sqrt();
+        assert_close(&half.point(&p), &Point3::new(0.5 + s, 1.5 + s, 0.0));
+        assert!((half.matrix.determinant3() - 1.5_f32.powi(3)).abs() < 1e-4);
+        assert_close(&half.inverse.transform_point(&half.point(&p)), &p);
+
+        // a mirror stays a mirror
+        let mirror = Transform::scale(Vec3::new(-1.0, 1.0, 1.0)).unwrap();
+        let turned = mirror.then(&Transform::rotate(Vec3::new(0.0, 1.0, 0.0), 60.0).unwrap());
+        let motion = AnimatedTransform::new(mirror, turned, 0.0, 1.0);
+        assert!((motion.at(0.5).matrix.determinant3() + 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_animated_transform_bounds() {
+        // a unit box spinning half a turn around the y axis sweeps a disk
+        // of the radius of its half diagonal
+        let motion = AnimatedTransform::new(
+            Transform::translate(Vec3::new(0.0, 0.0, 0.0)),
+            Transform::rotate(Vec3::new(0.0, 1.0, 0.0), 180.0)
+                .unwrap()
+                .then(&Transform::translate(Vec3::new(0.0, 2.0, 0.0))),
+            0.0,
+            1.0,
+        );
+        let b = motion.bounds(&Aabb::new(
+            Point3::new(-1.0, -1.0, -1.0),
+            Point3::new(1.0, 1.0, 1.0),
+        ));
+
+        let half_diagonal = 2.0_f32.sqrt();
+        assert!(b.box_max.x >= half_diagonal - 1e-4);
+        assert!(b.box_max.x < half_diagonal + 0.05);
+        assert!(b.box_min.z <= -half_diagonal + 1e-4);
+        assert!(b.box_min.y <= -1.0 && b.box_max.y >= 3.0);
+
+        // every time along the way is inside of the box
+        let corner = Point3::new(1.0, 1.0, 1.0);
+        for i in 0..=100 {
+            let p = motion.at(i as f32 / 100.0).point(&corner);
+            for axis in 0..3 {
+                assert!(p[axis] >= b.box_min[axis] && p[axis] <= b.box_max[axis]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_transform_deserialize() {
+        let json = r#"[
+            {"scale": {"x": 2.0, "y": 2.0, "z": 2.0}},
+            {"translate": {"x": 0.0, "y": 1.0, "z": 0.0}}
+        ]"#;
+        let t: Transform = serde_json::from_str(json).unwrap();
+        assert_close(
+            &t.point(&Point3::new(1.0, 1.0, 1.0)),
+            &Point3::new(2.0, 3.0, 2.0),
+        );
+
+        // written out as the matrix, and read back the same
+        let written = serde_json::to_string(&t).unwrap();
+        let t2: Transform = serde_json::from_str(&written).unwrap();
+        assert_eq!(t.matrix, t2.matrix);
+
+        let json = r#"[{"scale": {"x": 0.0, "y": 1.0, "z": 1.0}}]"#;
+        assert!(serde_json::from_str::<Transform>(json).is_err());
+    }
+}
@@ -1,7 +1,8 @@
 use std::f32;
 use std::ops;
 
-use super::min;
+use super::{max, min};
+use crate::renderer::sampler::Sampler;
 use rand::Rng;
 
 use serde::{Deserialize, Serialize};
@@ -39,11 +40,11 @@ impl Vec3 {
 
     /// Creates a random vector where the dimensional values
     /// are within a specified range.
-    pub fn random_range(min: f32, max: f32) -> Vec3 {
+    pub fn random_range<R: Rng>(rng: &mut R, min: f32, max: f32) -> Vec3 {
         Vec3 {
-            x: rand::thread_rng().gen_range(min..max),
-            y: rand::thread_rng().gen_range(min..max),
-            z: rand::thread_rng().gen_range(min..max),
+            x: rng.gen_range(min..max),
+            y: rng.gen_range(min..max),
+            z: rng.gen_range(min..max),
         }
     }
 
@@ -82,25 +83,25 @@ pub fn unit_vector(v: &Vec3) -> Vec3 {
     Vec3::new(v.x * invlen, v.y * invlen, v.z * invlen)
 }
 
-/// Returns a random vector on the surface of the unit sphere
-pub fn random_in_unit_sphere() -> Vec3 {
-    loop {
-        let p = Vec3::random_range(-1.0, 1.0);
-        if p.length_squared() > 1.0 {
-            continue;
-        }
-        return p;
-    }
+/// Returns a random vector inside of the unit sphere.
+pub fn random_in_unit_sphere(sampler: &mut dyn Sampler) -> Vec3 {
+    let radius = sampler.get_1d().cbrt();
+    radius * random_unit_vector(sampler)
 }
 
-/// Returns a random unit vector
-pub fn random_unit_vector() -> Vec3 {
-    unit_vector(&random_in_unit_sphere())
+/// Returns a random unit vector, uniformly distributed over the sphere.
+pub fn random_unit_vector(sampler: &mut dyn Sampler) -> Vec3 {
+    let (u1, u2) = sampler.get_2d();
+    let z = 1.0 - 2.0 * u1;
+    let r = max!(0.0, 1.0 - z * z).sqrt();
+    let phi = 2.0 * f32::consts::PI * u2;
+
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
 }
 
 /// Returns a random vector in a hemisphere
-pub fn random_in_hemisphere(n: &Vec3) -> Vec3 {
-    let unit_sphere = random_in_unit_sphere();
+pub fn random_in_hemisphere(n: &Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+    let unit_sphere = random_in_unit_sphere(sampler);
     if dot(&unit_sphere, n) > 0.0 {
         unit_sphere
     } else {
@@ -108,19 +109,38 @@ pub fn random_in_hemisphere(n: &Vec3) -> Vec3 {
     }
 }
 
-/// Returns a random in the unit disk (no height).
-pub fn random_in_unit_disk() -> Vec3 {
-    loop {
-        let p = Vec3::new(
-            rand::thread_rng().gen_range(-1.0..1.0),
-            rand::thread_rng().gen_range(-1.0..1.0),
-            0.0,
-        );
-        if p.length_squared() > 1.0 {
-            continue;
-        }
-        return p;
+/// Returns a random in the unit disk (no height). Uses the concentric
+/// mapping of the square onto the disk, which keeps samples that are
+/// spread out evenly over the square spread out over the disk.
+pub fn random_in_unit_disk(sampler: &mut dyn Sampler) -> Vec3 {
+    let (u1, u2) = sampler.get_2d();
+    let (a, b) = (2.0 * u1 - 1.0, 2.0 * u2 - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return Vec3::new(0.0, 0.0, 0.0);
     }
+
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, f32::consts::FRAC_PI_4 * (b / a))
+    } else {
+        (b, f32::consts::FRAC_PI_2 - f32::consts::FRAC_PI_4 * (a / b))
+    };
+
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
+/// Returns two unit vectors that form an orthonormal basis with the unit
+/// vector n. Useful for turning directions sampled around the z axis into
+/// directions around n.
+pub fn orthonormal_basis(n: &Vec3) -> (Vec3, Vec3) {
+    // Duff et al. 2017, "Building an Orthonormal Basis, Revisited"
+    let sign = 1.0_f32.copysign(n.z);
+    let a = -1.0 / (sign + n.z);
+    let b = n.x * n.y * a;
+
+    (
+        Vec3::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x),
+        Vec3::new(b, sign + n.y * n.y * a, -n.y),
+    )
 }
 
 /// Reflect a ray around a normal
@@ -284,10 +304,26 @@ impl ops::DivAssign<f32> for Vec3 {
     }
 }
 
+/// a[i] operator for accessing the dimensions by axis index (0 is x,
+/// 1 is y, 2 is z). Useful for algorithms that loop over the axes.
+impl ops::Index<usize> for Vec3 {
+    type Output = f32;
+
+    fn index(&self, axis: usize) -> &f32 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 axis index out of range: {}", axis),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::renderer::sampler::IndependentSampler;
 
     #[test]
     fn test_methods() {
@@ -297,8 +333,9 @@ mod tests {
         assert_eq!(v2.length(), 29.0_f32.sqrt());
 
         // a stochastic check to make sure we don't get something outside of the range.
+        let mut rng = rand::thread_rng();
         for _ in 0..10000 {
-            let v = Vec3::random_range(-13.0, 17.0);
+            let v = Vec3::random_range(&mut rng, -13.0, 17.0);
 
             assert!(v.x >= -13.0 && v.x <= 17.0);
             assert!(v.y >= -13.0 && v.y <= 17.0);
@@ -365,5 +402,33 @@ mod tests {
         let v2 = Vec3::new(-12.0, -15.0, -20.0);
         let dot = dot(&v1, &v2);
         assert_eq!(-769.0, dot);
+
+        let n = unit_vector(&Vec3::new(0.3, -0.5, 0.8));
+        let (u, v) = orthonormal_basis(&n);
+        assert!(super::dot(&u, &n).abs() < 1e-6);
+        assert!(super::dot(&v, &n).abs() < 1e-6);
+        assert!(super::dot(&u, &v).abs() < 1e-6);
+        assert!((u.length() - 1.0).abs() < 1e-6);
+        assert!((v.length() - 1.0).abs() < 1e-6);
+
+        assert_eq!(12.0, v1[0]);
+        assert_eq!(15.0, v1[1]);
+        assert_eq!(20.0, v1[2]);
+    }
+
+    #[test]
+    fn test_random() {
+        let mut sampler = IndependentSampler::new(0);
+        let n = Vec3::new(0.0, 1.0, 0.0);
+
+        for _ in 0..1000 {
+            assert!((random_unit_vector(&mut sampler).length() - 1.0).abs() < 1e-5);
+            assert!(random_in_unit_sphere(&mut sampler).length() <= 1.0 + 1e-5);
+            assert!(dot(&random_in_hemisphere(&n, &mut sampler), &n) >= 0.0);
+
+            let p = random_in_unit_disk(&mut sampler);
+            assert!(p.length() <= 1.0 + 1e-5);
+            assert_eq!(p.z, 0.0);
+        }
     }
 }
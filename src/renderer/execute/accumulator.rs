@@ -0,0 +1,158 @@
+use crate::renderer::core::color::{self, Color};
+
+/// Dark pixels are noisy in relative terms even when they look converged,
+/// so the noise is measured against at least this brightness.
+const NOISE_FLOOR: f32 = 0.01;
+
+/// Sums up the passes of a progressive render.
+///
+/// The passes alternate between two buffers, so that each holds an
+/// independent estimate of the image with half of the samples. How far the
+/// two halves disagree is a measure of the noise left in the image, without
+/// having to keep track of the variance of every pixel.
+///
+/// Only the pixels inside of the area that is rendered are accumulated,
+/// the rest of the image is left alone.
+pub struct Accumulator {
+    sums: [Vec<f32>; 2],
+    samples: [u32; 2],
+    width: usize,
+    area: (u32, u32, u32, u32),
+}
+
+impl Accumulator {
+    /// Creates an empty accumulator for an image of the bounds.
+    ///
+    /// Parameters:
+    /// * `bounds` - The width and height of the full image.
+    /// * `area` - The x0, y0, x1, y1 of the part of the image that is
+    ///   rendered.
+    pub fn new(bounds: (u32, u32), area: (u32, u32, u32, u32)) -> Accumulator {
+        let len = bounds.0 as usize * bounds.1 as usize * 3;
+        Accumulator {
+            sums: [vec![0.0; len], vec![0.0; len]],
+            samples: [0, 0],
+            width: bounds.0 as usize,
+            area,
+        }
+    }
+
+    /// The indices of the first float of each pixel in the area.
+    fn area_pixels(&self) -> impl Iterator<Item = usize> + '_ {
+        let (x0, y0, x1, y1) = self.area;
+        (y0..y1)
+            .flat_map(move |y| (x0..x1).map(move |x| (y as usize * self.width + x as usize) * 3))
+    }
+
+    /// Adds a pass that was rendered with the samples per pixel.
+    pub fn add_pass(&mut self, pixels: &[f32], samples: u32) {
+        // the half with fewer samples gets the pass, to keep them even
+        let half = if self.samples[0] <= self.samples[1] {
+            0
+        } else {
+            1
+        };
+
+        let (x0, y0, x1, y1) = self.area;
+        for y in y0..y1 {
+            let row = (y as usize * self.width + x0 as usize) * 3;
+            let end = row + (x1 - x0) as usize * 3;
+            for (sum, p) in self.sums[half][row..end].iter_mut().zip(&pixels[row..end]) {
+                *sum += p * samples as f32;
+            }
+        }
+        self.samples[half] += samples;
+    }
+
+    /// The total samples per pixel of all of the passes.
+    pub fn samples(&self) -> u32 {
+        self.samples[0] + self.samples[1]
+    }
+
+    /// Writes the average of all of the passes into the area of the image
+    /// buffer.
+    pub fn resolve(&self, pixels: &mut [f32]) {
+        let scale = 1.0 / self.samples().max(1) as f32;
+        let (x0, y0, x1, y1) = self.area;
+        for y in y0..y1 {
+            let row = (y as usize * self.width + x0 as usize) * 3;
+            let end = row + (x1 - x0) as usize * 3;
+            for (c, p) in pixels[row..end].iter_mut().enumerate() {
+                *p = (self.sums[0][row + c] + self.sums[1][row + c]) * scale;
+            }
+        }
+    }
+
+    /// Estimates the relative error left in the image, averaged over the
+    /// pixels. For a pixel, the two halves differ by about twice the
+    /// standard error of the full estimate, so the error relative to the
+    /// pixel's brightness is |a - b| / (a + b). Returns None until both of
+    /// the halves have samples.
+    pub fn noise(&self) -> Option<f32> {
+        if self.samples[0] == 0 || self.samples[1] == 0 {
+            return None;
+        }
+
+        let scale = (1.0 / self.samples[0] as f32, 1.0 / self.samples[1] as f32);
+        let (a, b) = (&self.sums[0], &self.sums[1]);
+
+        let mut total = 0.0;
+        let mut count = 0;
+        for i in self.area_pixels() {
+            let la = color::luminance(Color::new(a[i], a[i + 1], a[i + 2])) * scale.0;
+            let lb = color::luminance(Color::new(b[i], b[i + 1], b[i + 2])) * scale.1;
+            total += (la - lb).abs() / (la + lb).max(NOISE_FLOOR);
+            count += 1;
+        }
+
+        Some(total / count.max(1) as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accumulator() {
+        let mut acc = Accumulator::new((2, 1), (0, 0, 2, 1));
+        assert_eq!(acc.samples(), 0);
+        assert!(acc.noise().is_none());
+
+        acc.add_pass(&[1.0, 1.0, 1.0, 0.0, 0.0, 0.0], 2);
+        assert!(acc.noise().is_none());
+
+        acc.add_pass(&[1.0, 1.0, 1.0, 0.0, 0.0, 0.0], 2);
+        assert_eq!(acc.samples(), 4);
+        assert_eq!(acc.noise(), Some(0.0));
+
+        // a third pass that disagrees goes into the first half
+        acc.add_pass(&[4.0, 4.0, 4.0, 0.0, 0.0, 0.0], 2);
+        assert_eq!(acc.samples(), 6);
+
+        let mut pixels = vec![0.0; 6];
+        acc.resolve(&mut pixels);
+        assert_eq!(pixels[0], 2.0);
+        assert_eq!(pixels[3], 0.0);
+
+        // the first pixel is 2.5 against 1.0, the second one has no noise
+        let noise = acc.noise().unwrap();
+        assert!((noise - 0.5 * 1.5 / 3.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_accumulator_area() {
+        // only the second pixel is rendered
+        let mut acc = Accumulator::new((2, 1), (1, 0, 2, 1));
+        acc.add_pass(&[5.0, 5.0, 5.0, 1.0, 1.0, 1.0], 1);
+        acc.add_pass(&[5.0, 5.0, 5.0, 3.0, 3.0, 3.0], 1);
+
+        let mut pixels = vec![-1.0; 6];
+        acc.resolve(&mut pixels);
+        assert_eq!(pixels[0], -1.0);
+        assert_eq!(pixels[3], 2.0);
+
+        let noise = acc.noise().unwrap();
+        assert!((noise - 0.5).abs() < 1e-5);
+    }
+}
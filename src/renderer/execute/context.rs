@@ -1,4 +1,9 @@
+use crate::renderer::aov::Aov;
 use crate::renderer::core::debug_check;
+use crate::renderer::film::Filter;
+use crate::renderer::integrator::{Integrator, IntegratorConfig, PathIntegrator};
+use crate::renderer::options::AdaptiveSampling;
+use crate::renderer::sampler::SamplerType;
 use crate::renderer::scene::camera::Camera;
 use log::error;
 
@@ -14,6 +19,13 @@ pub struct RenderContext {
     pub start_y: u32,
     pub end_x: u32,
     pub end_y: u32,
+    pub adaptive: Option<AdaptiveSampling>,
+    pub sampler: SamplerType,
+    pub first_sample: u32,
+    pub seed: u64,
+    pub filter: Filter,
+    pub integrator: Box<dyn Integrator>,
+    pub aovs: Vec<Aov>,
 }
 
 impl RenderContext {
@@ -51,6 +63,58 @@ impl RenderContext {
             start_y,
             end_x,
             end_y,
+            adaptive: None,
+            sampler: SamplerType::default(),
+            first_sample: 0,
+            seed: 0,
+            filter: Filter::default(),
+            integrator: Box::new(PathIntegrator::new(max_depth)),
+            aovs: Vec::new(),
         }
     }
+
+    /// Draws the samples from the sampler, starting at the sample index
+    /// `first_sample` of every pixel.
+    pub fn with_sampler(mut self, sampler: SamplerType, first_sample: u32) -> RenderContext {
+        self.sampler = sampler;
+        self.first_sample = first_sample;
+        self
+    }
+
+    /// Seeds the random numbers of the samples. Renders with the same seed
+    /// produce the same image.
+    pub fn with_seed(mut self, seed: u64) -> RenderContext {
+        self.seed = seed;
+        self
+    }
+
+    /// Reconstructs the pixels from the samples with the filter.
+    pub fn with_filter(mut self, filter: Filter) -> RenderContext {
+        debug_check!(filter.radius.is_finite());
+
+        self.filter = filter;
+        self
+    }
+
+    /// Calculates the colors of the camera rays with the integrator.
+    pub fn with_integrator(mut self, integrator: &IntegratorConfig) -> RenderContext {
+        self.integrator = integrator.create(self.max_depth);
+        self
+    }
+
+    /// Also collects the output variables from the first hits of the
+    /// camera rays.
+    pub fn with_aovs(mut self, aovs: Vec<Aov>) -> RenderContext {
+        self.aovs = aovs;
+        self
+    }
+
+    /// Samples the pixels adaptively, with `samples` as the most any pixel
+    /// gets.
+    pub fn with_adaptive(mut self, adaptive: Option<AdaptiveSampling>) -> RenderContext {
+        debug_check!(adaptive.is_none_or(|a| a.min_samples > 1));
+
+        self.adaptive = adaptive;
+        self
+    }
 }
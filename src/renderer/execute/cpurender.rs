@@ -1,86 +1,185 @@
-use crate::renderer::core::{color::Color, convert_pixel, debug_check, write_pixel};
+use crate::renderer::core::debug_check;
+use crate::renderer::film::{Film, FilmTile};
+use crate::renderer::framebuffer::FrameBuffer;
 use crate::renderer::scene::world::Region;
 
 use log::error;
 
 use super::context::RenderContext;
 use super::error::ComputeError;
+use super::monitor::RenderMonitor;
+use super::tile::Tile;
 
 use rayon::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
-type RenderPixelOp = fn(&RenderContext, &Region, usize, usize) -> Color;
+/// Renders the samples of a single pixel into the film of its tile and
+/// returns the number of samples it took.
+type RenderPixelOp = fn(&RenderContext, &Region, usize, usize, &mut FilmTile) -> u32;
 
-/// Renders the multicore version of the algorithm using a set of n threads
-/// and a mpsc channel to collect the pixels into the output.
+/// Renders the multicore version of the algorithm. A worker per rayon
+/// thread takes the next tile in order, renders it into its own film tile
+/// and then copies it into the image. Handing out the tiles one at a time
+/// keeps all of the threads busy until the end, even when some parts of the
+/// image are much more expensive than others.
+///
+/// The samples near the edge of a tile also count for the pixels of the
+/// tiles next to it, so the image is only final once the film tiles are
+/// merged at the end. Until then, every finished tile shows the pixels
+/// reconstructed from its own samples.
+///
+/// When the render is cancelled, the threads stop at the next row and the
+/// tiles they were working on are dropped, so the image only has whole
+/// tiles in it.
 ///
 /// Parameters:
 /// * `context` - The render context that contains the information necessary to render the image.
 /// * `world` - The scene to render.
-/// * `img` - The image buffer to write to.
+/// * `frame` - The buffers of the full image to write to.
+/// * `tiles` - The tiles to render, in the order to render them.
+/// * `monitor` - Reports the finished tiles and checks for cancellation.
+/// * `render_op` - The function that renders a single pixel.
 pub fn render_threaded(
     context: &RenderContext,
     world: &Region,
-    pixels: &mut [u8],
-    bounds: (u32, u32),
+    frame: &mut FrameBuffer,
+    tiles: &[Tile],
+    monitor: &RenderMonitor,
     render_op: RenderPixelOp,
 ) -> Result<(), ComputeError> {
     // already covered by checks on the public api, but here to keep the internal behavior
     // consistency
-    debug_check!(bounds.0 > 0);
-    debug_check!(bounds.1 > 0);
-    debug_check!(pixels.len() == (bounds.0 as usize * bounds.1 as usize * 3));
+    debug_check!(frame.bounds.0 > 0);
+    debug_check!(frame.bounds.1 > 0);
+    debug_check!(frame.is_valid());
 
-    let w = bounds.0 as usize;
+    let (w, bounds) = (frame.bounds.0 as usize, frame.bounds);
+    let next_tile = AtomicUsize::new(0);
+    let output = Mutex::new((frame, Vec::new()));
 
-    //let mut pixels = img.into_raw();
-    let bands: Vec<(usize, &mut [u8])> = pixels.chunks_mut(w * 3).enumerate().collect();
+    (0..rayon::current_num_threads())
+        .into_par_iter()
+        .try_for_each(|_| -> Result<(), ComputeError> {
+            let mut tile_counts = Vec::new();
 
-    bands.into_par_iter().for_each(|(i, band)| {
-        for x in 0..w {
-            let pixel = render_op(context, world, x as usize, i as usize);
+            loop {
+                let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                let tile = match tiles.get(index) {
+                    Some(tile) => tile,
+                    None => return Ok(()),
+                };
 
-            write_pixel!(pixel, band, x);
-        }
-    });
+                let mut film = FilmTile::new(tile, context.filter, bounds).with_aovs(&context.aovs);
+                tile_counts.clear();
+                tile_counts.resize(tile.len(), 0);
+
+                let tw = (tile.x1 - tile.x0) as usize;
+                for y in tile.y0..tile.y1 {
+                    if monitor.is_cancelled() {
+                        return Ok(());
+                    }
+
+                    for x in tile.x0..tile.x1 {
+                        let location = (y - tile.y0) as usize * tw + (x - tile.x0) as usize;
+                        tile_counts[location] =
+                            render_op(context, world, x as usize, y as usize, &mut film);
+                    }
+                }
+
+                {
+                    let mut out = output.lock().map_err(|_| ComputeError::ThreadPanicked)?;
+                    let (frame, done) = &mut *out;
+                    film.resolve_into(tile, frame);
+
+                    if let Some(counts) = frame.sample_counts.as_mut() {
+                        for (row, y) in (tile.y0..tile.y1).enumerate() {
+                            let start = y as usize * w + tile.x0 as usize;
+                            counts[start..start + tw]
+                                .copy_from_slice(&tile_counts[row * tw..(row + 1) * tw]);
+                        }
+                    }
+                    done.push((index, film));
+                }
+
+                monitor.tile_done(tile);
+            }
+        })?;
+
+    let (frame, mut done) = output
+        .into_inner()
+        .map_err(|_| ComputeError::ThreadPanicked)?;
+    done.sort_by_key(|(index, _)| *index);
+    resolve_film(frame, tiles, &done);
 
     Ok(())
 }
 
 /// Renders the naive algorithm. It's a simple single threaded loop over all the
-/// pixels in the image. This is not likely to be used within the scope of this project,
+/// pixels of the tiles. This is not likely to be used within the scope of this project,
 /// however, if it were to be extended to where the tiling and threading were handled
 /// on a per process basis, this could be useful. Additionally, it's useful as a
 /// testing and performance baseline.
 ///
+/// When the render is cancelled, it stops at the next row of pixels.
+///
 /// Parameters:
 /// * `context` - The render context that contains the information necessary to render the image.
 /// * `world` - The scene to render.
-/// * `img` - The image buffer to write to.
+/// * `frame` - The buffers of the full image to write to.
+/// * `tiles` - The tiles to render, in the order to render them.
+/// * `monitor` - Reports the finished tiles and checks for cancellation.
+/// * `render_op` - The function that renders a single pixel.
 pub fn render_naive(
     context: &RenderContext,
     world: &Region,
-    pixels: &mut [u8],
-    bounds: (u32, u32),
+    frame: &mut FrameBuffer,
+    tiles: &[Tile],
+    monitor: &RenderMonitor,
     render_op: RenderPixelOp,
 ) {
     // already covered by checks on the public api, but here to keep the internal
     // consistency
-    debug_check!(bounds.0 > 0);
-    debug_check!(bounds.1 > 0);
+    debug_check!(frame.bounds.0 > 0);
+    debug_check!(frame.bounds.1 > 0);
 
-    let s_x = context.start_x;
-    let s_y = context.start_y;
-    let e_x = context.end_x;
-    let e_y = context.end_y;
+    let w = frame.bounds.0 as usize;
+    let mut done = Vec::new();
 
-    let w = (e_x - s_x) as usize;
+    'tiles: for (index, tile) in tiles.iter().enumerate() {
+        let mut film = FilmTile::new(tile, context.filter, frame.bounds).with_aovs(&context.aovs);
 
-    for y in s_y..e_y {
-        for x in s_x..e_x {
-            let pixel = render_op(context, world, x as usize, y as usize);
+        for y in tile.y0..tile.y1 {
+            if monitor.is_cancelled() {
+                break 'tiles;
+            }
 
-            write_pixel!(pixel, pixels, (y as usize * w + x as usize));
+            for x in tile.x0..tile.x1 {
+                let samples = render_op(context, world, x as usize, y as usize, &mut film);
+                if let Some(counts) = frame.sample_counts.as_mut() {
+                    counts[y as usize * w + x as usize] = samples;
+                }
+            }
         }
+
+        film.resolve_into(tile, frame);
+        done.push((index, film));
+        monitor.tile_done(tile);
+    }
+
+    resolve_film(frame, tiles, &done);
+}
+
+/// Merges the film tiles of the finished tiles, in the order of the tiles,
+/// and writes the final pixels of those tiles into the image.
+fn resolve_film(frame: &mut FrameBuffer, tiles: &[Tile], done: &[(usize, FilmTile)]) {
+    let mut film = Film::new(frame.bounds, &frame.aov_list());
+    for (_, film_tile) in done.iter() {
+        film.merge(film_tile);
+    }
+
+    for (index, _) in done.iter() {
+        film.resolve_into(&tiles[*index], frame);
     }
 }
 
@@ -88,9 +187,17 @@ pub fn render_naive(
 mod tests {
     use super::*;
     use crate::renderer::core::{color::Color, vector::Vec3};
+    use crate::renderer::execute::tile::make_tiles;
+    use crate::renderer::film::{FilmTile, Filter, FilterType};
+    use crate::renderer::options::{RenderOptions, TileOrder};
+    use crate::renderer::progress::{CancelToken, Progress};
     use crate::renderer::scene::camera::{Camera, CameraConfig};
     use crate::renderer::scene::world::Region;
-    use image::{ImageBuffer, RgbImage};
+    use std::sync::Arc;
+
+    fn full_image(w: u32, h: u32) -> Vec<Tile> {
+        make_tiles((0, 0, w, h), 1, TileOrder::Hilbert)
+    }
 
     // Render a bizarre value out for testing purposes.
     pub fn render_test_pixel(
@@ -98,15 +205,21 @@ mod tests {
         _world: &Region,
         x: usize,
         y: usize,
-    ) -> Color {
+        film: &mut FilmTile,
+    ) -> u32 {
         println!("{} {}", x, y);
-        Color::new(0.013 * (x as f32), 0.017 * (y as f32), 0.21)
+        film.add_sample(
+            (x as f32 + 0.5, y as f32 + 0.5),
+            Color::new(0.013 * (x as f32), 0.017 * (y as f32), 0.21),
+            &[],
+        );
+        (x + y) as u32
     }
 
     #[test]
     #[should_panic]
     fn test_naive_empty_image() {
-        let mut img = vec![0; 0];
+        let mut img = vec![0.0; 0];
         let ctx = RenderContext::new(
             Camera::new(
                 CameraConfig {
@@ -115,6 +228,8 @@ mod tests {
                     up: Vec3::new(0.0, 1.0, 0.0),
                     aperture: 2.0,
                     focal_distance: 1.0,
+                    shutter_open: 0.0,
+                    shutter_close: 0.0,
                     vertical_fov: 90.0,
                 },
                 (2, 2),
@@ -129,13 +244,20 @@ mod tests {
 
         let r = Region::new(Color::new(0.13, 0.17, 0.23));
 
-        render_naive(&ctx, &r, &mut img, (2, 2), render_test_pixel);
+        render_naive(
+            &ctx,
+            &r,
+            &mut FrameBuffer::new(&mut img, (2, 2)),
+            &full_image(2, 2),
+            &RenderMonitor::new(&full_image(2, 2), &RenderOptions::default()),
+            render_test_pixel,
+        );
     }
 
     #[test]
     #[should_panic]
     fn test_empty_image() {
-        let mut img = vec![0; 0];
+        let mut img = vec![0.0; 0];
         let ctx = RenderContext::new(
             Camera::new(
                 CameraConfig {
@@ -144,6 +266,8 @@ mod tests {
                     up: Vec3::new(0.0, 1.0, 0.0),
                     aperture: 2.0,
                     focal_distance: 1.0,
+                    shutter_open: 0.0,
+                    shutter_close: 0.0,
                     vertical_fov: 90.0,
                 },
                 (2, 2),
@@ -158,15 +282,23 @@ mod tests {
 
         let r = Region::new(Color::new(0.13, 0.17, 0.23));
 
-        match render_threaded(&ctx, &r, &mut img, (2, 2), render_test_pixel) {
-            Err(_) => panic!("we expect this"),
-            _ => {}
+        if render_threaded(
+            &ctx,
+            &r,
+            &mut FrameBuffer::new(&mut img, (2, 2)),
+            &full_image(2, 2),
+            &RenderMonitor::new(&full_image(2, 2), &RenderOptions::default()),
+            render_test_pixel,
+        )
+        .is_err()
+        {
+            panic!("we expect this")
         }
     }
 
     #[test]
     fn test_render_full_naive() {
-        let mut img = vec![0; 2 * 2 * 3];
+        let mut img = vec![0.0; 2 * 2 * 3];
         let ctx = RenderContext::new(
             Camera::new(
                 CameraConfig {
@@ -175,6 +307,8 @@ mod tests {
                     up: Vec3::new(0.0, 1.0, 0.0),
                     aperture: 2.0,
                     focal_distance: 1.0,
+                    shutter_open: 0.0,
+                    shutter_close: 0.0,
                     vertical_fov: 90.0,
                 },
                 (2, 2),
@@ -188,25 +322,27 @@ mod tests {
         );
         let r = Region::new(Color::new(1.0, 1.0, 1.0));
 
-        render_naive(&ctx, &r, &mut img, (2, 2), render_test_pixel);
+        render_naive(
+            &ctx,
+            &r,
+            &mut FrameBuffer::new(&mut img, (2, 2)),
+            &full_image(2, 2),
+            &RenderMonitor::new(&full_image(2, 2), &RenderOptions::default()),
+            render_test_pixel,
+        );
 
         for y in 0..2 {
             for x in 0..2 {
-                let (r, g, b) = convert_pixel(Vec3 {
-                    x: 0.013 * (x as f32),
-                    y: 0.017 * (y as f32),
-                    z: 0.21,
-                });
-                assert_eq!(img[3 * (2 * y + x) + 0], r);
-                assert_eq!(img[3 * (2 * y + x) + 1], g);
-                assert_eq!(img[3 * (2 * y + x) + 2], b);
+                assert_eq!(img[3 * (2 * y + x)], 0.013 * (x as f32));
+                assert_eq!(img[3 * (2 * y + x) + 1], 0.017 * (y as f32));
+                assert_eq!(img[3 * (2 * y + x) + 2], 0.21);
             }
         }
     }
 
     #[test]
     fn test_render_full_threaded() {
-        let mut img: RgbImage = ImageBuffer::new(2, 2);
+        let mut img = vec![0.0; 2 * 2 * 3];
         let ctx = RenderContext::new(
             Camera::new(
                 CameraConfig {
@@ -215,6 +351,8 @@ mod tests {
                     up: Vec3::new(0.0, 1.0, 0.0),
                     aperture: 2.0,
                     focal_distance: 1.0,
+                    shutter_open: 0.0,
+                    shutter_close: 0.0,
                     vertical_fov: 90.0,
                 },
                 (2, 2),
@@ -228,20 +366,253 @@ mod tests {
         );
         let r = Region::new(Color::new(1.0, 1.0, 1.0));
 
-        match render_threaded(&ctx, &r, &mut img, (2, 2), render_test_pixel) {
-            Err(_) => panic!("we don't expect this"),
-            _ => {}
+        if render_threaded(
+            &ctx,
+            &r,
+            &mut FrameBuffer::new(&mut img, (2, 2)),
+            &full_image(2, 2),
+            &RenderMonitor::new(&full_image(2, 2), &RenderOptions::default()),
+            render_test_pixel,
+        )
+        .is_err()
+        {
+            panic!("we don't expect this")
+        }
+
+        for (i, pixel) in img.chunks(3).enumerate() {
+            let (x, y) = (i % 2, i / 2);
+            assert_eq!(pixel[0], 0.013 * (x as f32));
+            assert_eq!(pixel[1], 0.017 * (y as f32));
+            assert_eq!(pixel[2], 0.21);
+        }
+    }
+
+    #[test]
+    fn test_render_tiles() {
+        let (w, h) = (37, 23);
+        let ctx = RenderContext::new(
+            Camera::new(
+                CameraConfig {
+                    look_from: Vec3::new(0.0, 0.0, 0.0),
+                    look_at: Vec3::new(0.0, 0.0, -1.0),
+                    up: Vec3::new(0.0, 1.0, 0.0),
+                    aperture: 2.0,
+                    focal_distance: 1.0,
+                    shutter_open: 0.0,
+                    shutter_close: 0.0,
+                    vertical_fov: 90.0,
+                },
+                (w, h),
+            ),
+            1,
+            1,
+            5,
+            3,
+            30,
+            20,
+        );
+        let r = Region::new(Color::new(1.0, 1.0, 1.0));
+
+        // only the crop window gets rendered, the rest is left alone
+        let tiles = make_tiles((5, 3, 30, 20), 8, TileOrder::Spiral);
+        let options = RenderOptions::default();
+        let monitor = RenderMonitor::new(&tiles, &options);
+        let mut naive = vec![-1.0; (w * h * 3) as usize];
+        let mut naive_counts = vec![0; (w * h) as usize];
+        render_naive(
+            &ctx,
+            &r,
+            &mut FrameBuffer::new(&mut naive, (w, h)).with_sample_counts(&mut naive_counts),
+            &tiles,
+            &monitor,
+            render_test_pixel,
+        );
+
+        let mut threaded = vec![-1.0; (w * h * 3) as usize];
+        let mut threaded_counts = vec![0; (w * h) as usize];
+        if render_threaded(
+            &ctx,
+            &r,
+            &mut FrameBuffer::new(&mut threaded, (w, h)).with_sample_counts(&mut threaded_counts),
+            &tiles,
+            &monitor,
+            render_test_pixel,
+        )
+        .is_err()
+        {
+            panic!("we don't expect this")
+        }
+
+        for y in 0..h {
+            for x in 0..w {
+                let i = 3 * (y * w + x) as usize;
+                if (5..30).contains(&x) && (3..20).contains(&y) {
+                    assert_eq!(naive[i], 0.013 * (x as f32));
+                    assert_eq!(naive[i + 1], 0.017 * (y as f32));
+                    assert_eq!(naive[i + 2], 0.21);
+                    assert_eq!(naive_counts[(y * w + x) as usize], x + y);
+                } else {
+                    assert_eq!(naive[i], -1.0);
+                    assert_eq!(naive_counts[(y * w + x) as usize], 0);
+                }
+            }
+        }
+        assert_eq!(naive, threaded);
+        assert_eq!(naive_counts, threaded_counts);
+    }
+
+    #[test]
+    fn test_render_cancelled() {
+        let (w, h) = (16, 16);
+        let ctx = RenderContext::new(
+            Camera::new(
+                CameraConfig {
+                    look_from: Vec3::new(0.0, 0.0, 0.0),
+                    look_at: Vec3::new(0.0, 0.0, -1.0),
+                    up: Vec3::new(0.0, 1.0, 0.0),
+                    aperture: 2.0,
+                    focal_distance: 1.0,
+                    shutter_open: 0.0,
+                    shutter_close: 0.0,
+                    vertical_fov: 90.0,
+                },
+                (w, h),
+            ),
+            1,
+            1,
+            0,
+            0,
+            w,
+            h,
+        );
+        let r = Region::new(Color::new(1.0, 1.0, 1.0));
+        let tiles = make_tiles((0, 0, w, h), 8, TileOrder::Scanline);
+
+        // cancel as soon as the first tile is done
+        let cancel_after_first_tile = || {
+            let token = CancelToken::new();
+            let cancel = token.clone();
+            RenderOptions {
+                progress: Some(Arc::new(move |_: &Progress| cancel.cancel())),
+                cancel: Some(token),
+                ..RenderOptions::default()
+            }
+        };
+
+        let options = cancel_after_first_tile();
+        let monitor = RenderMonitor::new(&tiles, &options);
+        let mut naive = vec![-1.0; (w * h * 3) as usize];
+        render_naive(
+            &ctx,
+            &r,
+            &mut FrameBuffer::new(&mut naive, (w, h)),
+            &tiles,
+            &monitor,
+            render_test_pixel,
+        );
+        assert_eq!(monitor.tiles_done(), 1);
+
+        // only the first tile was written
+        for y in 0..h {
+            for x in 0..w {
+                let i = 3 * (y * w + x) as usize;
+                if x < 8 && y < 8 {
+                    assert_eq!(naive[i + 2], 0.21);
+                } else {
+                    assert_eq!(naive[i + 2], -1.0);
+                }
+            }
         }
 
-        for (x, y, pixel) in img.enumerate_pixels() {
-            let (r, g, b) = convert_pixel(Vec3 {
-                x: 0.013 * (x as f32),
-                y: 0.017 * (y as f32),
-                z: 0.21,
-            });
-            assert_eq!(pixel[0], r);
-            assert_eq!(pixel[1], g);
-            assert_eq!(pixel[2], b);
+        // the other threads drop the tiles they're on, so only whole tiles
+        // are written
+        let options = cancel_after_first_tile();
+        let monitor = RenderMonitor::new(&tiles, &options);
+        let mut threaded = vec![-1.0; (w * h * 3) as usize];
+        if render_threaded(
+            &ctx,
+            &r,
+            &mut FrameBuffer::new(&mut threaded, (w, h)),
+            &tiles,
+            &monitor,
+            render_test_pixel,
+        )
+        .is_err()
+        {
+            panic!("we don't expect this")
+        }
+        assert!(monitor.tiles_done() >= 1);
+        for tile in tiles.iter() {
+            let corner = threaded[3 * (tile.y0 * w + tile.x0) as usize + 2];
+            let last = threaded[3 * ((tile.y1 - 1) * w + tile.x1 - 1) as usize + 2];
+            assert_eq!(corner, last);
         }
     }
+
+    #[test]
+    fn test_render_seeded() {
+        use crate::renderer::execute::render_op::render_pixel;
+        use crate::renderer::scene::materials::Material;
+        use crate::renderer::scene::objects::sphere::Sphere;
+
+        let (w, h) = (12, 10);
+        let mut r = Region::new(Color::new(0.5, 0.7, 1.0));
+        r.push(Box::new(Sphere {
+            center: Vec3::new(0.0, 0.0, -2.0),
+            radius: 1.0,
+            material: Material::Lambert {
+                albedo: Color::new(0.8, 0.3, 0.3).into(),
+            },
+        }));
+        r.recalculate_bounds();
+
+        let tiles = make_tiles((0, 0, w, h), 4, TileOrder::Hilbert);
+        let options = RenderOptions::default();
+        let render = |seed: u64, threaded: bool, filter: Filter| {
+            let ctx = RenderContext::new(
+                Camera::new(
+                    CameraConfig {
+                        look_from: Vec3::new(0.0, 0.0, 0.0),
+                        look_at: Vec3::new(0.0, 0.0, -1.0),
+                        up: Vec3::new(0.0, 1.0, 0.0),
+                        aperture: 0.1,
+                        focal_distance: 2.0,
+                        shutter_open: 0.0,
+                        shutter_close: 0.0,
+                        vertical_fov: 90.0,
+                    },
+                    (w, h),
+                ),
+                8,
+                4,
+                0,
+                0,
+                w,
+                h,
+            )
+            .with_seed(seed)
+            .with_filter(filter);
+            let monitor = RenderMonitor::new(&tiles, &options);
+            let mut img = vec![0.0; (w * h * 3) as usize];
+            let mut frame = FrameBuffer::new(&mut img, (w, h));
+            if threaded {
+                render_threaded(&ctx, &r, &mut frame, &tiles, &monitor, render_pixel).unwrap();
+            } else {
+                render_naive(&ctx, &r, &mut frame, &tiles, &monitor, render_pixel);
+            }
+            img
+        };
+
+        // the same seed renders the same image, however it was split up
+        let naive = render(7, false, Filter::default());
+        assert_eq!(naive, render(7, true, Filter::default()));
+        assert_eq!(naive, render(7, false, Filter::default()));
+        assert_ne!(naive, render(8, false, Filter::default()));
+
+        // even when the samples reach into the tiles next to them
+        let mitchell = Filter::new(FilterType::Mitchell);
+        let naive = render(7, false, mitchell);
+        assert_eq!(naive, render(7, true, mitchell));
+        assert!(naive.iter().all(|v| v.is_finite() && *v >= 0.0));
+    }
 }
@@ -1,7 +1,10 @@
+use crate::renderer::framebuffer::FrameBuffer;
 use crate::renderer::scene::world::Region;
 
 use super::context::RenderContext;
 use super::error::ComputeError;
+use super::monitor::RenderMonitor;
+use super::tile::Tile;
 use log::warn;
 
 /// A paper thin facade for a CUDA based render. This would be the
@@ -9,14 +12,22 @@ use log::warn;
 pub fn render_cuda(
     context: &RenderContext,
     world: &Region,
-    pixels: &mut [u8],
-    bounds: (u32, u32)
+    frame: &mut FrameBuffer,
+    tiles: &[Tile],
+    monitor: &RenderMonitor,
 ) -> Result<(), ComputeError> {
     // NOTE: when adding CUDA support, make sure to use this code
     // to fall back in case the user isn't on a nVidia card.
     warn!("CUDA not supported yet, reverting to multithreaded CPU.");
 
-    super::cpurender::render_threaded(context, world, pixels, bounds, super::render_op::render_pixel)?;
+    super::cpurender::render_threaded(
+        context,
+        world,
+        frame,
+        tiles,
+        monitor,
+        super::render_op::render_pixel,
+    )?;
 
     Ok(())
 }
@@ -26,14 +37,22 @@ pub fn render_cuda(
 pub fn render_opencl(
     context: &RenderContext,
     world: &Region,
-    pixels: &mut [u8],
-    bounds: (u32, u32)
+    frame: &mut FrameBuffer,
+    tiles: &[Tile],
+    monitor: &RenderMonitor,
 ) -> Result<(), ComputeError> {
     // NOTE: when adding CUDA support, make sure to use this code
     // to fall back in case the user isn't on an OpenCL environment.
     warn!("OpenCL not supported yet, reverting to multithreaded CPU.");
 
-    super::cpurender::render_threaded(context, world, pixels, bounds, super::render_op::render_pixel)?;
+    super::cpurender::render_threaded(
+        context,
+        world,
+        frame,
+        tiles,
+        monitor,
+        super::render_op::render_pixel,
+    )?;
 
     Ok(())
 }
@@ -1,8 +1,11 @@
 //! Module for the to contain the compute paths of the ray tracer algorithm.
 
+pub mod accumulator;
 pub mod context;
 pub mod cpurender;
 pub mod error;
 pub mod gpurender;
+pub mod monitor;
 
 pub mod render_op;
+pub mod tile;
@@ -0,0 +1,130 @@
+use crate::renderer::options::RenderOptions;
+use crate::renderer::progress::{CancelToken, Progress, ProgressCallback};
+
+use super::tile::Tile;
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Keeps track of the finished tiles of a render, reports the progress and
+/// checks for cancellation on behalf of the render threads.
+pub struct RenderMonitor<'a> {
+    tiles_total: usize,
+    pixels_total: usize,
+    // (tiles, pixels) done, locked for the callback so the reports are in
+    // order and one at a time
+    done: Mutex<(usize, usize)>,
+    start: Instant,
+    callback: Option<&'a ProgressCallback>,
+    cancel: Option<&'a CancelToken>,
+}
+
+impl<'a> RenderMonitor<'a> {
+    /// Creates a monitor for rendering the tiles with the options.
+    pub fn new(tiles: &[Tile], options: &'a RenderOptions) -> RenderMonitor<'a> {
+        RenderMonitor {
+            tiles_total: tiles.len(),
+            pixels_total: tiles.iter().map(|t| t.len()).sum(),
+            done: Mutex::new((0, 0)),
+            start: Instant::now(),
+            callback: options.progress.as_ref(),
+            cancel: options.cancel.as_ref(),
+        }
+    }
+
+    /// Whether the render has been asked to stop.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_some_and(|c| c.is_cancelled())
+    }
+
+    /// Records a finished tile and reports the progress.
+    pub fn tile_done(&self, tile: &Tile) {
+        // a poisoned lock only means another thread panicked, which is
+        // reported by the render itself
+        let mut done = match self.done.lock() {
+            Ok(done) => done,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        done.0 += 1;
+        done.1 += tile.len();
+
+        if let Some(callback) = self.callback {
+            callback(&self.progress(done.0, done.1));
+        }
+    }
+
+    /// The number of tiles that have finished.
+    pub fn tiles_done(&self) -> usize {
+        match self.done.lock() {
+            Ok(done) => done.0,
+            Err(poisoned) => poisoned.into_inner().0,
+        }
+    }
+
+    /// The number of tiles in the render.
+    pub fn tiles_total(&self) -> usize {
+        self.tiles_total
+    }
+
+    fn progress(&self, tiles_done: usize, pixels_done: usize) -> Progress {
+        let elapsed = self.start.elapsed();
+        let eta = if pixels_done > 0 {
+            let remaining = (self.pixels_total - pixels_done) as f64 / pixels_done as f64;
+            Some(Duration::from_secs_f64(elapsed.as_secs_f64() * remaining))
+        } else {
+            None
+        };
+
+        Progress {
+            tiles_done,
+            tiles_total: self.tiles_total,
+            pixels_done,
+            pixels_total: self.pixels_total,
+            elapsed,
+            eta,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::execute::tile::make_tiles;
+    use crate::renderer::options::TileOrder;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_monitor() {
+        let tiles = make_tiles((0, 0, 20, 10), 10, TileOrder::Scanline);
+        let reports = Arc::new(Mutex::new(Vec::new()));
+
+        let token = CancelToken::new();
+        let shared = reports.clone();
+        let options = RenderOptions {
+            progress: Some(Arc::new(move |p: &Progress| {
+                shared.lock().unwrap().push(*p);
+            })),
+            cancel: Some(token.clone()),
+            ..RenderOptions::default()
+        };
+
+        let monitor = RenderMonitor::new(&tiles, &options);
+        assert_eq!(monitor.tiles_total(), 2);
+        assert!(!monitor.is_cancelled());
+
+        monitor.tile_done(&tiles[0]);
+        monitor.tile_done(&tiles[1]);
+        assert_eq!(monitor.tiles_done(), 2);
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].tiles_done, 1);
+        assert_eq!(reports[0].pixels_done, 100);
+        assert_eq!(reports[0].pixels_total, 200);
+        assert!(reports[0].eta.is_some());
+        assert_eq!(reports[1].eta, Some(Duration::from_secs(0)));
+
+        token.cancel();
+        assert!(monitor.is_cancelled());
+    }
+}
@@ -1,78 +1,152 @@
 use crate::renderer::core::color;
-use crate::renderer::core::color::Color;
-use crate::renderer::core::ray::Ray;
-use crate::renderer::core::vector;
-use crate::renderer::scene::hittable::Hittable;
+use crate::renderer::film::FilmTile;
 use crate::renderer::scene::world::Region;
-use rand::Rng;
 
 use super::context::RenderContext;
 
-/// A utility function to loop through a random set of samples around the given ray
-/// and average out the rendered colors for each ray to get the pixel value.
-pub fn render_pixel(ctx_arc: &RenderContext, world: &Region, x: usize, y: usize) -> Color {
-    let mut pixel = Color::new(0.0, 0.0, 0.0);
+/// Dark pixels are noisy in relative terms even when they look converged,
+/// so the error of a pixel is measured against at least this brightness.
+const ERROR_FLOOR: f32 = 0.01;
+
+/// A utility function to loop through a random set of samples within the pixel
+/// and add the rendered color of each to the film, which weighs them into the
+/// pixels around them. Returns the number of samples taken.
+///
+/// With adaptive sampling, the pixel stops early once the standard error of
+/// its mean is small enough relative to its brightness, tracked with
+/// Welford's running variance of the luminance.
+pub fn render_pixel(
+    ctx_arc: &RenderContext,
+    world: &Region,
+    x: usize,
+    y: usize,
+    film: &mut FilmTile,
+) -> u32 {
     let w = ctx_arc.camera.film_width as usize;
     let h = ctx_arc.camera.film_height as usize;
-    let pixel_scale = 1.0 / (ctx_arc.samples as f32);
 
-    for _n in 0..ctx_arc.samples {
-        let mut rng = rand::thread_rng();
-        let randx = rng.gen_range(0.0..1.0);
-        let randy = rng.gen_range(0.0..1.0);
+    // running mean and sum of squared differences of the luminance
+    let mut mean = 0.0;
+    let mut m2 = 0.0;
+    let mut samples = 0;
+    let mut aovs = Vec::with_capacity(ctx_arc.aovs.len());
+    let needs_hit = ctx_arc.aovs.iter().any(|aov| aov.needs_hit());
+
+    let mut sampler = ctx_arc.sampler.create(ctx_arc.samples, ctx_arc.seed);
+
+    while samples < ctx_arc.samples {
+        sampler.start_sample((x as u32, y as u32), ctx_arc.first_sample + samples);
+
+        // the rows of the image go down and the camera's go up
+        let (randx, randy) = sampler.get_2d();
         let u = ((x as f32) + randx) / ((w - 1) as f32);
-        let v = (((h - y) as f32) + randy) / ((h - 1) as f32);
+        let v = (((h - y) as f32) + 1.0 - randy) / ((h - 1) as f32);
 
-        let r = ctx_arc.camera.get_ray(u, v, 1.0);
+        let r = ctx_arc.camera.get_ray(u, v, 1.0, sampler.as_mut());
 
-        pixel += ray_color(&r, world, ctx_arc.max_depth);
-    }
+        let light = ctx_arc
+            .integrator
+            .ray_components(&r, world, sampler.as_mut());
+        let sample = light.total();
 
-    pixel *= pixel_scale;
+        // most of the output variables come from the first hit of the
+        // camera ray, the rest from the light of the sample
+        if !ctx_arc.aovs.is_empty() {
+            let hit = if needs_hit {
+                world.hit_object(&r, 0.001, f32::INFINITY)
+            } else {
+                None
+            };
+            aovs.clear();
+            aovs.extend(
+                ctx_arc
+                    .aovs
+                    .iter()
+                    .map(|aov| aov.value(&r, hit.as_ref(), &light, world)),
+            );
+        }
 
-    pixel
-}
+        film.add_sample((x as f32 + randx, y as f32 + randy), sample, &aovs);
+        samples += 1;
 
-/// A utility to calculate the color of a single ray. This is the meat
-/// of the raytracer algorithm. It uses an iterative loop rather than the
-/// classical recursive loop in order to avoid unnecessary memory use for
-/// multithreading.
-#[allow(dead_code)]
-fn ray_color_it(r: &Ray, world: &Region, max_depth: u32) -> Color {
-    let mut color = color::BLACK;
-    let mut curr_ray = *r;
-
-    for n in 0..max_depth {
-        match world.hit(&curr_ray, 0.001, f32::INFINITY) {
-            Some(hit) => {
-                // randomize the ray reflection to account for the micro surface plane noise
-                // in a diffuse surface
-                let target = hit.p + hit.normal + vector::random_unit_vector();
-
-                curr_ray.orig = hit.p;
-                curr_ray.dir = target - hit.p;
-            }
-            None => {
-                let diffuse = 0.5_f32.powi((n) as i32);
-                color = diffuse * world.background_color(&curr_ray);
-                return color;
+        let adaptive = match ctx_arc.adaptive {
+            Some(adaptive) => adaptive,
+            None => continue,
+        };
+
+        let luminance = color::luminance(sample);
+        let delta = luminance - mean;
+        mean += delta / samples as f32;
+        m2 += delta * (luminance - mean);
+
+        if samples >= adaptive.min_samples {
+            let variance = m2 / (samples - 1) as f32;
+            let error = (variance / samples as f32).sqrt() / mean.max(ERROR_FLOOR);
+            if error < adaptive.threshold {
+                break;
             }
         }
     }
 
-    color
+    samples
 }
 
-fn ray_color(r: &Ray, world: &Region, depth: u32) -> Color {
-    if depth == 0 {
-        return color::BLACK;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color::Color;
+    use crate::renderer::execute::tile::Tile;
+    use crate::renderer::framebuffer::FrameBuffer;
+    use crate::renderer::options::AdaptiveSampling;
+    use crate::renderer::scene::camera::Camera;
+    use crate::renderer::scene::world::Background;
+
+    fn test_context(world: &Region, samples: u32) -> RenderContext {
+        RenderContext::new(
+            Camera::new(world.camera_config, (4, 4)),
+            4,
+            samples,
+            0,
+            0,
+            4,
+            4,
+        )
+    }
+
+    /// Renders pixel (1, 1) and returns its color and samples.
+    fn render_test_pixel(ctx: &RenderContext, world: &Region) -> (Color, u32) {
+        let tile = Tile {
+            x0: 1,
+            y0: 1,
+            x1: 2,
+            y1: 2,
+        };
+        let mut film = FilmTile::new(&tile, ctx.filter, (4, 4));
+        let samples = render_pixel(ctx, world, 1, 1, &mut film);
+
+        let mut pixels = vec![0.0; 4 * 4 * 3];
+        film.resolve_into(&tile, &mut FrameBuffer::new(&mut pixels, (4, 4)));
+        (Color::new(pixels[15], pixels[16], pixels[17]), samples)
     }
 
-    match world.hit(r, 0.001, f32::INFINITY) {
-        Some(hit) => match hit.material.scatter(r, &hit) {
-            Some((scattered, attenuation)) => attenuation * ray_color(&scattered, world, depth - 1),
-            None => color::BLACK,
-        },
-        None => world.background_color(r),
+    #[test]
+    fn test_render_pixel_adaptive() {
+        // the background is the same in every direction, so the pixel has
+        // no variance and stops as soon as it may
+        let mut world = Region::new(color::BLACK);
+        world.background = Background::Solid(Color::new(0.5, 0.5, 0.5));
+
+        let ctx = test_context(&world, 64);
+        let (color, samples) = render_test_pixel(&ctx, &world);
+        assert_eq!(samples, 64);
+        assert!((color.x - 0.5).abs() < 1e-5);
+
+        let ctx = test_context(&world, 64).with_adaptive(Some(AdaptiveSampling {
+            min_samples: 4,
+            threshold: 0.01,
+        }));
+        let (color, samples) = render_test_pixel(&ctx, &world);
+        assert_eq!(samples, 4);
+        assert!((color.x - 0.5).abs() < 1e-5);
     }
 }
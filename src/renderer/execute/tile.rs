@@ -0,0 +1,180 @@
+use crate::renderer::options::TileOrder;
+
+/// A square section of the image, or smaller on the right and bottom
+/// edges, that is rendered as one unit of work. The coordinates are in
+/// pixels of the full image, from the top left corner up to, but not
+/// including, the bottom right corner.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tile {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl Tile {
+    /// The number of pixels in the tile.
+    pub fn len(&self) -> usize {
+        ((self.x1 - self.x0) * (self.y1 - self.y0)) as usize
+    }
+}
+
+/// Splits the area from (x0, y0) up to (x1, y1) into tiles of the size and
+/// sorts them into the order they should be rendered in.
+pub fn make_tiles(area: (u32, u32, u32, u32), size: u32, order: TileOrder) -> Vec<Tile> {
+    let (x0, y0, x1, y1) = area;
+    let nx = (x1 - x0).div_ceil(size);
+    let ny = (y1 - y0).div_ceil(size);
+
+    let tile = |(i, j): (u32, u32)| Tile {
+        x0: x0 + i * size,
+        y0: y0 + j * size,
+        x1: (x0 + (i + 1) * size).min(x1),
+        y1: (y0 + (j + 1) * size).min(y1),
+    };
+
+    let mut grid: Vec<(u32, u32)> = (0..ny).flat_map(|j| (0..nx).map(move |i| (i, j))).collect();
+
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Hilbert => {
+            let n = nx.max(ny).next_power_of_two();
+            grid.sort_by_key(|(i, j)| hilbert_index(n, *i, *j));
+        }
+        TileOrder::Spiral => grid = spiral(nx, ny),
+    }
+
+    grid.into_iter().map(tile).collect()
+}
+
+/// Returns the distance along the Hilbert curve that fills the n by n
+/// grid, where n is a power of two, of the cell (x, y).
+fn hilbert_index(n: u32, x: u32, y: u32) -> u64 {
+    let (mut x, mut y) = (x, y);
+    let mut d: u64 = 0;
+    let mut s = n / 2;
+
+    while s > 0 {
+        let rx = ((x & s) > 0) as u32;
+        let ry = ((y & s) > 0) as u32;
+        d += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+
+        // rotate the quadrant so the curve stays continuous
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+
+        s /= 2;
+    }
+
+    d
+}
+
+/// Walks the nx by ny grid in a square spiral starting from the center.
+fn spiral(nx: u32, ny: u32) -> Vec<(u32, u32)> {
+    let total = (nx * ny) as usize;
+    let mut cells = Vec::with_capacity(total);
+
+    let (mut x, mut y) = ((nx as i64 - 1) / 2, (ny as i64 - 1) / 2);
+    let (mut dx, mut dy) = (1, 0);
+    let mut leg = 1;
+
+    // the legs of the spiral grow by one every second turn. Cells outside
+    // of the grid are skipped, but the walk continues until it has covered
+    // all of them.
+    while cells.len() < total {
+        for _ in 0..2 {
+            for _ in 0..leg {
+                if x >= 0 && y >= 0 && x < nx as i64 && y < ny as i64 {
+                    cells.push((x as u32, y as u32));
+                }
+                x += dx;
+                y += dy;
+            }
+            // turn
+            let t = dx;
+            dx = -dy;
+            dy = t;
+        }
+        leg += 1;
+    }
+
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_coverage(tiles: &[Tile], area: (u32, u32, u32, u32)) {
+        let (x0, y0, x1, y1) = area;
+        let w = (x1 - x0) as usize;
+        let mut covered = vec![0; w * (y1 - y0) as usize];
+
+        for tile in tiles.iter() {
+            assert!(tile.len() > 0);
+            for y in tile.y0..tile.y1 {
+                for x in tile.x0..tile.x1 {
+                    assert!(x >= x0 && x < x1 && y >= y0 && y < y1);
+                    covered[(y - y0) as usize * w + (x - x0) as usize] += 1;
+                }
+            }
+        }
+
+        assert!(covered.iter().all(|c| *c == 1));
+    }
+
+    #[test]
+    fn test_tile_coverage() {
+        let areas = [
+            (0, 0, 100, 70),
+            (13, 7, 50, 61),
+            (0, 0, 32, 32),
+            (5, 5, 6, 6),
+        ];
+
+        for area in areas.iter() {
+            for order in [TileOrder::Hilbert, TileOrder::Spiral, TileOrder::Scanline].iter() {
+                check_coverage(&make_tiles(*area, 16, *order), *area);
+            }
+        }
+
+        let tiles = make_tiles((0, 0, 100, 70), 32, TileOrder::Scanline);
+        assert_eq!(tiles.len(), 12);
+        assert_eq!(tiles[0].len(), 32 * 32);
+        assert_eq!(tiles[3].len(), 4 * 32);
+        assert_eq!(tiles[11].len(), 4 * 6);
+    }
+
+    #[test]
+    fn test_hilbert_order() {
+        // on a power of two grid every step moves to a neighbor
+        let tiles = make_tiles((0, 0, 64, 64), 8, TileOrder::Hilbert);
+        assert_eq!(tiles[0].x0, 0);
+        assert_eq!(tiles[0].y0, 0);
+
+        for pair in tiles.windows(2) {
+            let dx = (pair[0].x0 as i64 - pair[1].x0 as i64).abs();
+            let dy = (pair[0].y0 as i64 - pair[1].y0 as i64).abs();
+            assert_eq!(dx + dy, 8);
+        }
+    }
+
+    #[test]
+    fn test_spiral_order() {
+        let tiles = make_tiles((0, 0, 50, 50), 10, TileOrder::Spiral);
+
+        // starts in the middle and works its way outward
+        assert_eq!(tiles[0].x0, 20);
+        assert_eq!(tiles[0].y0, 20);
+
+        let ring = |t: &Tile| ((t.x0 as i64 - 20).abs()).max((t.y0 as i64 - 20).abs());
+        for pair in tiles.windows(2) {
+            assert!(ring(&pair[0]) <= ring(&pair[1]));
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! The reconstruction filters that decide how much a sample counts towards
+//! the pixels around it.
+
+use std::f32::consts::PI;
+
+/// The filters to choose from. All of them are separable, the weight of a
+/// sample is the product of the filter along x and along y.
+///
+/// * `Box` - Every sample within the radius counts the same. With the
+///   default radius of half a pixel, a sample only counts for the pixel it
+///   was taken in, the way the renderer always averaged its samples.
+/// * `Gaussian` - A Gaussian with a standard deviation of a third of the
+///   radius, shifted down to reach zero at the radius. Soft, without any
+///   ringing.
+/// * `Mitchell` - The Mitchell-Netravali cubic with B = C = 1/3, a
+///   compromise between blurring and ringing.
+/// * `Lanczos` - A sinc windowed by a wider sinc. The sharpest of the
+///   filters, but it rings around hard edges.
+/// * `BlackmanHarris` - The Blackman-Harris window, close to a Gaussian
+///   but slightly sharper.
+#[derive(Copy, Clone, Debug, PartialEq, Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterType {
+    #[default]
+    Box,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+    BlackmanHarris,
+}
+
+impl FilterType {
+    /// The radius of the filter, in pixels, when it isn't set.
+    pub fn default_radius(self) -> f32 {
+        match self {
+            FilterType::Box => 0.5,
+            FilterType::Gaussian => 1.5,
+            FilterType::Mitchell => 2.0,
+            FilterType::Lanczos => 3.0,
+            FilterType::BlackmanHarris => 1.5,
+        }
+    }
+}
+
+/// A reconstruction filter of the film.
+///
+/// Fields:
+/// * `filter_type` - The shape of the filter.
+/// * `radius` - How far the filter reaches from the sample along x and y,
+///   in pixels. Must be > 0.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Filter {
+    pub filter_type: FilterType,
+    pub radius: f32,
+}
+
+impl Filter {
+    /// Creates a filter of the type with its default radius.
+    pub fn new(filter_type: FilterType) -> Filter {
+        Filter {
+            filter_type,
+            radius: filter_type.default_radius(),
+        }
+    }
+
+    /// Changes the radius of the filter.
+    pub fn with_radius(mut self, radius: f32) -> Filter {
+        self.radius = radius;
+        self
+    }
+
+    /// The weight of a sample for a pixel, where `dx` and `dy` are the
+    /// offsets from the center of the pixel to the sample. Some filters
+    /// have negative lobes, so the weight can be below zero.
+    pub fn evaluate(&self, dx: f32, dy: f32) -> f32 {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    /// The weight along one axis, zero outside of the radius.
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        let r = self.radius;
+        let x = x.abs();
+        if x > r {
+            return 0.0;
+        }
+
+        match self.filter_type {
+            FilterType::Box => 1.0,
+            FilterType::Gaussian => {
+                let sigma = r / 3.0;
+                let gaussian = |x: f32| (-x * x / (2.0 * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(r)).max(0.0)
+            }
+            FilterType::Mitchell => mitchell(2.0 * x / r, 1.0 / 3.0, 1.0 / 3.0),
+            FilterType::Lanczos => sinc(x) * sinc(x / r),
+            FilterType::BlackmanHarris => {
+                // the window spans [0, 1] across the diameter of the filter
+                let t = 2.0 * PI * (0.5 + x / (2.0 * r));
+                0.35875 - 0.48829 * t.cos() + 0.14128 * (2.0 * t).cos() - 0.01168 * (3.0 * t).cos()
+            }
+        }
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter::new(FilterType::Box)
+    }
+}
+
+/// The Mitchell-Netravali cubic for x in [0, 2].
+fn mitchell(x: f32, b: f32, c: f32) -> f32 {
+    if x > 1.0 {
+        ((-b - 6.0 * c) * x * x * x
+            + (6.0 * b + 30.0 * c) * x * x
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0
+    } else {
+        ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+            + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+            + (6.0 - 2.0 * b))
+            / 6.0
+    }
+}
+
+/// The normalized sinc, sin(pi x) / (pi x).
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters() {
+        for filter_type in [
+            FilterType::Box,
+            FilterType::Gaussian,
+            FilterType::Mitchell,
+            FilterType::Lanczos,
+            FilterType::BlackmanHarris,
+        ] {
+            let filter = Filter::new(filter_type);
+            let r = filter.radius;
+
+            // peaks in the center, is symmetric and ends at the radius
+            let center = filter.evaluate(0.0, 0.0);
+            assert!(center > 0.0, "{:?}", filter_type);
+            for d in [0.1, 0.3, 0.7] {
+                let d = d * r;
+                assert!(filter.evaluate(d, 0.0) <= center, "{:?}", filter_type);
+                assert_eq!(filter.evaluate(d, 0.2), filter.evaluate(-d, -0.2));
+            }
+            if filter_type != FilterType::Box {
+                assert!(filter.evaluate(r, 0.0).abs() < 1e-4, "{:?}", filter_type);
+            }
+            assert_eq!(filter.evaluate(1.01 * r, 0.0), 0.0);
+            assert_eq!(filter.evaluate(0.0, -1.01 * r), 0.0);
+        }
+
+        assert_eq!(Filter::default().evaluate(0.5, -0.3), 1.0);
+        assert!((Filter::new(FilterType::BlackmanHarris).evaluate(0.0, 0.0) - 1.0).abs() < 1e-5);
+
+        // the cubic is continuous where its two pieces meet
+        let b = 1.0 / 3.0;
+        assert!((mitchell(1.0 - 1e-4, b, b) - mitchell(1.0 + 1e-4, b, b)).abs() < 1e-3);
+        assert!(mitchell(2.0, b, b).abs() < 1e-5);
+
+        // the negative lobes of the sharp filters
+        assert!(Filter::new(FilterType::Lanczos).evaluate(1.5, 0.0) < 0.0);
+        assert!(Filter::new(FilterType::Mitchell).evaluate(1.5, 0.0) < 0.0);
+    }
+}
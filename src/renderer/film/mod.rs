@@ -0,0 +1,405 @@
+//! The film collects the samples of the render and reconstructs the pixels
+//! from them. Every sample is weighted by the reconstruction filter into
+//! all of the pixels within the filter's radius, not only the pixel it was
+//! taken for.
+//!
+//! The tiles are rendered into their own `FilmTile`, which reaches past the
+//! edges of the tile by the radius of the filter. Once all of the tiles are
+//! done, they're merged into the `Film` in the order of the tiles, so the
+//! image comes out the same no matter which thread rendered which tile, or
+//! which tile finished first.
+//!
+//! The film also collects the arbitrary output variables of the render.
+//! Most are filtered the same way as the color, but the ids keep the value
+//! of the sample closest to the center of the pixel.
+
+pub mod filter;
+
+pub use filter::{Filter, FilterType};
+
+use crate::renderer::aov::Aov;
+use crate::renderer::core::color::{self, Color};
+use crate::renderer::core::{debug_check, write_pixel};
+use crate::renderer::execute::tile::Tile;
+use crate::renderer::framebuffer::FrameBuffer;
+
+use log::error;
+
+/// The weighted sum of the samples of a pixel and the sum of their
+/// weights.
+#[derive(Copy, Clone, Debug)]
+struct FilmPixel {
+    sum: Color,
+    weight: f32,
+}
+
+impl FilmPixel {
+    const EMPTY: FilmPixel = FilmPixel {
+        sum: color::BLACK,
+        weight: 0.0,
+    };
+
+    /// The reconstructed value of the pixel. The filters with negative
+    /// lobes can ring below zero around bright edges, which is clamped
+    /// away.
+    fn resolve(&self) -> Color {
+        if self.weight == 0.0 {
+            return color::BLACK;
+        }
+
+        let c = self.sum / self.weight;
+        Color::new(c.x.max(0.0), c.y.max(0.0), c.z.max(0.0))
+    }
+}
+
+/// An output variable of a pixel. For the filtered variables, the value is
+/// the weighted sum of the samples, sharing the weight of the `FilmPixel`.
+/// For the others, it's the value of the sample nearest to the center of
+/// the pixel, at the squared distance `nearest`.
+#[derive(Copy, Clone, Debug)]
+struct AovPixel {
+    value: Color,
+    nearest: f32,
+}
+
+impl AovPixel {
+    const EMPTY: AovPixel = AovPixel {
+        value: color::BLACK,
+        nearest: f32::INFINITY,
+    };
+
+    /// Adds the pixel of another film for the variable.
+    fn merge(&mut self, aov: Aov, other: &AovPixel) {
+        if aov.is_filtered() {
+            self.value += other.value;
+        } else if other.nearest < self.nearest {
+            *self = *other;
+        }
+    }
+
+    /// The value of the variable, with `weight` being the weight of the
+    /// color of the pixel. Unlike the color, the filtered values aren't
+    /// clamped, since normals are negative just as often as not.
+    fn resolve(&self, aov: Aov, weight: f32) -> Color {
+        if !aov.is_filtered() {
+            return self.value;
+        }
+        if weight == 0.0 {
+            return color::BLACK;
+        }
+        self.value / weight
+    }
+}
+
+/// Writes the output variables of a pixel into the buffers of the frame.
+fn write_aovs(frame: &mut FrameBuffer, aovs: &[AovPixel], weight: f32, location: usize) {
+    for ((aov, buffer), pixel) in frame.aovs.iter_mut().zip(aovs.iter()) {
+        write_pixel!(pixel.resolve(*aov, weight), buffer, location);
+    }
+}
+
+/// The samples of a single tile.
+///
+/// Fields:
+/// * `filter` - The reconstruction filter.
+/// * `x0`, `y0`, `x1`, `y1` - The pixels the samples of the tile can reach,
+///   the tile grown by the radius of the filter and clipped to the image.
+/// * `pixels` - The samples of those pixels.
+/// * `aovs` - The output variables collected along with the color.
+/// * `aov_pixels` - The output variables of the pixels, all of the
+///   variables of the first pixel, then of the second, and so on.
+pub struct FilmTile {
+    filter: Filter,
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+    pixels: Vec<FilmPixel>,
+    aovs: Vec<Aov>,
+    aov_pixels: Vec<AovPixel>,
+}
+
+impl FilmTile {
+    /// Creates an empty film for the tile of an image with the bounds.
+    pub fn new(tile: &Tile, filter: Filter, bounds: (u32, u32)) -> FilmTile {
+        // a sample reaches the pixels whose centers are within the radius
+        let pad = (filter.radius - 0.5).max(0.0).ceil() as u32;
+
+        let x0 = tile.x0.saturating_sub(pad);
+        let y0 = tile.y0.saturating_sub(pad);
+        let x1 = (tile.x1 + pad).min(bounds.0);
+        let y1 = (tile.y1 + pad).min(bounds.1);
+
+        FilmTile {
+            filter,
+            x0,
+            y0,
+            x1,
+            y1,
+            pixels: vec![FilmPixel::EMPTY; ((x1 - x0) * (y1 - y0)) as usize],
+            aovs: Vec::new(),
+            aov_pixels: Vec::new(),
+        }
+    }
+
+    /// Also collects the output variables, in the order of `aovs`.
+    pub fn with_aovs(mut self, aovs: &[Aov]) -> FilmTile {
+        self.aovs = aovs.to_vec();
+        self.aov_pixels = vec![AovPixel::EMPTY; self.pixels.len() * aovs.len()];
+        self
+    }
+
+    /// Adds a sample to the pixels around it.
+    ///
+    /// Parameters:
+    /// * `position` - Where the sample was taken on the image, in pixels
+    ///   from the top left corner. Pixel (x, y) covers [x, x + 1) by
+    ///   [y, y + 1).
+    /// * `color` - The color of the sample.
+    /// * `aovs` - The values of the output variables of the sample, in the
+    ///   order of the film's variables.
+    pub fn add_sample(&mut self, position: (f32, f32), color: Color, aovs: &[Color]) {
+        debug_check!(aovs.len() == self.aovs.len());
+
+        let (px0, px1) = self.reach(position.0, self.x0, self.x1);
+        let (py0, py1) = self.reach(position.1, self.y0, self.y1);
+        let (sx, sy) = (position.0.floor() as u32, position.1.floor() as u32);
+
+        let w = (self.x1 - self.x0) as usize;
+        let n = self.aovs.len();
+        for y in py0..py1 {
+            let dy = position.1 - (y as f32 + 0.5);
+            for x in px0..px1 {
+                let dx = position.0 - (x as f32 + 0.5);
+                let weight = self.filter.evaluate(dx, dy);
+
+                let location = (y - self.y0) as usize * w + (x - self.x0) as usize;
+                let pixel = &mut self.pixels[location];
+                pixel.sum += weight * color;
+                pixel.weight += weight;
+
+                let own_pixel = x == sx && y == sy;
+                let distance = dx * dx + dy * dy;
+                let aov_pixels = &mut self.aov_pixels[location * n..(location + 1) * n];
+                for ((aov, pixel), value) in self.aovs.iter().zip(aov_pixels).zip(aovs) {
+                    if aov.is_filtered() {
+                        pixel.value += weight * *value;
+                    } else if own_pixel && distance < pixel.nearest {
+                        pixel.value = *value;
+                        pixel.nearest = distance;
+                    }
+                }
+            }
+        }
+    }
+
+    /// The pixels along an axis, within the range [lo, hi), that the
+    /// sample at p reaches. A sample exactly on the radius only counts for
+    /// the pixel after it, so that with the box filter every sample counts
+    /// for exactly one pixel.
+    fn reach(&self, p: f32, lo: u32, hi: u32) -> (u32, u32) {
+        let r = self.filter.radius;
+        let first = ((p - 0.5 - r).floor() as i64 + 1).max(lo as i64);
+        let last = ((p - 0.5 + r).floor() as i64 + 1).min(hi as i64);
+        (first as u32, last.max(first) as u32)
+    }
+
+    /// Writes the pixels of the tile into the buffers of the image, with
+    /// only the samples of this tile. The samples of the tiles next to it
+    /// are missing until the film is resolved.
+    pub fn resolve_into(&self, tile: &Tile, frame: &mut FrameBuffer) {
+        debug_check!(frame.aovs.len() == self.aovs.len());
+
+        let w = (self.x1 - self.x0) as usize;
+        let n = self.aovs.len();
+        for y in tile.y0..tile.y1 {
+            for x in tile.x0..tile.x1 {
+                let from = (y - self.y0) as usize * w + (x - self.x0) as usize;
+                let to = (y * frame.bounds.0 + x) as usize;
+                let pixel = &self.pixels[from];
+                write_pixel!(pixel.resolve(), frame.pixels, to);
+                write_aovs(
+                    frame,
+                    &self.aov_pixels[from * n..(from + 1) * n],
+                    pixel.weight,
+                    to,
+                );
+            }
+        }
+    }
+}
+
+/// The samples of the full image.
+pub struct Film {
+    bounds: (u32, u32),
+    pixels: Vec<FilmPixel>,
+    aovs: Vec<Aov>,
+    aov_pixels: Vec<AovPixel>,
+}
+
+impl Film {
+    /// Creates an empty film for an image with the bounds, collecting the
+    /// output variables `aovs`.
+    pub fn new(bounds: (u32, u32), aovs: &[Aov]) -> Film {
+        let len = (bounds.0 * bounds.1) as usize;
+        Film {
+            bounds,
+            pixels: vec![FilmPixel::EMPTY; len],
+            aovs: aovs.to_vec(),
+            aov_pixels: vec![AovPixel::EMPTY; len * aovs.len()],
+        }
+    }
+
+    /// Adds the samples of a tile to the film.
+    pub fn merge(&mut self, tile: &FilmTile) {
+        debug_check!(tile.aovs == self.aovs);
+
+        let tw = (tile.x1 - tile.x0) as usize;
+        let n = self.aovs.len();
+        for y in tile.y0..tile.y1 {
+            for x in tile.x0..tile.x1 {
+                let from = (y - tile.y0) as usize * tw + (x - tile.x0) as usize;
+                let to = (y * self.bounds.0 + x) as usize;
+                self.pixels[to].sum += tile.pixels[from].sum;
+                self.pixels[to].weight += tile.pixels[from].weight;
+
+                for (i, aov) in self.aovs.iter().enumerate() {
+                    self.aov_pixels[to * n + i].merge(*aov, &tile.aov_pixels[from * n + i]);
+                }
+            }
+        }
+    }
+
+    /// Writes the pixels of the tile into the buffers of the image.
+    pub fn resolve_into(&self, tile: &Tile, frame: &mut FrameBuffer) {
+        debug_check!(frame.aovs.len() == self.aovs.len());
+
+        let n = self.aovs.len();
+        for y in tile.y0..tile.y1 {
+            for x in tile.x0..tile.x1 {
+                let location = (y * self.bounds.0 + x) as usize;
+                let pixel = &self.pixels[location];
+                write_pixel!(pixel.resolve(), frame.pixels, location);
+                write_aovs(
+                    frame,
+                    &self.aov_pixels[location * n..(location + 1) * n],
+                    pixel.weight,
+                    location,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_box_film_tile() {
+        // with the box filter every sample only counts for its own pixel,
+        // and nothing reaches past the tile
+        let tile = Tile {
+            x0: 2,
+            y0: 2,
+            x1: 4,
+            y1: 4,
+        };
+        let mut film = FilmTile::new(&tile, Filter::default(), (8, 8));
+        assert_eq!((film.x0, film.y0, film.x1, film.y1), (2, 2, 4, 4));
+
+        film.add_sample((2.0, 2.0), Color::new(1.0, 0.0, 0.0), &[]);
+        film.add_sample((2.999, 2.5), Color::new(0.0, 1.0, 0.0), &[]);
+        film.add_sample((3.0, 3.999), Color::new(0.0, 0.0, 1.0), &[]);
+
+        let mut pixels = vec![-1.0; 8 * 8 * 3];
+        film.resolve_into(&tile, &mut FrameBuffer::new(&mut pixels, (8, 8)));
+
+        let pixel = |x: usize, y: usize| {
+            let i = 3 * (y * 8 + x);
+            (pixels[i], pixels[i + 1], pixels[i + 2])
+        };
+        assert_eq!(pixel(2, 2), (0.5, 0.5, 0.0));
+        assert_eq!(pixel(3, 3), (0.0, 0.0, 1.0));
+        assert_eq!(pixel(3, 2), (0.0, 0.0, 0.0));
+        assert_eq!(pixel(1, 1), (-1.0, -1.0, -1.0));
+    }
+
+    #[test]
+    fn test_film_merge() {
+        // a wide filter splats into the next tile, and the merged film has
+        // the samples of both
+        let filter = Filter::new(FilterType::Gaussian).with_radius(1.5);
+        let left = Tile {
+            x0: 0,
+            y0: 0,
+            x1: 2,
+            y1: 1,
+        };
+        let right = Tile {
+            x0: 2,
+            y0: 0,
+            x1: 4,
+            y1: 1,
+        };
+
+        let mut left_film = FilmTile::new(&left, filter, (4, 1));
+        assert_eq!((left_film.x0, left_film.x1), (0, 3));
+        left_film.add_sample((1.9, 0.5), Color::new(1.0, 1.0, 1.0), &[]);
+
+        let mut right_film = FilmTile::new(&right, filter, (4, 1));
+        right_film.add_sample((2.5, 0.5), Color::new(0.0, 0.0, 0.0), &[]);
+
+        let mut pixels = vec![0.0; 4 * 3];
+        right_film.resolve_into(&right, &mut FrameBuffer::new(&mut pixels, (4, 1)));
+        assert_eq!(pixels[6], 0.0);
+
+        let mut film = Film::new((4, 1), &[]);
+        film.merge(&left_film);
+        film.merge(&right_film);
+        film.resolve_into(&right, &mut FrameBuffer::new(&mut pixels, (4, 1)));
+
+        // pixel 2 is between the two samples, closer to the dark one
+        assert!(pixels[6] > 0.0 && pixels[6] < 0.5, "{}", pixels[6]);
+        assert_eq!(pixels[9], 0.0);
+    }
+
+    #[test]
+    fn test_film_aovs() {
+        // the normals are filtered like the color, and the ids are taken
+        // from the sample closest to the center of their own pixel
+        let filter = Filter::new(FilterType::Gaussian).with_radius(1.5);
+        let aovs = [Aov::Normal, Aov::ObjectId];
+        let tile = Tile {
+            x0: 0,
+            y0: 0,
+            x1: 2,
+            y1: 1,
+        };
+
+        let mut film = FilmTile::new(&tile, filter, (2, 1)).with_aovs(&aovs);
+        let up = Color::new(0.0, 1.0, 0.0);
+        let down = Color::new(0.0, -1.0, 0.0);
+        let id = |id: f32| Color::new(id, id, id);
+        film.add_sample((0.5, 0.5), color::WHITE, &[up, id(1.0)]);
+        film.add_sample((0.9, 0.5), color::WHITE, &[up, id(2.0)]);
+        film.add_sample((1.5, 0.5), color::WHITE, &[down, id(3.0)]);
+
+        let mut merged = Film::new((2, 1), &aovs);
+        merged.merge(&film);
+
+        let mut pixels = vec![0.0; 2 * 3];
+        let mut normals = vec![0.0; 2 * 3];
+        let mut ids = vec![0.0; 2 * 3];
+        let mut frame = FrameBuffer::new(&mut pixels, (2, 1))
+            .with_aov(Aov::Normal, &mut normals)
+            .with_aov(Aov::ObjectId, &mut ids);
+        merged.resolve_into(&tile, &mut frame);
+
+        // the normals of the first pixel lean up, and aren't clamped on the
+        // second
+        assert!(normals[1] > 0.0 && normals[1] < 1.0, "{}", normals[1]);
+        assert!(normals[4] < 0.0, "{}", normals[4]);
+        assert_eq!(&ids, &[1.0, 1.0, 1.0, 3.0, 3.0, 3.0]);
+    }
+}
@@ -0,0 +1,86 @@
+//! The buffers a render writes its output into.
+
+use crate::renderer::aov::Aov;
+
+/// The image buffers of a render. Only the pixels are required, the other
+/// buffers are filled in when they're supplied.
+///
+/// Fields:
+/// * `pixels` - The linear floating point rgb buffer of the full image.
+/// * `sample_counts` - The number of samples taken for every pixel, one
+///   value per pixel.
+/// * `aovs` - The arbitrary output variables to render along with the
+///   pixels, each into its own rgb buffer the size of the pixels.
+/// * `bounds` - The width and height of the full image.
+pub struct FrameBuffer<'a> {
+    pub pixels: &'a mut [f32],
+    pub sample_counts: Option<&'a mut [u32]>,
+    pub aovs: Vec<(Aov, &'a mut [f32])>,
+    pub bounds: (u32, u32),
+}
+
+impl<'a> FrameBuffer<'a> {
+    /// Creates a frame buffer with only the pixels.
+    pub fn new(pixels: &'a mut [f32], bounds: (u32, u32)) -> FrameBuffer<'a> {
+        FrameBuffer {
+            pixels,
+            sample_counts: None,
+            aovs: Vec::new(),
+            bounds,
+        }
+    }
+
+    /// Also records the number of samples of each pixel into the buffer.
+    pub fn with_sample_counts(mut self, sample_counts: &'a mut [u32]) -> FrameBuffer<'a> {
+        self.sample_counts = Some(sample_counts);
+        self
+    }
+
+    /// Also renders the output variable into the buffer.
+    pub fn with_aov(mut self, aov: Aov, buffer: &'a mut [f32]) -> FrameBuffer<'a> {
+        self.aovs.push((aov, buffer));
+        self
+    }
+
+    /// The output variables of the frame, in order.
+    pub fn aov_list(&self) -> Vec<Aov> {
+        self.aovs.iter().map(|(aov, _)| *aov).collect()
+    }
+
+    /// Whether the buffers are the right size for the bounds.
+    pub fn is_valid(&self) -> bool {
+        let len = self.bounds.0 as usize * self.bounds.1 as usize;
+        self.pixels.len() == len * 3
+            && self.sample_counts.as_ref().is_none_or(|c| c.len() == len)
+            && self.aovs.iter().all(|(_, buffer)| buffer.len() == len * 3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_buffer() {
+        let mut pixels = vec![0.0; 2 * 3 * 3];
+        let mut counts = vec![0; 2 * 3];
+        assert!(FrameBuffer::new(&mut pixels, (2, 3)).is_valid());
+        assert!(!FrameBuffer::new(&mut pixels, (3, 3)).is_valid());
+
+        let frame = FrameBuffer::new(&mut pixels, (2, 3)).with_sample_counts(&mut counts);
+        assert!(frame.is_valid());
+
+        let mut counts = vec![0; 5];
+        let frame = FrameBuffer::new(&mut pixels, (2, 3)).with_sample_counts(&mut counts);
+        assert!(!frame.is_valid());
+
+        let mut depth = vec![0.0; 2 * 3 * 3];
+        let frame = FrameBuffer::new(&mut pixels, (2, 3)).with_aov(Aov::Depth, &mut depth);
+        assert!(frame.is_valid());
+        assert_eq!(frame.aov_list(), vec![Aov::Depth]);
+
+        let mut depth = vec![0.0; 2 * 3];
+        let frame = FrameBuffer::new(&mut pixels, (2, 3)).with_aov(Aov::Depth, &mut depth);
+        assert!(!frame.is_valid());
+    }
+}
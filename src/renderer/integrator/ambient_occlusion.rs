@@ -0,0 +1,63 @@
+use crate::renderer::core::color;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector;
+use crate::renderer::sampler::Sampler;
+use crate::renderer::scene::hittable::Hittable;
+use crate::renderer::scene::world::Region;
+
+use super::{Integrator, LightComponents};
+
+/// Ambient occlusion, how much of the hemisphere above a surface is open.
+/// A single ray is cast from the first hit in a cosine distributed
+/// direction, and the surface is white when it gets out and black when
+/// something within `distance` blocks it. Averaged over the samples, this
+/// shades the creases and contact points of the scene without any lights
+/// or materials.
+///
+/// The occlusion counts as the direct diffuse light of the surface, the
+/// sky lighting it.
+pub struct AmbientOcclusionIntegrator {
+    distance: f32,
+}
+
+impl AmbientOcclusionIntegrator {
+    pub fn new(distance: f32) -> AmbientOcclusionIntegrator {
+        AmbientOcclusionIntegrator { distance }
+    }
+}
+
+impl Integrator for AmbientOcclusionIntegrator {
+    fn ray_components(
+        &self,
+        r: &Ray,
+        world: &Region,
+        sampler: &mut dyn Sampler,
+    ) -> LightComponents {
+        let hit = match world.hit(r, 0.001, f32::INFINITY) {
+            Some(hit) => hit,
+            None => {
+                return LightComponents {
+                    background: world.background_color(r),
+                    ..LightComponents::default()
+                }
+            }
+        };
+
+        // a point on the unit sphere on top of the normal is cosine
+        // distributed around it
+        let mut dir = hit.normal + vector::random_unit_vector(sampler);
+        if dir.near_zero() {
+            dir = hit.normal;
+        }
+
+        let occlusion_ray = Ray::with_time(hit.p, vector::unit_vector(&dir), r.time);
+        let open = match world.hit(&occlusion_ray, 0.001, self.distance) {
+            Some(_) => color::BLACK,
+            None => color::WHITE,
+        };
+        LightComponents {
+            direct_diffuse: open,
+            ..LightComponents::default()
+        }
+    }
+}
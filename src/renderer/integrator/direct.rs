@@ -0,0 +1,96 @@
+use crate::renderer::core::color;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::sampler::Sampler;
+use crate::renderer::scene::hittable::Hittable;
+use crate::renderer::scene::world::Region;
+
+use super::{power_heuristic, sample_light_mis, Integrator, LightComponents};
+
+/// Only the light that reaches the first diffuse surface straight from the
+/// lights or the background. The lights are sampled the same way as in
+/// the path tracer, along with a single bounce off of the material that
+/// picks up the background and any lights it hits, but the light that
+/// bounces around the scene is left out.
+///
+/// Mirrors and glass are followed for up to `max_depth` bounces, so that
+/// they still show what's reflected in them.
+pub struct DirectLightingIntegrator {
+    max_depth: u32,
+}
+
+impl DirectLightingIntegrator {
+    pub fn new(max_depth: u32) -> DirectLightingIntegrator {
+        DirectLightingIntegrator { max_depth }
+    }
+}
+
+impl Integrator for DirectLightingIntegrator {
+    fn ray_components(
+        &self,
+        r: &Ray,
+        world: &Region,
+        sampler: &mut dyn Sampler,
+    ) -> LightComponents {
+        let mut light = LightComponents::default();
+        let mut first_specular = false;
+        let mut throughput = color::WHITE;
+        let mut ray = *r;
+        let mut bsdf_pdf: Option<f32> = None;
+        let mut diffuse_bounce = false;
+
+        for bounce in 0..self.max_depth {
+            let hit = match world.hit(&ray, 0.001, f32::INFINITY) {
+                Some(hit) => hit,
+                None => {
+                    let background = throughput * world.background_color(&ray);
+                    if bounce == 0 {
+                        light.background += background;
+                    } else {
+                        light.add_bounced(bounce, first_specular, background);
+                    }
+                    return light;
+                }
+            };
+
+            let mut emitted = hit.material.emitted(&hit);
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                if hit.material.is_emissive() {
+                    emitted *= power_heuristic(bsdf_pdf, world.light_pdf(&ray.orig, &ray.dir));
+                }
+            }
+            if bounce == 0 {
+                light.emission += emitted;
+                first_specular = hit.material.is_specular();
+            } else {
+                light.add_bounced(bounce, first_specular, throughput * emitted);
+            }
+
+            // whatever the bounce off of the diffuse surface hit is indirect
+            if diffuse_bounce {
+                break;
+            }
+
+            let sample_lights = world.has_lights() && !hit.material.is_specular();
+            if sample_lights {
+                let direct = throughput * sample_light_mis(&ray, world, &hit, sampler);
+                light.add_bounced(bounce + 1, first_specular, direct);
+            }
+
+            let (scattered, attenuation) = match hit.material.scatter(&ray, &hit, sampler) {
+                Some(scatter) => scatter,
+                None => break,
+            };
+
+            bsdf_pdf = if sample_lights {
+                Some(hit.material.pdf(&ray, &hit, &scattered.dir))
+            } else {
+                None
+            };
+            diffuse_bounce = !hit.material.is_specular();
+            throughput = throughput * attenuation;
+            ray = scattered;
+        }
+
+        light
+    }
+}
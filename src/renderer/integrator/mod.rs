@@ -0,0 +1,338 @@
+//! The integrators that work out the color of a camera ray. The full path
+//! tracer is what the final frames are rendered with, the others trade
+//! accuracy for speed or show a single aspect of the scene, e.g. for quick
+//! previews while setting up the look of a scene.
+
+mod ambient_occlusion;
+mod direct;
+mod path;
+mod whitted;
+
+pub use ambient_occlusion::AmbientOcclusionIntegrator;
+pub use direct::DirectLightingIntegrator;
+pub use path::PathIntegrator;
+pub use whitted::WhittedIntegrator;
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::renderer::core::color::{self, Color};
+use crate::renderer::core::debug_check;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::sampler::Sampler;
+use crate::renderer::scene::hittable::{HitRecord, Hittable};
+use crate::renderer::scene::world::Region;
+
+/// Calculates the light arriving along a ray.
+pub trait Integrator: Send + Sync {
+    /// Returns the light of the ray split up by the way it got to the
+    /// camera, with the random choices drawn from the sampler.
+    fn ray_components(&self, r: &Ray, world: &Region, sampler: &mut dyn Sampler)
+        -> LightComponents;
+
+    /// Returns the color of the ray, with the random choices drawn from
+    /// the sampler.
+    fn ray_color(&self, r: &Ray, world: &Region, sampler: &mut dyn Sampler) -> Color {
+        self.ray_components(r, world, sampler).total()
+    }
+}
+
+/// The light of a ray split up by the way it got to the camera, for
+/// compositing. The components add up to the color of the ray.
+///
+/// Fields:
+/// * `direct_diffuse` - The light that lit the first surface the ray hit
+///   straight from a light or the background, when that surface isn't
+///   specular.
+/// * `indirect_diffuse` - The light that bounced off of other surfaces
+///   before it lit the first surface, when that surface isn't specular.
+/// * `specular` - All of the light reflected or refracted by the first
+///   surface, when it's a mirror or glass.
+/// * `emission` - The light given off by the first surface.
+/// * `background` - The background, when the ray doesn't hit anything.
+#[derive(Copy, Clone, Debug)]
+pub struct LightComponents {
+    pub direct_diffuse: Color,
+    pub indirect_diffuse: Color,
+    pub specular: Color,
+    pub emission: Color,
+    pub background: Color,
+}
+
+/// No light at all. The default color is white, so this can't be derived.
+impl Default for LightComponents {
+    fn default() -> Self {
+        LightComponents {
+            direct_diffuse: color::BLACK,
+            indirect_diffuse: color::BLACK,
+            specular: color::BLACK,
+            emission: color::BLACK,
+            background: color::BLACK,
+        }
+    }
+}
+
+impl LightComponents {
+    /// The color of the ray.
+    pub fn total(&self) -> Color {
+        self.direct_diffuse
+            + self.indirect_diffuse
+            + self.specular
+            + self.emission
+            + self.background
+    }
+
+    /// Adds light that made it to the camera off of `bounces` surfaces,
+    /// where the first one is specular or not.
+    fn add_bounced(&mut self, bounces: u32, first_specular: bool, light: Color) {
+        debug_check!(bounces > 0);
+
+        if first_specular {
+            self.specular += light;
+        } else if bounces == 1 {
+            self.direct_diffuse += light;
+        } else {
+            self.indirect_diffuse += light;
+        }
+    }
+}
+
+/// The integrators to choose from.
+///
+/// * `Path` - The full path tracer with light sampling and Russian
+///   roulette.
+/// * `AmbientOcclusion` - How much of the sky around a surface isn't
+///   blocked by the objects nearby, white for fully open and black for
+///   fully covered. Ignores the materials and lights.
+/// * `Direct` - Only the light that reaches a surface straight from the
+///   lights or the background, without the light bouncing off of other
+///   surfaces. Mirrors and glass are still followed.
+/// * `Whitted` - The classic recursive ray tracer. Follows mirrors and
+///   glass, and lights the other surfaces only with shadow rays to the
+///   emissive objects, so scenes lit by the sky alone come out dark.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum IntegratorType {
+    #[default]
+    Path,
+    AmbientOcclusion,
+    Direct,
+    Whitted,
+}
+
+/// The configuration of the integrator of a scene.
+///
+/// Fields:
+/// * `kind` - The integrator to render with.
+/// * `ao_distance` - How far away objects still block the sky for ambient
+///   occlusion. Anything further away doesn't count. None for no limit.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct IntegratorConfig {
+    pub kind: IntegratorType,
+    pub ao_distance: Option<f32>,
+}
+
+impl IntegratorConfig {
+    /// Creates the integrator, following rays for up to `max_depth`
+    /// bounces.
+    pub fn create(&self, max_depth: u32) -> Box<dyn Integrator> {
+        match self.kind {
+            IntegratorType::Path => Box::new(PathIntegrator::new(max_depth)),
+            IntegratorType::AmbientOcclusion => Box::new(AmbientOcclusionIntegrator::new(
+                self.ao_distance.unwrap_or(f32::INFINITY),
+            )),
+            IntegratorType::Direct => Box::new(DirectLightingIntegrator::new(max_depth)),
+            IntegratorType::Whitted => Box::new(WhittedIntegrator::new(max_depth)),
+        }
+    }
+}
+
+/// The light from a randomly chosen light that makes it to a hit.
+///
+/// Fields:
+/// * `color` - The light times the scattering of the material.
+/// * `light_pdf` - The pdf of choosing the direction toward the light.
+/// * `bsdf_pdf` - The pdf of the material scattering into the same
+///   direction.
+struct LightSample {
+    color: Color,
+    light_pdf: f32,
+    bsdf_pdf: f32,
+}
+
+/// Samples a direction toward one of the lights from the hit, and returns
+/// the light coming from it unless something is in the way.
+fn sample_light(
+    r: &Ray,
+    world: &Region,
+    hit: &HitRecord,
+    sampler: &mut dyn Sampler,
+) -> Option<LightSample> {
+    let dir = world.sample_light_direction(&hit.p, sampler)?;
+
+    let light_pdf = world.light_pdf(&hit.p, &dir);
+    let f = hit.material.eval(r, hit, &dir);
+    if light_pdf <= 0.0 || f.near_zero() {
+        return None;
+    }
+
+    // the shadow ray has to make it to a light without being blocked. The
+    // light pdf covers all of the lights, so it doesn't matter which one.
+    let shadow_ray = Ray::with_time(hit.p, dir, r.time);
+    let light_hit = world.hit(&shadow_ray, 0.001, f32::INFINITY)?;
+
+    Some(LightSample {
+        color: f * light_hit.material.emitted(&light_hit),
+        light_pdf,
+        bsdf_pdf: hit.material.pdf(r, hit, &dir),
+    })
+}
+
+/// Estimates the light arriving at the hit directly from a randomly chosen
+/// light, weighted against the material sampling the same direction.
+fn sample_light_mis(r: &Ray, world: &Region, hit: &HitRecord, sampler: &mut dyn Sampler) -> Color {
+    match sample_light(r, world, hit, sampler) {
+        Some(light) => {
+            let weight = power_heuristic(light.light_pdf, light.bsdf_pdf);
+            (weight / light.light_pdf) * light.color
+        }
+        None => color::BLACK,
+    }
+}
+
+/// The power heuristic with an exponent of 2 for weighting one sample from
+/// each of two sampling strategies.
+fn power_heuristic(f_pdf: f32, g_pdf: f32) -> f32 {
+    let f = f_pdf * f_pdf;
+    let g = g_pdf * g_pdf;
+    if f + g <= 0.0 {
+        return 0.0;
+    }
+    f / (f + g)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::vector::Vec3;
+    use crate::renderer::sampler::IndependentSampler;
+    use crate::renderer::scene::materials::Material;
+    use crate::renderer::scene::objects::sphere::Sphere;
+    use crate::renderer::scene::world::Background;
+
+    #[test]
+    fn test_power_heuristic() {
+        assert_eq!(power_heuristic(1.0, 0.0), 1.0);
+        assert_eq!(power_heuristic(0.0, 1.0), 0.0);
+        assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+        assert_eq!(power_heuristic(2.0, 2.0), 0.5);
+        assert!((power_heuristic(1.0, 2.0) - 0.2).abs() < 1e-6);
+        assert!((power_heuristic(1.0, 2.0) + power_heuristic(2.0, 1.0) - 1.0).abs() < 1e-6);
+    }
+
+    /// The average light of a ray from the origin in the direction, with a
+    /// sphere of the material in front of the camera under a uniform sky.
+    fn sphere_light(kind: IntegratorType, material: Material, dir: Vec3) -> LightComponents {
+        let mut world = Region::new(color::BLACK);
+        world.background = Background::Solid(Color::new(1.0, 1.0, 1.0));
+        world.push(Box::new(Sphere {
+            center: Vec3::new(0.0, 0.0, -3.0),
+            radius: 1.0,
+            material,
+        }));
+        world.recalculate_bounds();
+
+        let integrator = IntegratorConfig {
+            kind,
+            ..IntegratorConfig::default()
+        }
+        .create(8);
+
+        let r = Ray::new(Vec3::new(0.0, 0.0, 0.0), dir);
+        let mut sampler = IndependentSampler::new(0);
+        let n = 1000;
+        let mut total = LightComponents::default();
+        for i in 0..n {
+            sampler.start_sample((0, 0), i);
+            let light = integrator.ray_components(&r, &world, &mut sampler);
+            total.direct_diffuse += light.direct_diffuse / n as f32;
+            total.indirect_diffuse += light.indirect_diffuse / n as f32;
+            total.specular += light.specular / n as f32;
+            total.emission += light.emission / n as f32;
+            total.background += light.background / n as f32;
+        }
+        total
+    }
+
+    /// The average color of a ray straight at a diffuse sphere under a
+    /// uniform sky.
+    fn sphere_color(kind: IntegratorType) -> Color {
+        let gray = Material::Lambert {
+            albedo: Color::new(0.5, 0.5, 0.5).into(),
+        };
+        sphere_light(kind, gray, Vec3::new(0.0, 0.0, -1.0)).total()
+    }
+
+    #[test]
+    fn test_integrators() {
+        // nothing blocks the sky of a lone sphere, so the light bounces
+        // off of it once and leaves
+        let path = sphere_color(IntegratorType::Path);
+        assert!((path.x - 0.5).abs() < 1e-4, "{:?}", path);
+        let direct = sphere_color(IntegratorType::Direct);
+        assert!((direct.x - 0.5).abs() < 1e-4, "{:?}", direct);
+        let ao = sphere_color(IntegratorType::AmbientOcclusion);
+        assert!((ao.x - 1.0).abs() < 1e-4, "{:?}", ao);
+
+        // and there are no lights for the Whitted shadow rays
+        let whitted = sphere_color(IntegratorType::Whitted);
+        assert_eq!(whitted.x, 0.0);
+    }
+
+    #[test]
+    fn test_light_components() {
+        let gray = || Material::Lambert {
+            albedo: Color::new(0.5, 0.5, 0.5).into(),
+        };
+        let mirror = || Material::Metal {
+            albedo: Color::new(0.8, 0.8, 0.8).into(),
+            fuzz: color::BLACK.into(),
+        };
+        let ahead = Vec3::new(0.0, 0.0, -1.0);
+        let up = Vec3::new(0.0, 1.0, 0.0);
+
+        // the sky lights the lone sphere directly, and nothing bounces
+        // back onto it
+        let light = sphere_light(IntegratorType::Path, gray(), ahead);
+        assert!((light.direct_diffuse.x - 0.5).abs() < 1e-4, "{:?}", light);
+        assert_eq!(light.indirect_diffuse.x, 0.0);
+        assert_eq!(
+            light.specular.x + light.emission.x + light.background.x,
+            0.0
+        );
+
+        // the mirror reflects the sky
+        for kind in [
+            IntegratorType::Path,
+            IntegratorType::Direct,
+            IntegratorType::Whitted,
+        ] {
+            let light = sphere_light(kind, mirror(), ahead);
+            assert!((light.specular.x - 0.8).abs() < 1e-4, "{:?}", light);
+            assert_eq!(light.direct_diffuse.x, 0.0);
+        }
+
+        // and the rays that miss only see the background
+        for kind in [
+            IntegratorType::Path,
+            IntegratorType::AmbientOcclusion,
+            IntegratorType::Direct,
+            IntegratorType::Whitted,
+        ] {
+            let light = sphere_light(kind, gray(), up);
+            assert!((light.background.x - 1.0).abs() < 1e-4, "{:?}", light);
+            assert_eq!(light.total().x, light.background.x);
+        }
+    }
+}
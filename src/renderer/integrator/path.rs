@@ -0,0 +1,165 @@
+use crate::renderer::core::color::{self, Color};
+use crate::renderer::core::ray::Ray;
+use crate::renderer::sampler::Sampler;
+use crate::renderer::scene::hittable::Hittable;
+use crate::renderer::scene::world::Region;
+
+use super::{power_heuristic, sample_light_mis, Integrator, LightComponents};
+
+/// The bounces every path takes before Russian roulette can end it. The
+/// first few bounces carry most of the light, so they're always traced.
+const MIN_BOUNCES: u32 = 3;
+
+/// An iterative path tracer. The path keeps track of its throughput, how
+/// much of the light found further along it still makes it back to the
+/// camera, instead of recursing.
+///
+/// At every non-specular hit, one of the lights is sampled directly (next
+/// event estimation) on top of the usual material scattering. Both
+/// estimates of the light are combined with multiple importance sampling
+/// so that neither small lights nor glossy reflections of large lights end
+/// up noisy.
+///
+/// After `MIN_BOUNCES`, dim paths are ended early with Russian roulette.
+/// `max_depth` is only a safety cap for paths that never get dimmer, like
+/// the ones bouncing between mirrors.
+pub struct PathIntegrator {
+    max_depth: u32,
+}
+
+impl PathIntegrator {
+    pub fn new(max_depth: u32) -> PathIntegrator {
+        PathIntegrator { max_depth }
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn ray_components(
+        &self,
+        r: &Ray,
+        world: &Region,
+        sampler: &mut dyn Sampler,
+    ) -> LightComponents {
+        let mut light = LightComponents::default();
+        let mut first_specular = false;
+        let mut throughput = color::WHITE;
+        let mut ray = *r;
+
+        // the pdf of the material sampling that produced the ray. None if
+        // the ray comes from the camera or a specular bounce, in which case
+        // the light wasn't sampled directly and any emission it hits counts
+        // in full.
+        let mut bsdf_pdf: Option<f32> = None;
+
+        for bounce in 0..self.max_depth {
+            let hit = match world.hit(&ray, 0.001, f32::INFINITY) {
+                Some(hit) => hit,
+                None => {
+                    let background = throughput * world.background_color(&ray);
+                    if bounce == 0 {
+                        light.background += background;
+                    } else {
+                        light.add_bounced(bounce, first_specular, background);
+                    }
+                    return light;
+                }
+            };
+
+            let mut emitted = hit.material.emitted(&hit);
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                if hit.material.is_emissive() {
+                    emitted *= power_heuristic(bsdf_pdf, world.light_pdf(&ray.orig, &ray.dir));
+                }
+            }
+            if bounce == 0 {
+                light.emission += emitted;
+                first_specular = hit.material.is_specular();
+            } else {
+                light.add_bounced(bounce, first_specular, throughput * emitted);
+            }
+
+            let sample_lights = world.has_lights() && !hit.material.is_specular();
+            if sample_lights {
+                let direct = throughput * sample_light_mis(&ray, world, &hit, sampler);
+                light.add_bounced(bounce + 1, first_specular, direct);
+            }
+
+            let (scattered, attenuation) = match hit.material.scatter(&ray, &hit, sampler) {
+                Some(scatter) => scatter,
+                None => break,
+            };
+
+            bsdf_pdf = if sample_lights {
+                Some(hit.material.pdf(&ray, &hit, &scattered.dir))
+            } else {
+                None
+            };
+            throughput = throughput * attenuation;
+            ray = scattered;
+
+            if bounce + 1 >= MIN_BOUNCES {
+                throughput = match russian_roulette(throughput, sampler) {
+                    Some(throughput) => throughput,
+                    None => break,
+                };
+            }
+        }
+
+        light
+    }
+}
+
+/// Randomly ends a path with a probability that grows as its throughput
+/// drops. The paths that survive are made brighter by the odds of
+/// surviving, so that on average the image stays the same, only with less
+/// time spent on paths that barely contribute.
+///
+/// Returns the new throughput, or None if the path ends.
+fn russian_roulette(throughput: Color, sampler: &mut dyn Sampler) -> Option<Color> {
+    // always leave some chance of ending, so paths between bright
+    // surfaces don't go on forever
+    let survival = throughput.x.max(throughput.y).max(throughput.z).min(0.95);
+    if sampler.get_1d() >= survival {
+        return None;
+    }
+
+    Some(throughput / survival)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::sampler::IndependentSampler;
+
+    #[test]
+    fn test_russian_roulette() {
+        let mut sampler = IndependentSampler::new(0);
+
+        // bright paths still end with the capped odds
+        let bright = Color::new(2.0, 0.5, 0.0);
+        let n = 100000;
+        let survived = (0..n)
+            .filter(|i| {
+                sampler.start_sample((0, 0), *i);
+                russian_roulette(bright, &mut sampler).is_some()
+            })
+            .count();
+        assert!((survived as f32 / n as f32 - 0.95).abs() < 0.01);
+
+        // on average, the throughput is the same as without the roulette
+        let dim = Color::new(0.2, 0.1, 0.05);
+        let mut total = color::BLACK;
+        for i in 0..n {
+            sampler.start_sample((0, 0), i);
+            if let Some(t) = russian_roulette(dim, &mut sampler) {
+                assert!((t.x - 1.0).abs() < 1e-5);
+                total += t;
+            }
+        }
+        let mean = total / n as f32;
+        assert!((mean.x - dim.x).abs() < 0.01, "{:?}", mean);
+        assert!((mean.z - dim.z).abs() < 0.005, "{:?}", mean);
+
+        assert!(russian_roulette(color::BLACK, &mut sampler).is_none());
+    }
+}
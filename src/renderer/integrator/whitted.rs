@@ -0,0 +1,72 @@
+use crate::renderer::core::color;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::sampler::Sampler;
+use crate::renderer::scene::hittable::Hittable;
+use crate::renderer::scene::world::Region;
+
+use super::{sample_light, Integrator, LightComponents};
+
+/// The classic recursive ray tracer from Whitted 1980, "An Improved
+/// Illumination Model for Shaded Display". Mirrors and glass spawn a
+/// reflected or refracted ray, and every other surface is lit by a shadow
+/// ray toward one of the emissive objects. There's no light bouncing
+/// between diffuse surfaces, and the background only shows up directly or
+/// in reflections, so it's fast but flat.
+pub struct WhittedIntegrator {
+    max_depth: u32,
+}
+
+impl WhittedIntegrator {
+    pub fn new(max_depth: u32) -> WhittedIntegrator {
+        WhittedIntegrator { max_depth }
+    }
+
+    /// The recursive part of `ray_components()`. Everything seen in a
+    /// mirror or glass is specular light, no matter how it got there.
+    fn trace(
+        &self,
+        r: &Ray,
+        world: &Region,
+        depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> LightComponents {
+        let mut light = LightComponents::default();
+        if depth == 0 {
+            return light;
+        }
+
+        let hit = match world.hit(r, 0.001, f32::INFINITY) {
+            Some(hit) => hit,
+            None => {
+                light.background = world.background_color(r);
+                return light;
+            }
+        };
+
+        light.emission = hit.material.emitted(&hit);
+        if !hit.material.is_specular() {
+            light.direct_diffuse = match sample_light(r, world, &hit, sampler) {
+                Some(sample) => sample.color / sample.light_pdf,
+                None => color::BLACK,
+            };
+            return light;
+        }
+
+        if let Some((scattered, attenuation)) = hit.material.scatter(r, &hit, sampler) {
+            light.specular =
+                attenuation * self.trace(&scattered, world, depth - 1, sampler).total();
+        }
+        light
+    }
+}
+
+impl Integrator for WhittedIntegrator {
+    fn ray_components(
+        &self,
+        r: &Ray,
+        world: &Region,
+        sampler: &mut dyn Sampler,
+    ) -> LightComponents {
+        self.trace(r, world, self.max_depth, sampler)
+    }
+}
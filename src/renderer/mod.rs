@@ -1,18 +1,39 @@
 //! This module contains the actual rendering implementation.
-//! As a library, it has a single entry point, 'render()'.
+//! As a library, it has two entry points, 'render()' for 8 bit images
+//! and 'render_hdr()' for linear floating point images. Both have a
+//! '_with_options()' variant that takes the options for executing the
+//! render, like the tile size or a crop window. 'render_progressive()'
+//! keeps adding passes to a floating point image until it runs out of
+//! time or the image is clean enough.
 
+pub mod aov;
 pub mod core;
 mod execute;
+pub mod film;
+pub mod framebuffer;
+pub mod integrator;
+pub mod options;
+pub mod post;
+pub mod progress;
+pub mod progressive;
+pub mod sampler;
 pub mod scene;
 
-use log::{error, info};
-use std::time::Instant;
+use log::{debug, info};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+use crate::renderer::execute::accumulator::Accumulator;
 use crate::renderer::execute::context::RenderContext;
 use crate::renderer::execute::cpurender;
 use crate::renderer::execute::gpurender;
+use crate::renderer::execute::monitor::RenderMonitor;
 use crate::renderer::execute::render_op;
+use crate::renderer::execute::tile;
+use crate::renderer::framebuffer::FrameBuffer;
+use crate::renderer::options::RenderOptions;
+use crate::renderer::progress::PartialImage;
+use crate::renderer::progressive::{PassReport, ProgressiveOptions, StopReason};
 use crate::renderer::scene::camera::Camera;
 use crate::renderer::scene::world::Region;
 
@@ -43,6 +64,16 @@ pub enum RendererError {
 
     #[error("Empty scene file.")]
     InvalidScene,
+
+    #[error("The crop window must have pixels and be inside of the image.")]
+    CropWindow,
+
+    #[error(
+        "The render was cancelled with {} of {} tiles done.",
+        .0.tiles_done,
+        .0.tiles_total
+    )]
+    Cancelled(PartialImage),
 }
 
 /// This macro takes an expression as an argument and will
@@ -52,7 +83,7 @@ pub enum RendererError {
 macro_rules! condition_check {
     ($expression:expr, $error:expr) => {
         if ($expression) {
-            error!("API precondition check failed: {}", stringify!($expression));
+            log::error!("API precondition check failed: {}", stringify!($expression));
             return Err($error);
         }
     };
@@ -71,21 +102,25 @@ macro_rules! condition_check {
     };
 }
 
-/// Renders the objects in the world into the image buffer using a simple
-/// diffuse raytracing algorithm. It will choose the most effective compute
-/// environment for the process based on the ComputeEnv suggestion.
+/// Renders the objects in the world into the 8 bit image buffer using a
+/// simple diffuse raytracing algorithm. It will choose the most effective
+/// compute environment for the process based on the ComputeEnv suggestion.
+///
+/// The image is rendered with `render_hdr()` and then converted with the
+/// tone mapping of the world, so anything brighter than white is lost. Use
+/// `render_hdr()` directly to keep the full range.
 ///
 /// # Arguments
 ///
 /// * `env` - The suggested backend compute environment. It will always choose
-/// a environment that will succeed.
+///   a environment that will succeed.
 /// * `samples_per_pixel` - The number of samples to use for antialiasing. Must be > 0.
 /// * `max_depth` - The maximum number of ray reflections to use per pixel. Must be > 0.
 /// * `aspect_ratio` - The output aspect ratio.
 /// * `world` - The scene as a HittableList object.
-/// * `img_bug` - The output image buffer. Must be of type rgb and have
-/// a width and height of the intended output image. Width and height must be
-/// in (0, 4096].
+/// * `pixels` - The output image buffer. Must be of type rgb and have
+///   a width and height of the intended output image. Width and height must be
+///   in (0, 4096].
 ///
 /// # Examples
 ///
@@ -110,7 +145,7 @@ macro_rules! condition_check {
 /// * `InvalideParameterError` - A parameter is within an invalid range.
 /// * `BufferSizeError` - The image buffer supplied is of an unsupported size.
 /// * `ComputeError` - There was an error or panic while executing the render.
-/// This is likely due to a system level failure or defect.
+///   This is likely due to a system level failure or defect.
 pub fn render(
     env: ComputeEnv,
     samples_per_pixel: u32,
@@ -119,6 +154,166 @@ pub fn render(
     pixels: &mut [u8],
     bounds: (u32, u32),
 ) -> Result<(), RendererError> {
+    render_with_options(
+        env,
+        samples_per_pixel,
+        max_depth,
+        world,
+        pixels,
+        bounds,
+        &RenderOptions::default(),
+    )
+}
+
+/// The same as `render()`, with the options for executing the render.
+///
+/// When the render is cancelled, the finished part of the image is still
+/// converted into the buffer before the error is returned.
+pub fn render_with_options(
+    env: ComputeEnv,
+    samples_per_pixel: u32,
+    max_depth: u32,
+    world: &Region,
+    pixels: &mut [u8],
+    bounds: (u32, u32),
+    options: &RenderOptions,
+) -> Result<(), RendererError> {
+    condition_check!(
+        !world.tone_mapping.is_valid(),
+        RendererError::InvalidParameter
+    );
+
+    let mut hdr_pixels = vec![0.0; pixels.len()];
+
+    let result = render_hdr_with_options(
+        env,
+        samples_per_pixel,
+        max_depth,
+        world,
+        &mut hdr_pixels,
+        bounds,
+        options,
+    );
+
+    if result.is_ok() || matches!(result, Err(RendererError::Cancelled(_))) {
+        world.tone_mapping.convert_buffer(&hdr_pixels, pixels);
+    }
+
+    result
+}
+
+/// Renders the objects in the world into a floating point image buffer.
+/// The pixels are the linear radiance arriving at the camera, with no
+/// clamping or gamma correction applied, which makes them suitable for
+/// writing out to high dynamic range formats like OpenEXR.
+///
+/// # Arguments
+///
+/// * `env` - The suggested backend compute environment. It will always choose
+///   a environment that will succeed.
+/// * `samples_per_pixel` - The number of samples to use for antialiasing. Must be > 0.
+/// * `max_depth` - The maximum number of ray reflections to use per pixel. Must be > 0.
+/// * `world` - The scene as a HittableList object.
+/// * `pixels` - The output image buffer with three floats per pixel. Must
+///   have a width and height of the intended output image. Width and height
+///   must be in (0, 4096].
+///
+/// # Examples
+///
+/// ```
+/// # use rustyrender::parser::FileReaderFactory;
+/// # use rustyrender::renderer::{ComputeEnv, render_hdr};
+/// # let input_file_path = "test_input/one_sphere.json";
+/// # let file_parser = FileReaderFactory::get_file_processor(input_file_path).unwrap();
+///
+/// let world = file_parser.process_file().unwrap();
+/// let imgx = 200;
+/// let imgy = 200;
+/// let mut pixels = vec![0.0; (imgx as usize) * (imgy as usize) * 3];
+///
+/// render_hdr(ComputeEnv::Multicore,
+///    10, 50,
+///    &world, &mut pixels, (imgx, imgy));
+/// ```
+///
+/// # Errors
+///
+/// The same as `render()`.
+pub fn render_hdr(
+    env: ComputeEnv,
+    samples_per_pixel: u32,
+    max_depth: u32,
+    world: &Region,
+    pixels: &mut [f32],
+    bounds: (u32, u32),
+) -> Result<(), RendererError> {
+    render_hdr_with_options(
+        env,
+        samples_per_pixel,
+        max_depth,
+        world,
+        pixels,
+        bounds,
+        &RenderOptions::default(),
+    )
+}
+
+/// The same as `render_hdr()`, with the options for executing the render.
+///
+/// The image is split into square tiles that are rendered in the order of
+/// `options.tile_order`. With a crop window, only the tiles inside of it
+/// are rendered, and the rest of the buffer is left untouched.
+///
+/// `options.progress` is called every time a tile finishes, and
+/// `options.cancel` stops the render early. A cancelled render leaves the
+/// finished tiles in the buffer.
+///
+/// With `options.adaptive`, the samples per pixel are the most any pixel
+/// gets, and pixels that converge sooner stop early.
+///
+/// # Errors
+///
+/// The same as `render()`, and:
+///
+/// * `CropWindowError` - The crop window is empty or outside of the image.
+/// * `Cancelled` - The render was cancelled before all of the tiles were
+///   done. Holds a copy of the partial image.
+pub fn render_hdr_with_options(
+    env: ComputeEnv,
+    samples_per_pixel: u32,
+    max_depth: u32,
+    world: &Region,
+    pixels: &mut [f32],
+    bounds: (u32, u32),
+    options: &RenderOptions,
+) -> Result<(), RendererError> {
+    render_frame(
+        env,
+        samples_per_pixel,
+        max_depth,
+        world,
+        &mut FrameBuffer::new(pixels, bounds),
+        options,
+    )
+}
+
+/// The same as `render_hdr_with_options()`, rendering into a frame buffer
+/// that can also collect the number of samples taken for every pixel,
+/// e.g. to see where adaptive sampling spent its time.
+///
+/// # Errors
+///
+/// The same as `render_hdr_with_options()`.
+pub fn render_frame(
+    env: ComputeEnv,
+    samples_per_pixel: u32,
+    max_depth: u32,
+    world: &Region,
+    frame: &mut FrameBuffer,
+    options: &RenderOptions,
+) -> Result<(), RendererError> {
+    let bounds = frame.bounds;
+
     // precondition checks
     condition_check!(samples_per_pixel == 0, RendererError::InvalidParameter);
     condition_check!(max_depth == 0, RendererError::InvalidParameter);
@@ -128,42 +323,391 @@ pub fn render(
         RendererError::BufferSize
     );
     condition_check!(world.objects.is_empty(), RendererError::InvalidScene);
+    condition_check!(options.tile_size == 0, RendererError::InvalidParameter);
+    condition_check!(
+        options.filter.radius.is_nan() || options.filter.radius <= 0.0,
+        RendererError::InvalidParameter
+    );
+    condition_check!(!frame.is_valid(), RendererError::BufferSize);
+    condition_check!(
+        options.crop.is_some_and(|crop| !crop.is_valid(bounds)),
+        RendererError::CropWindow
+    );
+    condition_check!(
+        options
+            .adaptive
+            .is_some_and(|a| a.min_samples < 2 || a.min_samples > samples_per_pixel),
+        RendererError::InvalidParameter
+    );
 
     let camera_config = world.camera_config;
 
+    // the context covers the part of the image that gets rendered
+    let (start_x, start_y, end_x, end_y) = match options.crop {
+        Some(crop) => (crop.x0, crop.y0, crop.x1, crop.y1),
+        None => (0, 0, bounds.0, bounds.1),
+    };
+
     let context = RenderContext::new(
         Camera::new(camera_config, bounds),
         max_depth,
         samples_per_pixel,
-        0,
-        0,
-        bounds.0,
-        bounds.1,
+        start_x,
+        start_y,
+        end_x,
+        end_y,
+    )
+    .with_adaptive(options.adaptive)
+    .with_sampler(options.sampler, options.first_sample)
+    .with_seed(options.seed)
+    .with_filter(options.filter)
+    .with_integrator(&world.integrator)
+    .with_aovs(frame.aov_list());
+
+    let tiles = tile::make_tiles(
+        (
+            context.start_x,
+            context.start_y,
+            context.end_x,
+            context.end_y,
+        ),
+        options.tile_size,
+        options.tile_order,
     );
 
+    let monitor = RenderMonitor::new(&tiles, options);
     let start = Instant::now();
 
     match env {
         ComputeEnv::Naive => {
             info!("Executing naive implementation.");
-            cpurender::render_naive(&context, world, pixels, bounds, render_op::render_pixel);
+            cpurender::render_naive(
+                &context,
+                world,
+                frame,
+                &tiles,
+                &monitor,
+                render_op::render_pixel,
+            );
         }
         ComputeEnv::Cuda => {
             info!("Executing CUDA implementation.");
-            gpurender::render_cuda(&context, world, pixels, bounds)?;
+            gpurender::render_cuda(&context, world, frame, &tiles, &monitor)?;
         }
         ComputeEnv::Opencl => {
             info!("Executing OpenCL implementation.");
-            gpurender::render_opencl(&context, world, pixels, bounds)?
+            gpurender::render_opencl(&context, world, frame, &tiles, &monitor)?
         }
         _ => {
             info!("Executing Mulithreading implementation.");
-            cpurender::render_threaded(&context, world, pixels, bounds, render_op::render_pixel)?
+            cpurender::render_threaded(
+                &context,
+                world,
+                frame,
+                &tiles,
+                &monitor,
+                render_op::render_pixel,
+            )?
         }
     };
     info!("Rendering execution time: {:?}", start.elapsed());
 
+    if monitor.is_cancelled() && monitor.tiles_done() < monitor.tiles_total() {
+        info!(
+            "Render cancelled with {} of {} tiles done.",
+            monitor.tiles_done(),
+            monitor.tiles_total()
+        );
+        return Err(RendererError::Cancelled(PartialImage {
+            pixels: frame.pixels.to_vec(),
+            bounds,
+            tiles_done: monitor.tiles_done(),
+            tiles_total: monitor.tiles_total(),
+        }));
+    }
+
     // post condition checks go here ;-)
 
     Ok(())
 }
+
+/// Renders the objects in the world into a floating point image buffer in
+/// passes, until one of the limits of `progressive` is reached. Every pass
+/// adds `progressive.samples_per_pass` samples to each pixel, and the
+/// buffer always holds the average of the passes so far.
+///
+/// # Arguments
+///
+/// * `env`, `max_depth`, `world`, `pixels`, `bounds`, `options` - The same
+///   as `render_hdr_with_options()`. The options apply to every pass, and
+///   the progress callback reports the tiles of the current pass. Adaptive
+///   sampling isn't used, the noise target takes its place.
+/// * `progressive` - When to stop adding passes.
+/// * `on_pass` - Called after every pass with the report and the image so
+///   far, e.g. to write out the intermediate image.
+///
+/// Returns the report of the last pass and why the render stopped. When the
+/// time limit is reached before the first pass finishes, the report has no
+/// passes and the buffer is left as it was.
+///
+/// # Examples
+///
+/// ```
+/// # use rustyrender::parser::FileReaderFactory;
+/// # use rustyrender::renderer::{ComputeEnv, render_progressive};
+/// # use rustyrender::renderer::options::RenderOptions;
+/// # use rustyrender::renderer::progressive::ProgressiveOptions;
+/// # use std::time::Duration;
+/// # let input_file_path = "test_input/one_sphere.json";
+/// # let file_parser = FileReaderFactory::get_file_processor(input_file_path).unwrap();
+///
+/// let world = file_parser.process_file().unwrap();
+/// let imgx = 200;
+/// let imgy = 200;
+/// let mut pixels = vec![0.0; (imgx as usize) * (imgy as usize) * 3];
+/// let progressive = ProgressiveOptions {
+///     time_limit: Some(Duration::from_secs(2)),
+///     max_samples: Some(16),
+///     ..ProgressiveOptions::default()
+/// };
+///
+/// render_progressive(ComputeEnv::Multicore, 50,
+///    &world, &mut pixels, (imgx, imgy),
+///    &RenderOptions::default(), &progressive,
+///    |report, _pixels| println!("{} samples", report.samples));
+/// ```
+///
+/// # Errors
+///
+/// The same as `render_hdr_with_options()`, and:
+///
+/// * `InvalidParameterError` - None of the limits are set, or the noise target
+///   isn't > 0.
+/// * `Cancelled` - Only when the first pass is cancelled. After that, a
+///   cancelled pass is dropped and the render stops with
+///   `StopReason::Cancelled`.
+#[allow(clippy::too_many_arguments)]
+pub fn render_progressive<F>(
+    env: ComputeEnv,
+    max_depth: u32,
+    world: &Region,
+    pixels: &mut [f32],
+    bounds: (u32, u32),
+    options: &RenderOptions,
+    progressive: &ProgressiveOptions,
+    mut on_pass: F,
+) -> Result<(PassReport, StopReason), RendererError>
+where
+    F: FnMut(&PassReport, &[f32]),
+{
+    condition_check!(
+        progressive.samples_per_pass == 0,
+        RendererError::InvalidParameter
+    );
+    condition_check!(!progressive.is_bounded(), RendererError::InvalidParameter);
+    condition_check!(
+        progressive
+            .noise_target
+            .is_some_and(|target| !target.is_finite() || target <= 0.0),
+        RendererError::InvalidParameter
+    );
+
+    let area = match options.crop {
+        Some(crop) => (crop.x0, crop.y0, crop.x1, crop.y1),
+        None => (0, 0, bounds.0, bounds.1),
+    };
+
+    let start = Instant::now();
+    let mut pass_options = RenderOptions {
+        adaptive: None,
+        ..options.clone()
+    };
+    // a pass that is still running at the time limit is stopped
+    if let Some(limit) = progressive.time_limit {
+        let token = options.cancel.clone().unwrap_or_default();
+        pass_options.cancel = Some(token.with_deadline(start + limit));
+    }
+    let out_of_time = || {
+        progressive
+            .time_limit
+            .is_some_and(|limit| start.elapsed() >= limit)
+    };
+
+    let mut accumulator = Accumulator::new(bounds, area);
+    let mut pass = vec![0.0; pixels.len()];
+    let mut report = PassReport {
+        passes: 0,
+        samples: 0,
+        elapsed: Duration::ZERO,
+        noise: None,
+    };
+
+    loop {
+        // every pass continues the sample sequences where the last one ended
+        pass_options.first_sample = options.first_sample + accumulator.samples();
+
+        let pass_start = Instant::now();
+        match render_hdr_with_options(
+            env,
+            progressive.samples_per_pass,
+            max_depth,
+            world,
+            &mut pass,
+            bounds,
+            &pass_options,
+        ) {
+            Ok(()) => {}
+            Err(RendererError::Cancelled(_)) if out_of_time() => {
+                info!(
+                    "Progressive render ran out of time after {} passes.",
+                    report.passes
+                );
+                return Ok((report, StopReason::TimeLimit));
+            }
+            Err(RendererError::Cancelled(_)) if report.passes > 0 => {
+                info!(
+                    "Progressive render cancelled after {} passes.",
+                    report.passes
+                );
+                return Ok((report, StopReason::Cancelled));
+            }
+            Err(e) => return Err(e),
+        }
+        let pass_time = pass_start.elapsed();
+
+        accumulator.add_pass(&pass, progressive.samples_per_pass);
+        accumulator.resolve(pixels);
+
+        report = PassReport {
+            passes: report.passes + 1,
+            samples: accumulator.samples(),
+            elapsed: start.elapsed(),
+            noise: accumulator.noise(),
+        };
+        debug!(
+            "Pass {} done, {} samples per pixel, noise {:?}, {:?} elapsed.",
+            report.passes, report.samples, report.noise, report.elapsed
+        );
+        on_pass(&report, pixels);
+
+        if progressive
+            .max_samples
+            .is_some_and(|max| report.samples >= max)
+        {
+            return Ok((report, StopReason::MaxSamples));
+        }
+        if let (Some(target), Some(noise)) = (progressive.noise_target, report.noise) {
+            if noise <= target {
+                return Ok((report, StopReason::NoiseTarget));
+            }
+        }
+        // only start a pass that should finish inside of the budget
+        if progressive
+            .time_limit
+            .is_some_and(|limit| report.elapsed + pass_time > limit)
+        {
+            return Ok((report, StopReason::TimeLimit));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color::Color;
+    use crate::renderer::core::vector::Point3;
+    use crate::renderer::options::AdaptiveSampling;
+    use crate::renderer::scene::materials::Material;
+    use crate::renderer::scene::objects::sphere::Sphere;
+    use crate::renderer::scene::textures::Texture;
+
+    fn one_sphere() -> Region {
+        let mut world = Region::new(Color::new(0.5, 0.7, 1.0));
+        world.push(Box::new(Sphere {
+            center: Point3::new(0.0, 0.0, -1.0),
+            radius: 0.5,
+            material: Material::Lambert {
+                albedo: Texture::Constant(Color::new(0.5, 0.5, 0.5)),
+            },
+        }));
+        world
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "API precondition check failed")
+    )]
+    fn test_adaptive_min_samples_above_samples() {
+        let world = one_sphere();
+        let mut pixels = vec![0.0; 8 * 8 * 3];
+        let mut frame = FrameBuffer::new(&mut pixels, (8, 8));
+        let options = RenderOptions {
+            adaptive: Some(AdaptiveSampling {
+                min_samples: 16,
+                threshold: 0.02,
+            }),
+            ..RenderOptions::default()
+        };
+
+        let result = render_frame(ComputeEnv::Naive, 10, 4, &world, &mut frame, &options);
+        assert!(matches!(result, Err(RendererError::InvalidParameter)));
+    }
+
+    #[test]
+    fn test_progressive_time_limit_stops_pass() {
+        let world = one_sphere();
+        let mut pixels = vec![0.0; 32 * 32 * 3];
+        // a single pass takes far longer than the time limit
+        let progressive = ProgressiveOptions {
+            samples_per_pass: 4096,
+            time_limit: Some(Duration::from_millis(20)),
+            ..ProgressiveOptions::default()
+        };
+
+        let start = Instant::now();
+        let (report, reason) = render_progressive(
+            ComputeEnv::Naive,
+            4,
+            &world,
+            &mut pixels,
+            (32, 32),
+            &RenderOptions::default(),
+            &progressive,
+            |_, _| panic!("no pass should finish"),
+        )
+        .unwrap();
+
+        assert_eq!(reason, StopReason::TimeLimit);
+        assert_eq!(report.passes, 0);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(pixels.iter().all(|&p| p == 0.0));
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "API precondition check failed")
+    )]
+    fn test_progressive_noise_target_zero() {
+        let world = one_sphere();
+        let mut pixels = vec![0.0; 8 * 8 * 3];
+        let progressive = ProgressiveOptions {
+            noise_target: Some(0.0),
+            max_samples: Some(16),
+            ..ProgressiveOptions::default()
+        };
+
+        let result = render_progressive(
+            ComputeEnv::Naive,
+            4,
+            &world,
+            &mut pixels,
+            (8, 8),
+            &RenderOptions::default(),
+            &progressive,
+            |_, _| {},
+        );
+        assert!(matches!(result, Err(RendererError::InvalidParameter)));
+    }
+}
@@ -0,0 +1,166 @@
+//! The settings for how a render is executed, as opposed to what is
+//! rendered, which is described by the scene.
+
+use crate::renderer::film::Filter;
+use crate::renderer::progress::{CancelToken, ProgressCallback};
+use crate::renderer::sampler::SamplerType;
+
+use std::str::FromStr;
+
+/// The order the tiles of the image are rendered in. The tiles are
+/// handed out to the threads in this order.
+///
+/// * `Hilbert` - Follows a Hilbert curve, so consecutive tiles are always
+///   next to each other, which keeps the scene data the threads are working
+///   on close together.
+/// * `Spiral` - Starts in the center of the image and spirals outward, so
+///   the subject of the image finishes first.
+/// * `Scanline` - Row by row from the top left.
+#[derive(Copy, Clone, Debug, PartialEq, Default, serde::Deserialize)]
+pub enum TileOrder {
+    #[default]
+    Hilbert,
+    Spiral,
+    Scanline,
+}
+
+/// A rectangle of pixels of the image, from the top left corner up to,
+/// but not including, the bottom right corner.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CropWindow {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl CropWindow {
+    /// Returns whether the window has pixels and is inside of an image of
+    /// the bounds.
+    pub fn is_valid(&self, bounds: (u32, u32)) -> bool {
+        self.x0 < self.x1 && self.y0 < self.y1 && self.x1 <= bounds.0 && self.y1 <= bounds.1
+    }
+}
+
+/// Parses a crop window from the four pixel coordinates `x0,y0,x1,y1`.
+impl FromStr for CropWindow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|v| v.trim().parse::<u32>())
+            .collect::<Result<Vec<u32>, _>>()
+            .map_err(|e| format!("Invalid crop window {}: {}", s, e))?;
+
+        match values.as_slice() {
+            [x0, y0, x1, y1] => Ok(CropWindow {
+                x0: *x0,
+                y0: *y0,
+                x1: *x1,
+                y1: *y1,
+            }),
+            _ => Err(format!("Invalid crop window {}, expected x0,y0,x1,y1", s)),
+        }
+    }
+}
+
+/// Settings for adaptive sampling, which stops sampling a pixel once it
+/// has converged instead of always taking the full samples per pixel.
+/// The samples per pixel of the render become the most a pixel gets.
+///
+/// Fields:
+/// * `min_samples` - The samples every pixel gets before its error is
+///   estimated. Must be > 1, the variance needs at least two samples,
+///   and not more than the samples per pixel of the render.
+/// * `threshold` - A pixel stops once the standard error of its mean
+///   relative to its brightness is below this, e.g. 0.02 for 2%.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AdaptiveSampling {
+    pub min_samples: u32,
+    pub threshold: f32,
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> AdaptiveSampling {
+        AdaptiveSampling {
+            min_samples: 4,
+            threshold: 0.02,
+        }
+    }
+}
+
+/// The options for executing the render.
+///
+/// Fields:
+/// * `tile_size` - The width and height of the square tiles the image is
+///   split into. Must be > 0.
+/// * `tile_order` - The order the tiles are rendered in.
+/// * `crop` - Only render the pixels inside of this window. The pixels
+///   outside of it are left as they are in the image buffer.
+/// * `progress` - Called with the progress every time a tile finishes.
+/// * `cancel` - Checked while rendering to stop the render early.
+/// * `adaptive` - Sample the pixels adaptively, instead of the same number
+///   of samples for all of them.
+/// * `sampler` - Where the random numbers of the samples come from.
+/// * `first_sample` - The index of the first sample of every pixel. Renders
+///   that add samples to an earlier render continue the sample sequences
+///   from here instead of repeating them.
+/// * `seed` - The seed of the random numbers. The same seed renders the
+///   same image, however the work is split between the threads.
+/// * `filter` - The filter that reconstructs the pixels from the samples.
+#[derive(Clone)]
+pub struct RenderOptions {
+    pub tile_size: u32,
+    pub tile_order: TileOrder,
+    pub crop: Option<CropWindow>,
+    pub progress: Option<ProgressCallback>,
+    pub cancel: Option<CancelToken>,
+    pub adaptive: Option<AdaptiveSampling>,
+    pub sampler: SamplerType,
+    pub first_sample: u32,
+    pub seed: u64,
+    pub filter: Filter,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            tile_size: 32,
+            tile_order: TileOrder::Hilbert,
+            crop: None,
+            progress: None,
+            cancel: None,
+            adaptive: None,
+            sampler: SamplerType::Sobol,
+            first_sample: 0,
+            seed: 0,
+            filter: Filter::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crop_window() {
+        let crop: CropWindow = "10, 20,30,40".parse().unwrap();
+        assert_eq!(crop.x0, 10);
+        assert_eq!(crop.y0, 20);
+        assert_eq!(crop.x1, 30);
+        assert_eq!(crop.y1, 40);
+
+        assert!(crop.is_valid((30, 40)));
+        assert!(!crop.is_valid((29, 40)));
+        assert!(!crop.is_valid((30, 39)));
+
+        let empty: CropWindow = "10,20,10,40".parse().unwrap();
+        assert!(!empty.is_valid((100, 100)));
+
+        assert!("10,20,30".parse::<CropWindow>().is_err());
+        assert!("10,20,30,-40".parse::<CropWindow>().is_err());
+        assert!("a,b,c,d".parse::<CropWindow>().is_err());
+    }
+}
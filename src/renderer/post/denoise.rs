@@ -0,0 +1,289 @@
+//! A denoiser for renders with few samples per pixel, the edge-avoiding
+//! à-trous wavelet filter from Dammertz et al. 2010, "Edge-Avoiding
+//! À-Trous Wavelet Transform for fast Global Illumination Filtering".
+//!
+//! The image is blurred with a 5x5 kernel several times, spreading the
+//! taps further apart every time, so a few cheap passes cover a large
+//! area. The taps are weighted down across edges, which are found in the
+//! noise free albedo, normals and depth of the first hits, and in the
+//! brightness of the image itself, measured against the noise around the
+//! pixel like in Schied et al. 2017, "Spatiotemporal Variance-Guided
+//! Filtering".
+
+use rayon::prelude::*;
+
+use crate::renderer::aov::Aov;
+use crate::renderer::core::color::{self, Color};
+use crate::renderer::core::vector::Vec3;
+use crate::renderer::core::write_pixel;
+
+/// The B3 spline the kernel is made of.
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// The buffers that guide the denoiser, rgb like the image. They're the
+/// AOVs in `Denoiser::FEATURES`.
+///
+/// Fields:
+/// * `albedo` - The colors of the first hits.
+/// * `normal` - The shading normals of the first hits.
+/// * `depth` - The distances to the first hits, the same in all channels.
+/// * `emission` - The light given off by the first hits. It's noise free,
+///   so it's left out of the filtering.
+pub struct DenoiseFeatures<'a> {
+    pub albedo: &'a [f32],
+    pub normal: &'a [f32],
+    pub depth: &'a [f32],
+    pub emission: &'a [f32],
+}
+
+/// The settings of the denoiser. The sigmas are how different two pixels
+/// can be before they're kept apart, smaller values keep more detail and
+/// more noise.
+///
+/// Fields:
+/// * `iterations` - The number of passes. Every pass doubles the distance
+///   between the taps, so 5 passes reach 2 * 16 pixels away.
+/// * `sigma_color` - For the brightness of the image with the albedo taken
+///   out, in standard deviations of the pixels around it. Where the image
+///   is noisy, it takes a big difference to be an edge.
+/// * `sigma_normal` - For the distance between the normals.
+/// * `sigma_depth` - For the difference in depth per pixel, relative to
+///   the depth.
+#[derive(Copy, Clone, Debug)]
+pub struct Denoiser {
+    pub iterations: u32,
+    pub sigma_color: f32,
+    pub sigma_normal: f32,
+    pub sigma_depth: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Denoiser {
+        Denoiser {
+            iterations: 5,
+            sigma_color: 4.0,
+            sigma_normal: 0.3,
+            sigma_depth: 0.1,
+        }
+    }
+}
+
+/// The features of a pixel.
+#[derive(Copy, Clone)]
+struct Guide {
+    normal: Vec3,
+    depth: f32,
+    emission: f32,
+}
+
+impl Denoiser {
+    /// The AOVs that have to be rendered for the denoiser.
+    pub const FEATURES: [Aov; 4] = [Aov::Albedo, Aov::Normal, Aov::Depth, Aov::Emission];
+
+    /// Returns the denoised image.
+    ///
+    /// Parameters:
+    /// * `pixels` - The linear rgb image.
+    /// * `features` - The feature buffers of the image.
+    /// * `bounds` - The width and height of the image.
+    pub fn denoise(
+        &self,
+        pixels: &[f32],
+        features: &DenoiseFeatures,
+        bounds: (u32, u32),
+    ) -> Vec<f32> {
+        let color_at = |buffer: &[f32], i: usize| {
+            Color::new(buffer[i * 3], buffer[i * 3 + 1], buffer[i * 3 + 2])
+        };
+        let len = bounds.0 as usize * bounds.1 as usize;
+
+        // the texture detail is in the albedo, so only the lighting is
+        // filtered. Nothing is taken out of the background.
+        let albedo: Vec<Color> = (0..len)
+            .map(|i| {
+                let a = color_at(features.albedo, i);
+                if a.near_zero() {
+                    color::WHITE
+                } else {
+                    Color::new(a.x.max(0.01), a.y.max(0.01), a.z.max(0.01))
+                }
+            })
+            .collect();
+        let guides: Vec<Guide> = (0..len)
+            .map(|i| Guide {
+                normal: color_at(features.normal, i),
+                depth: features.depth[i * 3],
+                emission: color::luminance(color_at(features.emission, i)),
+            })
+            .collect();
+
+        let mut light: Vec<Color> = (0..len)
+            .map(|i| {
+                let c = color_at(pixels, i) - color_at(features.emission, i);
+                let a = albedo[i];
+                Color::new(c.x / a.x, c.y / a.y, c.z / a.z)
+            })
+            .collect();
+        for i in 0..self.iterations {
+            light = self.filter_pass(&light, &guides, bounds, 1 << i);
+        }
+
+        let mut denoised = vec![0.0; pixels.len()];
+        for (i, (l, a)) in light.iter().zip(albedo.iter()).enumerate() {
+            write_pixel!(*l * *a + color_at(features.emission, i), denoised, i);
+        }
+        denoised
+    }
+
+    /// A single pass of the filter, with the taps `step` pixels apart.
+    fn filter_pass(
+        &self,
+        light: &[Color],
+        guides: &[Guide],
+        bounds: (u32, u32),
+        step: i32,
+    ) -> Vec<Color> {
+        let (w, h) = (bounds.0 as i32, bounds.1 as i32);
+        let luminance: Vec<f32> = light.iter().map(|l| color::luminance(*l)).collect();
+        let inv_normal = 1.0 / (self.sigma_normal * self.sigma_normal);
+
+        let mut out = vec![color::BLACK; light.len()];
+        out.par_chunks_mut(w as usize)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let y = y as i32;
+                for (x, out) in row.iter_mut().enumerate() {
+                    let x = x as i32;
+                    let p = (y * w + x) as usize;
+                    let g_p = guides[p];
+                    let deviation = local_deviation(&luminance, (x, y), (w, h));
+                    let inv_luminance = 1.0 / (self.sigma_color * deviation + 1e-4);
+
+                    let mut sum = color::BLACK;
+                    let mut total = 0.0;
+                    for (ky, hy) in KERNEL.iter().enumerate() {
+                        let qy = y + (ky as i32 - 2) * step;
+                        if qy < 0 || qy >= h {
+                            continue;
+                        }
+                        for (kx, hx) in KERNEL.iter().enumerate() {
+                            let qx = x + (kx as i32 - 2) * step;
+                            if qx < 0 || qx >= w {
+                                continue;
+                            }
+                            let q = (qy * w + qx) as usize;
+                            let g_q = guides[q];
+
+                            let luminance_distance = (luminance[p] - luminance[q]).abs();
+                            let normal_distance = (g_p.normal - g_q.normal).length_squared();
+                            // planes seen at an angle change in depth from
+                            // pixel to pixel, so the difference is per pixel
+                            let pixels = (((qx - x).pow(2) + (qy - y).pow(2)) as f32).sqrt();
+                            let depth_distance = (g_p.depth - g_q.depth).abs()
+                                / (self.sigma_depth
+                                    * g_p.depth.max(g_q.depth).max(1e-3)
+                                    * pixels.max(1.0));
+                            // the lights don't blend with what's around them
+                            let emission_distance = (g_p.emission - g_q.emission).abs()
+                                / (0.1 * g_p.emission.max(g_q.emission) + 1e-4);
+
+                            let weight = hx
+                                * hy
+                                * (-luminance_distance * inv_luminance
+                                    - normal_distance * inv_normal
+                                    - depth_distance
+                                    - emission_distance)
+                                    .exp();
+                            sum += weight * light[q];
+                            total += weight;
+                        }
+                    }
+
+                    // the center tap always has some weight
+                    *out = sum / total;
+                }
+            });
+
+        out
+    }
+}
+
+/// The standard deviation of the luminance of the 3x3 pixels around a
+/// pixel, as an estimate of its noise.
+fn local_deviation(luminance: &[f32], (x, y): (i32, i32), (w, h): (i32, i32)) -> f32 {
+    let mut sum = 0.0;
+    let mut sum_squared = 0.0;
+    let mut n = 0.0;
+    for qy in (y - 1).max(0)..(y + 2).min(h) {
+        for qx in (x - 1).max(0)..(x + 2).min(w) {
+            let l = luminance[(qy * w + qx) as usize];
+            sum += l;
+            sum_squared += l * l;
+            n += 1.0;
+        }
+    }
+
+    let mean = sum / n;
+    (sum_squared / n - mean * mean).max(0.0).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::sampler::hash;
+
+    #[test]
+    fn test_denoise() {
+        // noisy light on two walls facing different ways
+        let (w, h) = (32, 16);
+        let expected = |x: u32| if x < w / 2 { 0.1 } else { 0.4 };
+        let mut pixels = Vec::new();
+        let mut albedo = Vec::new();
+        let mut normal = Vec::new();
+        let mut depth = Vec::new();
+        let emission = vec![0.0; (w * h * 3) as usize];
+        for y in 0..h {
+            for x in 0..w {
+                let noise = hash::to_unit_float(hash::hash(&[x as u64, y as u64])) - 0.5;
+                pixels.extend_from_slice(&[expected(x) * (1.0 + noise); 3]);
+                albedo.extend_from_slice(&[0.5; 3]);
+                if x < w / 2 {
+                    normal.extend_from_slice(&[1.0, 0.0, 0.0]);
+                } else {
+                    normal.extend_from_slice(&[0.0, 1.0, 0.0]);
+                }
+                depth.extend_from_slice(&[2.0; 3]);
+            }
+        }
+
+        let features = DenoiseFeatures {
+            albedo: &albedo,
+            normal: &normal,
+            depth: &depth,
+            emission: &emission,
+        };
+        let denoised = Denoiser::default().denoise(&pixels, &features, (w, h));
+        assert_eq!(denoised.len(), pixels.len());
+
+        // most of the noise is gone, and the edge between the walls is
+        // still there
+        let error = |image: &[f32]| {
+            let mut total = 0.0;
+            for y in 0..h {
+                for x in 0..w {
+                    let e = image[((y * w + x) * 3) as usize] / expected(x) - 1.0;
+                    total += e * e;
+                }
+            }
+            (total / (w * h) as f32).sqrt()
+        };
+        assert!(
+            error(&denoised) < 0.2 * error(&pixels),
+            "{} {}",
+            error(&denoised),
+            error(&pixels)
+        );
+        assert!(denoised[((w / 2 - 1) * 3) as usize] < 0.12);
+        assert!(denoised[((w / 2) * 3) as usize] > 0.35);
+    }
+}
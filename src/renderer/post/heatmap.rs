@@ -0,0 +1,79 @@
+use super::tonemap::srgb_eotf;
+use crate::renderer::core::color::{self, Color};
+
+/// The colors of the heatmap from no samples to the most samples, in sRGB.
+const RAMP: [Color; 5] = [
+    Color {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    },
+    Color {
+        x: 0.1,
+        y: 0.2,
+        z: 0.8,
+    },
+    Color {
+        x: 0.1,
+        y: 0.8,
+        z: 0.3,
+    },
+    Color {
+        x: 1.0,
+        y: 0.85,
+        z: 0.1,
+    },
+    Color {
+        x: 0.9,
+        y: 0.1,
+        z: 0.1,
+    },
+];
+
+/// Turns the number of samples per pixel into a linear rgb image, going
+/// from black for no samples over blue, green and yellow to red for the
+/// most samples. The colors are linear so that they come out as in the
+/// ramp when written with the default tone mapping.
+///
+/// Parameters:
+/// * `counts` - The number of samples of each pixel.
+/// * `max_samples` - The count that maps to red. Counts above it are red
+///   as well.
+pub fn sample_heatmap(counts: &[u32], max_samples: u32) -> Vec<f32> {
+    let mut pixels = Vec::with_capacity(counts.len() * 3);
+
+    for count in counts.iter() {
+        let t = (*count as f32 / max_samples.max(1) as f32).min(1.0);
+        let scaled = t * (RAMP.len() - 1) as f32;
+        let i = (scaled as usize).min(RAMP.len() - 2);
+        let c = color::lerp(RAMP[i], RAMP[i + 1], scaled - i as f32);
+
+        pixels.push(srgb_eotf(c.x));
+        pixels.push(srgb_eotf(c.y));
+        pixels.push(srgb_eotf(c.z));
+    }
+
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_heatmap() {
+        let pixels = sample_heatmap(&[0, 8, 16, 32], 16);
+        assert_eq!(pixels.len(), 12);
+
+        // nothing is black
+        assert_eq!(&pixels[0..3], &[0.0, 0.0, 0.0]);
+
+        // halfway is the middle of the ramp
+        assert!((pixels[3] - srgb_eotf(0.1)).abs() < 1e-6);
+        assert!((pixels[4] - srgb_eotf(0.8)).abs() < 1e-6);
+
+        // the max and anything over it is red
+        assert!((pixels[6] - srgb_eotf(0.9)).abs() < 1e-6);
+        assert_eq!(&pixels[6..9], &pixels[9..12]);
+    }
+}
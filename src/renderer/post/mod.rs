@@ -0,0 +1,7 @@
+//! Post processing applied to the rendered image before it's written
+//! out, like mapping the linear radiance into a displayable range.
+
+pub mod denoise;
+pub mod heatmap;
+pub mod preview;
+pub mod tonemap;
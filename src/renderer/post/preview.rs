@@ -0,0 +1,82 @@
+use super::tonemap::srgb_eotf;
+use crate::renderer::aov::Aov;
+use crate::renderer::core::color::{self, Color};
+use crate::renderer::core::vector;
+use crate::renderer::sampler::hash;
+
+/// Turns the buffer of an output variable into a linear rgb image that
+/// shows it when written to an 8 bit format with the default tone mapping.
+/// The high dynamic range formats keep the raw values instead.
+///
+/// * The normals map from [-1, 1] to [0, 1].
+/// * The depth goes from white up close to black at the furthest hit.
+/// * Every id gets its own random color.
+/// * The rest, like the albedo and the light components, are shown as
+///   they are.
+///
+/// The pixels where the camera ray doesn't hit anything stay black.
+pub fn aov_preview(aov: Aov, pixels: &[f32]) -> Vec<f32> {
+    let max_depth = match aov {
+        Aov::Depth => pixels.iter().fold(0.0f32, |max, d| max.max(*d)),
+        _ => 0.0,
+    };
+
+    let mut preview = Vec::with_capacity(pixels.len());
+    for value in pixels.chunks_exact(3) {
+        let value = Color::new(value[0], value[1], value[2]);
+        let c = if value.near_zero() {
+            color::BLACK
+        } else {
+            match aov {
+                Aov::Normal | Aov::GeometricNormal => {
+                    0.5 * vector::unit_vector(&value) + Color::new(0.5, 0.5, 0.5)
+                }
+                Aov::Depth => {
+                    let t = 1.0 - value.x / max_depth;
+                    Color::new(t, t, t)
+                }
+                Aov::ObjectId | Aov::MaterialId => id_color(value.x as u64),
+                _ => value,
+            }
+        };
+
+        preview.push(srgb_eotf(c.x.clamp(0.0, 1.0)));
+        preview.push(srgb_eotf(c.y.clamp(0.0, 1.0)));
+        preview.push(srgb_eotf(c.z.clamp(0.0, 1.0)));
+    }
+
+    preview
+}
+
+/// A random but bright enough color for an id, in sRGB.
+fn id_color(id: u64) -> Color {
+    let channel = |i: u64| 0.2 + 0.8 * hash::to_unit_float(hash::hash(&[id, i]));
+    Color::new(channel(0), channel(1), channel(2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aov_preview() {
+        let normals = aov_preview(Aov::Normal, &[0.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        assert_eq!(&normals[0..3], &[0.0, 0.0, 0.0]);
+        assert_eq!(normals[4], 1.0);
+        assert!((normals[3] - srgb_eotf(0.5)).abs() < 1e-6);
+
+        // the closest hit is the brightest, the furthest is black
+        let depth = aov_preview(Aov::Depth, &[1.0, 1.0, 1.0, 4.0, 4.0, 4.0]);
+        assert!((depth[0] - srgb_eotf(0.75)).abs() < 1e-6);
+        assert_eq!(depth[3], 0.0);
+
+        // the same id always gets the same color
+        let ids = aov_preview(
+            Aov::ObjectId,
+            &[3.0, 3.0, 3.0, 3.0, 3.0, 3.0, 4.0, 4.0, 4.0],
+        );
+        assert_eq!(&ids[0..3], &ids[3..6]);
+        assert_ne!(&ids[0..3], &ids[6..9]);
+        assert!(ids.iter().all(|c| *c > 0.0));
+    }
+}
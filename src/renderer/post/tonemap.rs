@@ -0,0 +1,284 @@
+use serde::{Deserialize, Serialize};
+
+use crate::renderer::core::color::Color;
+use crate::renderer::core::{clamp, convert_pixel};
+
+/// The curve used to compress the linear radiance of the render into the
+/// displayable [0, 1] range. All of the curves are applied to each
+/// channel separately.
+///
+/// * `Linear` - Clamps anything brighter than white.
+/// * `Reinhard` - The simple Reinhard curve, x / (1 + x). It never reaches
+///   white.
+/// * `ExtendedReinhard` - Reinhard with a white point that maps to white.
+/// * `Aces` - Krzysztof Narkowicz's fit of the ACES filmic curve.
+/// * `Hable` - John Hable's filmic curve from Uncharted 2.
+/// * `Legacy` - The square root gamma 2.0 approximation, without the sRGB
+///   curve, that older versions of the renderer used. Useful for
+///   reproducing old renders.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToneMapOperator {
+    #[default]
+    Linear,
+    Reinhard,
+    ExtendedReinhard,
+    Aces,
+    Hable,
+    Legacy,
+}
+
+/// The configuration for converting the linear floating point render into
+/// an 8 bit display referred image. The exposure is applied first, then the
+/// tone mapping curve and lastly the sRGB transfer function.
+///
+/// Fields:
+/// * `operator` - The tone mapping curve.
+/// * `exposure` - The exposure adjustment in stops. Each stop doubles the
+///   brightness.
+/// * `white_point` - The linear value that maps to white for the extended
+///   Reinhard curve. Must be > 0.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct ToneMapping {
+    pub operator: ToneMapOperator,
+    pub exposure: f32,
+    pub white_point: f32,
+}
+
+impl Default for ToneMapping {
+    fn default() -> ToneMapping {
+        ToneMapping {
+            operator: ToneMapOperator::Linear,
+            exposure: 0.0,
+            white_point: 4.0,
+        }
+    }
+}
+
+impl ToneMapping {
+    /// Whether the settings can be used, the white point has to be
+    /// positive for the extended Reinhard curve to be defined.
+    pub fn is_valid(&self) -> bool {
+        self.white_point > 0.0 && self.white_point.is_finite()
+    }
+
+    /// Maps a linear pixel to a display referred sRGB pixel in [0, 1].
+    pub fn map(&self, pixel: Color) -> Color {
+        let scale = self.exposure.exp2();
+
+        Color::new(
+            self.map_channel(pixel.x * scale),
+            self.map_channel(pixel.y * scale),
+            self.map_channel(pixel.z * scale),
+        )
+    }
+
+    /// Maps a linear pixel to an 8 bit sRGB pixel.
+    pub fn convert_pixel(&self, pixel: Color) -> (u8, u8, u8) {
+        if self.operator == ToneMapOperator::Legacy {
+            // keep the exact quantization of the old renders
+            return convert_pixel(self.exposure.exp2() * pixel);
+        }
+
+        let mapped = self.map(pixel);
+        (quantize(mapped.x), quantize(mapped.y), quantize(mapped.z))
+    }
+
+    /// Converts a buffer of linear floating point rgb pixels into 8 bit rgb
+    /// pixels. Both buffers must have the same number of pixels.
+    pub fn convert_buffer(&self, pixels: &[f32], out: &mut [u8]) {
+        for (from, to) in pixels.chunks_exact(3).zip(out.chunks_exact_mut(3)) {
+            let (r, g, b) = self.convert_pixel(Color::new(from[0], from[1], from[2]));
+
+            to[0] = r;
+            to[1] = g;
+            to[2] = b;
+        }
+    }
+
+    fn map_channel(&self, x: f32) -> f32 {
+        // negative values can come out of the reconstruction, there's no
+        // meaningful way to display them
+        let x = x.max(0.0);
+
+        let mapped = match self.operator {
+            ToneMapOperator::Linear => x,
+            ToneMapOperator::Reinhard => x / (1.0 + x),
+            ToneMapOperator::ExtendedReinhard => {
+                let white_squared = self.white_point * self.white_point;
+                x * (1.0 + x / white_squared) / (1.0 + x)
+            }
+            ToneMapOperator::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+            ToneMapOperator::Hable => {
+                // the exposure bias and linear white point from the
+                // original presentation
+                hable_partial(2.0 * x) / hable_partial(HABLE_WHITE_POINT)
+            }
+            ToneMapOperator::Legacy => return clamp(x.sqrt(), 0.0, 1.0),
+        };
+
+        srgb_oetf(clamp(mapped, 0.0, 1.0))
+    }
+}
+
+/// The linear value that Hable's curve maps to white.
+const HABLE_WHITE_POINT: f32 = 11.2;
+
+/// Hable's filmic curve before it's normalized by the white point.
+fn hable_partial(x: f32) -> f32 {
+    const A: f32 = 0.15;
+    const B: f32 = 0.50;
+    const C: f32 = 0.10;
+    const D: f32 = 0.20;
+    const E: f32 = 0.02;
+    const F: f32 = 0.30;
+
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+/// The exact piecewise sRGB opto-electronic transfer function, converting
+/// a linear value in [0, 1] to its sRGB encoding.
+pub fn srgb_oetf(x: f32) -> f32 {
+    if x <= 0.003_130_8 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// The inverse of `srgb_oetf()`, converting an sRGB encoded value in
+/// [0, 1] back to linear.
+pub fn srgb_eotf(x: f32) -> f32 {
+    if x <= 0.040_45 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a value in [0, 1] to the nearest 8 bit value.
+#[inline]
+fn quantize(x: f32) -> u8 {
+    (clamp(x, 0.0, 1.0) * 255.0 + 0.5) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(operator: ToneMapOperator, x: f32) -> f32 {
+        let tm = ToneMapping {
+            operator,
+            ..ToneMapping::default()
+        };
+        tm.map(Color::new(x, x, x)).x
+    }
+
+    #[test]
+    fn test_srgb_oetf() {
+        assert_eq!(srgb_oetf(0.0), 0.0);
+        assert!((srgb_oetf(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_oetf(0.5) - 0.735_357).abs() < 1e-5);
+
+        // both pieces meet at the break point
+        let below = srgb_oetf(0.003_130_8);
+        let above = srgb_oetf(0.003_130_9);
+        assert!((above - below).abs() < 1e-5);
+
+        for i in 0..=100 {
+            let x = i as f32 / 100.0;
+            assert!((srgb_eotf(srgb_oetf(x)) - x).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_operators() {
+        let operators = [
+            ToneMapOperator::Linear,
+            ToneMapOperator::Reinhard,
+            ToneMapOperator::ExtendedReinhard,
+            ToneMapOperator::Aces,
+            ToneMapOperator::Hable,
+            ToneMapOperator::Legacy,
+        ];
+
+        for operator in operators.iter() {
+            // black stays black and the curves never decrease
+            assert!(map(*operator, 0.0).abs() < 1e-6);
+            assert_eq!(map(*operator, -1.0), map(*operator, 0.0));
+
+            let mut last = 0.0;
+            for i in 1..100 {
+                let y = map(*operator, i as f32 * 0.1);
+                assert!(y >= last);
+                assert!(y <= 1.0);
+                last = y;
+            }
+        }
+
+        assert!((map(ToneMapOperator::Linear, 2.0) - 1.0).abs() < 1e-6);
+        assert!((map(ToneMapOperator::Reinhard, 1.0) - srgb_oetf(0.5)).abs() < 1e-6);
+        assert!((map(ToneMapOperator::ExtendedReinhard, 4.0) - 1.0).abs() < 1e-6);
+        assert!((map(ToneMapOperator::Hable, HABLE_WHITE_POINT / 2.0) - 1.0).abs() < 1e-6);
+        assert!(map(ToneMapOperator::Aces, 100.0) > 0.99);
+        assert_eq!(map(ToneMapOperator::Legacy, 0.25), 0.5);
+    }
+
+    #[test]
+    fn test_exposure() {
+        let tm = ToneMapping {
+            exposure: 1.0,
+            ..ToneMapping::default()
+        };
+        let brighter = tm.map(Color::new(0.25, 0.25, 0.25));
+        assert!((brighter.x - srgb_oetf(0.5)).abs() < 1e-6);
+
+        let tm = ToneMapping {
+            exposure: -2.0,
+            ..ToneMapping::default()
+        };
+        let darker = tm.map(Color::new(2.0, 2.0, 2.0));
+        assert!((darker.x - srgb_oetf(0.5)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_convert_pixel() {
+        let tm = ToneMapping::default();
+        assert_eq!(tm.convert_pixel(Color::new(0.0, 1.0, 10.0)), (0, 255, 255));
+        assert_eq!(tm.convert_pixel(Color::new(0.5, 0.5, 0.5)), (188, 188, 188));
+
+        // the legacy conversion matches the old renders exactly
+        let tm = ToneMapping {
+            operator: ToneMapOperator::Legacy,
+            ..ToneMapping::default()
+        };
+        let pixel = Color::new(0.1, 0.5, 2.0);
+        assert_eq!(tm.convert_pixel(pixel), convert_pixel(pixel));
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let tm: ToneMapping =
+            serde_json::from_str(r#"{"operator": "extended-reinhard", "exposure": 1.5}"#).unwrap();
+        assert_eq!(tm.operator, ToneMapOperator::ExtendedReinhard);
+        assert_eq!(tm.exposure, 1.5);
+        assert_eq!(tm.white_point, 4.0);
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(ToneMapping::default().is_valid());
+
+        let tm: ToneMapping = serde_json::from_str(r#"{"white_point": 0}"#).unwrap();
+        assert!(!tm.is_valid());
+
+        for white_point in [-1.0, f32::NAN, f32::INFINITY] {
+            let tm = ToneMapping {
+                white_point,
+                ..ToneMapping::default()
+            };
+            assert!(!tm.is_valid());
+        }
+    }
+}
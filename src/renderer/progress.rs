@@ -0,0 +1,152 @@
+//! Feedback on a render while it's running, and a way to stop it early.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A snapshot of how far along the render is. Reported to the progress
+/// callback every time a tile finishes.
+///
+/// Fields:
+/// * `tiles_done` - The number of tiles that have finished.
+/// * `tiles_total` - The number of tiles in the render.
+/// * `pixels_done` - The number of pixels in the finished tiles.
+/// * `pixels_total` - The number of pixels in the render.
+/// * `elapsed` - The time since the render started.
+/// * `eta` - The estimated time until the render finishes, extrapolated
+///   from the pixels done so far. None until there's something to
+///   extrapolate from.
+#[derive(Copy, Clone, Debug)]
+pub struct Progress {
+    pub tiles_done: usize,
+    pub tiles_total: usize,
+    pub pixels_done: usize,
+    pub pixels_total: usize,
+    pub elapsed: Duration,
+    pub eta: Option<Duration>,
+}
+
+impl Progress {
+    /// The fraction of the pixels that are done, in [0, 1].
+    pub fn fraction(&self) -> f32 {
+        if self.pixels_total == 0 {
+            return 1.0;
+        }
+        self.pixels_done as f32 / self.pixels_total as f32
+    }
+}
+
+/// The function called with the progress of the render. It's called from
+/// the render threads, one call at a time.
+pub type ProgressCallback = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+/// A token for stopping a render early. Clones share the same state, so
+/// one can be given to the render while another is kept to cancel it from
+/// another thread or from the progress callback. The render stops at the
+/// next row of pixels. A token can also have a deadline, after which it
+/// counts as cancelled on its own.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Asks the render to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// A clone of the token that is also cancelled once the deadline has
+    /// passed. Cancelling either of them still cancels both.
+    pub fn with_deadline(&self, deadline: Instant) -> CancelToken {
+        CancelToken {
+            cancelled: self.cancelled.clone(),
+            deadline: Some(deadline),
+        }
+    }
+
+    /// Whether the render has been asked to stop.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// What was rendered before a render was cancelled. The tiles that
+/// didn't finish are left as they were in the image buffer.
+///
+/// Fields:
+/// * `pixels` - A copy of the image buffer when the render stopped.
+/// * `bounds` - The width and height of the image.
+/// * `tiles_done` - The number of tiles that finished.
+/// * `tiles_total` - The number of tiles in the render.
+pub struct PartialImage {
+    pub pixels: Vec<f32>,
+    pub bounds: (u32, u32),
+    pub tiles_done: usize,
+    pub tiles_total: usize,
+}
+
+// the pixels would flood the log
+impl std::fmt::Debug for PartialImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PartialImage")
+            .field("bounds", &self.bounds)
+            .field("tiles_done", &self.tiles_done)
+            .field("tiles_total", &self.tiles_total)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_token() {
+        let token = CancelToken::new();
+        let shared = token.clone();
+        assert!(!token.is_cancelled());
+
+        shared.cancel();
+        assert!(token.is_cancelled());
+        assert!(shared.is_cancelled());
+    }
+
+    #[test]
+    fn test_cancel_token_deadline() {
+        let token = CancelToken::new();
+        let later = token.with_deadline(Instant::now() + Duration::from_secs(3600));
+        let passed = token.with_deadline(Instant::now());
+        assert!(!token.is_cancelled());
+        assert!(!later.is_cancelled());
+        assert!(passed.is_cancelled());
+
+        later.cancel();
+        assert!(token.is_cancelled());
+        assert!(later.is_cancelled());
+    }
+
+    #[test]
+    fn test_progress_fraction() {
+        let mut progress = Progress {
+            tiles_done: 1,
+            tiles_total: 4,
+            pixels_done: 25,
+            pixels_total: 100,
+            elapsed: Duration::from_secs(1),
+            eta: None,
+        };
+        assert_eq!(progress.fraction(), 0.25);
+
+        progress.pixels_total = 0;
+        assert_eq!(progress.fraction(), 1.0);
+    }
+}
@@ -0,0 +1,136 @@
+//! The settings and reports of a progressive render, which keeps adding
+//! passes of samples to the image until it runs out of time or the image
+//! is clean enough.
+
+use std::time::Duration;
+
+/// When a progressive render stops. At least one of the limits has to be
+/// set, and the render stops at whichever is reached first.
+///
+/// Fields:
+/// * `samples_per_pass` - The samples per pixel rendered in each pass.
+///   Must be > 0.
+/// * `time_limit` - The wall clock budget for the render. A pass is only
+///   started when it's expected to finish in time, and a pass that is still
+///   running at the limit is stopped and dropped, so the render may end a
+///   little early but never goes over.
+/// * `noise_target` - Stop once the estimated relative error of the image
+///   is below this, e.g. 0.01 for 1%. Needs at least two passes. Must be
+///   greater than 0, and since a target may never be reached, it's best
+///   paired with one of the other limits.
+/// * `max_samples` - Stop once the pixels have this many samples.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ProgressiveOptions {
+    pub samples_per_pass: u32,
+    pub time_limit: Option<Duration>,
+    pub noise_target: Option<f32>,
+    pub max_samples: Option<u32>,
+}
+
+impl ProgressiveOptions {
+    /// Whether any of the limits are set.
+    pub fn is_bounded(&self) -> bool {
+        self.time_limit.is_some() || self.noise_target.is_some() || self.max_samples.is_some()
+    }
+}
+
+impl Default for ProgressiveOptions {
+    fn default() -> ProgressiveOptions {
+        ProgressiveOptions {
+            samples_per_pass: 4,
+            time_limit: None,
+            noise_target: None,
+            max_samples: None,
+        }
+    }
+}
+
+/// The state of a progressive render after a pass.
+///
+/// Fields:
+/// * `passes` - The number of passes done.
+/// * `samples` - The samples per pixel in the image.
+/// * `elapsed` - The time since the render started.
+/// * `noise` - The estimated relative error of the image, None until
+///   there are two passes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PassReport {
+    pub passes: u32,
+    pub samples: u32,
+    pub elapsed: Duration,
+    pub noise: Option<f32>,
+}
+
+/// Why a progressive render stopped.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StopReason {
+    TimeLimit,
+    NoiseTarget,
+    MaxSamples,
+    Cancelled,
+}
+
+/// Parses a duration like `90s`, `10m`, `1h30m` or a plain number of
+/// seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration {}, expected e.g. 90s, 10m or 1h30m", s);
+
+    let s = s.trim();
+    if let Ok(seconds) = s.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).map_err(|_| invalid());
+    }
+
+    let mut total = 0.0;
+    let mut number = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+
+        let unit = match c {
+            'h' => 3600.0,
+            'm' => 60.0,
+            's' => 1.0,
+            _ => return Err(invalid()),
+        };
+        let value = number.parse::<f64>().map_err(|_| invalid())?;
+        total += value * unit;
+        number.clear();
+    }
+
+    if !number.is_empty() || s.is_empty() {
+        return Err(invalid());
+    }
+
+    Duration::try_from_secs_f64(total).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_duration(" 1h30m "), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("1.5m"), Ok(Duration::from_secs(90)));
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("1h30").is_err());
+        assert!(parse_duration("-5").is_err());
+    }
+
+    #[test]
+    fn test_is_bounded() {
+        let mut options = ProgressiveOptions::default();
+        assert!(!options.is_bounded());
+
+        options.max_samples = Some(64);
+        assert!(options.is_bounded());
+    }
+}
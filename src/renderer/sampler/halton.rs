@@ -0,0 +1,100 @@
+use super::hash::{hash, to_unit_float};
+use super::independent::sample_rng;
+use super::rng::Pcg32;
+use super::{Sampler, ONE_MINUS_EPSILON};
+
+/// The bases of the dimensions of the Halton sequence. The higher bases
+/// take many samples to fill out the dimension, so past these, the values
+/// are independent random values instead.
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// The Halton sequence, where dimension d is the radical inverse of the
+/// sample index in the d-th prime base. Every pixel uses the same sequence,
+/// shifted by a random offset per pixel and dimension (a Cranley-Patterson
+/// rotation), so that neighboring pixels don't have the same pattern.
+pub struct HaltonSampler {
+    pixel: (u32, u32),
+    index: u32,
+    dimension: usize,
+    seed: u64,
+    rng: Pcg32,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> HaltonSampler {
+        HaltonSampler {
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            seed,
+            rng: Pcg32::new(seed, 0),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+        self.rng = sample_rng(self.seed, pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        if dimension >= PRIMES.len() {
+            return self.rng.uniform();
+        }
+
+        let shift = to_unit_float(hash(&[
+            self.seed,
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            dimension as u64,
+        ]));
+        let value = radical_inverse(PRIMES[dimension], self.index) + shift;
+
+        (value - value.floor()).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+/// Mirrors the digits of a in the base around the decimal point, e.g.
+/// 6 = 110 in base 2 becomes 0.011 = 0.375.
+pub fn radical_inverse(base: u32, mut a: u32) -> f32 {
+    let inv_base = 1.0 / base as f64;
+    let mut reversed: u64 = 0;
+    let mut inv_base_n = 1.0;
+
+    while a > 0 {
+        let next = a / base;
+        let digit = a - next * base;
+        reversed = reversed * base as u64 + digit as u64;
+        inv_base_n *= inv_base;
+        a = next;
+    }
+
+    ((reversed as f64 * inv_base_n) as f32).min(ONE_MINUS_EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_radical_inverse() {
+        assert_eq!(radical_inverse(2, 0), 0.0);
+        assert_eq!(radical_inverse(2, 1), 0.5);
+        assert_eq!(radical_inverse(2, 6), 0.375);
+        assert!((radical_inverse(3, 1) - 1.0 / 3.0).abs() < 1e-6);
+        assert!((radical_inverse(3, 5) - 7.0 / 9.0).abs() < 1e-6);
+    }
+}
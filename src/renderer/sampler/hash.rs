@@ -0,0 +1,109 @@
+//! Hashing and scrambling helpers for decorrelating the samples of the
+//! pixels and dimensions.
+
+/// A 64 bit finalizer that spreads every input bit over the output.
+#[inline]
+pub fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
+/// Hashes the values into a single seed.
+#[inline]
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e37_79b9_7f4a_7c15, |h, v| {
+        mix_bits(h ^ v.wrapping_add(0x9e37_79b9_7f4a_7c15))
+    })
+}
+
+/// Turns the high bits of a hash into a float in [0, 1).
+#[inline]
+pub fn to_unit_float(v: u64) -> f32 {
+    (v >> 40) as f32 * (1.0 / (1u64 << 24) as f32)
+}
+
+/// Returns the element at index i of a random permutation of [0, n), where
+/// the permutation is chosen by the seed. From Kensler 2013, "Correlated
+/// Multi-Jittered Sampling".
+pub fn permutation_element(mut i: u32, n: u32, seed: u32) -> u32 {
+    let p = seed;
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+
+        if i < n {
+            break;
+        }
+    }
+
+    i.wrapping_add(p) % n
+}
+
+/// An Owen scramble of the bits of x, where every bit is flipped depending
+/// on the bits above it. From Burley 2020, "Practical Hash-based Owen
+/// Scrambling".
+pub fn owen_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x.reverse_bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permutation_element() {
+        for n in [1, 2, 7, 16, 100] {
+            let mut seen = vec![false; n as usize];
+            for i in 0..n {
+                let p = permutation_element(i, n, 1234);
+                assert!(!seen[p as usize]);
+                seen[p as usize] = true;
+            }
+        }
+    }
+
+    #[test]
+    fn test_owen_scramble() {
+        // a scramble is a permutation of the values, so the 16 values with
+        // the same low bits map to 16 different values
+        let mut values: Vec<u32> = (0..16).map(|i| owen_scramble(i << 28, 42) >> 28).collect();
+        values.sort_unstable();
+        assert_eq!(values, (0..16).collect::<Vec<u32>>());
+
+        assert_ne!(hash(&[1, 2]), hash(&[2, 1]));
+        assert!(to_unit_float(u64::MAX) < 1.0);
+    }
+}
@@ -0,0 +1,42 @@
+use super::hash::hash;
+use super::rng::Pcg32;
+use super::Sampler;
+
+/// Uniformly random values with no relation between the samples. This is
+/// how the renderer always sampled, and the baseline the other samplers
+/// improve on.
+pub struct IndependentSampler {
+    seed: u64,
+    rng: Pcg32,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> IndependentSampler {
+        IndependentSampler {
+            seed,
+            rng: Pcg32::new(seed, 0),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.rng = sample_rng(self.seed, pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.rng.uniform()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.get_1d(), self.get_1d())
+    }
+}
+
+/// The random number stream of a sample of a pixel. Every sample gets its
+/// own stream, so the numbers don't depend on the order the pixels are
+/// rendered in.
+pub(crate) fn sample_rng(seed: u64, pixel: (u32, u32), index: u32) -> Pcg32 {
+    let h = hash(&[seed, pixel.0 as u64, pixel.1 as u64, index as u64]);
+    Pcg32::new(h, h >> 32)
+}
@@ -0,0 +1,118 @@
+//! The samplers that produce the random numbers of the render. Every
+//! random decision, from the position of a sample in the pixel to the
+//! direction a ray bounces, draws its numbers from a `Sampler`, so that
+//! the samplers can spread the samples of a pixel out evenly instead of
+//! leaving it to chance.
+//!
+//! A sampler is asked for the numbers of one sample of a pixel at a time.
+//! Each call to `get_1d()` or `get_2d()` moves on to the next dimension,
+//! so the same decision of every sample, e.g. the second bounce, gets its
+//! numbers from the same dimension.
+
+mod halton;
+pub(crate) mod hash;
+mod independent;
+mod rng;
+mod sobol;
+mod stratified;
+
+pub use halton::HaltonSampler;
+pub use independent::IndependentSampler;
+pub use sobol::SobolSampler;
+pub use stratified::StratifiedSampler;
+
+/// The largest f32 below 1, for keeping the samples in [0, 1).
+pub(crate) const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+/// A source of sample values in [0, 1).
+pub trait Sampler {
+    /// Starts a sample of a pixel, resetting the dimension to the first.
+    ///
+    /// Parameters:
+    /// * `pixel` - The x and y of the pixel in the image.
+    /// * `index` - The index of the sample of the pixel.
+    fn start_sample(&mut self, pixel: (u32, u32), index: u32);
+
+    /// Returns the value of the next dimension.
+    fn get_1d(&mut self) -> f32;
+
+    /// Returns the values of the next two dimensions.
+    fn get_2d(&mut self) -> (f32, f32);
+}
+
+/// The samplers to choose from.
+///
+/// * `Independent` - Every value is uniformly random on its own, so the
+///   samples clump together and leave holes like white noise.
+/// * `Stratified` - Splits each dimension into as many strata as there are
+///   samples per pixel, and jitters one sample inside of each.
+/// * `Halton` - The Halton sequence, with a random shift per pixel.
+/// * `Sobol` - The Sobol sequence with Owen scrambling, which is well
+///   distributed for any number of samples.
+#[derive(Copy, Clone, Debug, PartialEq, Default, serde::Deserialize)]
+pub enum SamplerType {
+    Independent,
+    Stratified,
+    Halton,
+    #[default]
+    Sobol,
+}
+
+impl SamplerType {
+    /// Creates a sampler of the type for pixels with the samples per pixel.
+    /// The same seed always gives the same values for a sample of a pixel,
+    /// no matter which order the samples are taken in.
+    pub fn create(self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        match self {
+            SamplerType::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerType::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel, seed)),
+            SamplerType::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerType::Sobol => Box::new(SobolSampler::new(seed)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that the first n samples of the first dimensions of every
+    /// sampler are in range, and that the samplers that aren't random
+    /// spread them out evenly.
+    #[test]
+    fn test_samplers() {
+        let n = 64;
+        for sampler_type in [
+            SamplerType::Independent,
+            SamplerType::Stratified,
+            SamplerType::Halton,
+            SamplerType::Sobol,
+        ] {
+            let mut sampler = sampler_type.create(n, 0);
+            let mut strata = vec![0; n as usize];
+            let mut cells = vec![0; n as usize];
+
+            for i in 0..n {
+                sampler.start_sample((3, 7), i);
+                let x = sampler.get_1d();
+                let (y, z) = sampler.get_2d();
+
+                for v in [x, y, z] {
+                    assert!((0.0..1.0).contains(&v), "{:?} {}", sampler_type, v);
+                }
+                strata[(x * n as f32) as usize] += 1;
+                cells[(y * 8.0) as usize + 8 * (z * 8.0) as usize] += 1;
+            }
+
+            // one sample in each of the n strata of the first dimension
+            if sampler_type != SamplerType::Independent {
+                assert!(strata.iter().all(|s| *s == 1), "{:?}", sampler_type);
+            }
+
+            // and one in each cell of an 8x8 grid of the next two
+            if sampler_type == SamplerType::Stratified || sampler_type == SamplerType::Sobol {
+                assert!(cells.iter().all(|c| *c == 1), "{:?}", sampler_type);
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! A small, fast and seedable random number generator, so that every
+//! sample of every pixel can have its own reproducible stream of random
+//! numbers.
+
+/// The PCG32 generator (O'Neill 2014, "PCG: A Family of Simple Fast
+/// Space-Efficient Statistically Good Algorithms for Random Number
+/// Generation"). Generators with different streams give independent
+/// sequences for the same seed.
+#[derive(Clone, Debug)]
+pub struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+impl Pcg32 {
+    const MULTIPLIER: u64 = 0x5851_f42d_4c95_7f2d;
+
+    /// Creates a generator with the seed, on one of 2^63 streams.
+    pub fn new(seed: u64, stream: u64) -> Pcg32 {
+        let mut rng = Pcg32 {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    /// Returns the next 32 random bits.
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(Pcg32::MULTIPLIER).wrapping_add(self.inc);
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    /// Returns a uniformly distributed float in [0, 1).
+    pub fn uniform(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pcg32() {
+        // the reference output of the pcg32 demo for seed 42, stream 54
+        let mut rng = Pcg32::new(42, 54);
+        assert_eq!(rng.next_u32(), 0xa15c_02b7);
+        assert_eq!(rng.next_u32(), 0x7b47_f409);
+        assert_eq!(rng.next_u32(), 0xba1d_3330);
+
+        let mut a = Pcg32::new(7, 1);
+        let mut b = Pcg32::new(7, 2);
+        assert_ne!(a.next_u32(), b.next_u32());
+
+        for _ in 0..1000 {
+            assert!((0.0..1.0).contains(&a.uniform()));
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use super::hash::{hash, owen_scramble};
+use super::Sampler;
+
+/// The scale from a 32 bit value to [0, 1). Only the top 24 bits fit into
+/// an f32, so the rest are dropped to stay below 1.
+const TO_FLOAT: f32 = 1.0 / (1u64 << 24) as f32;
+
+/// The Sobol sequence with Owen scrambling, padded to any number of
+/// dimensions. Each dimension, or pair of dimensions for `get_2d()`, is
+/// the first two dimensions of the Sobol sequence, which are well
+/// distributed together, with the order of the samples shuffled and the
+/// values scrambled by a hash of the pixel and dimension. From Burley 2020,
+/// "Practical Hash-based Owen Scrambling".
+pub struct SobolSampler {
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+    seed: u64,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> SobolSampler {
+        SobolSampler {
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            seed,
+        }
+    }
+
+    /// The seed of the next dimension, and the sample index shuffled by it.
+    fn next_dimension(&mut self) -> (u32, u32) {
+        let seed = hash(&[
+            self.seed,
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            self.dimension as u64,
+        ]);
+        self.dimension += 1;
+
+        let seed = (seed ^ (seed >> 32)) as u32;
+        (seed, owen_scramble(self.index, seed))
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let (seed, index) = self.next_dimension();
+        let x = owen_scramble(sobol_first(index), seed.wrapping_mul(0x9e37_79b9));
+
+        (x >> 8) as f32 * TO_FLOAT
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let (seed, index) = self.next_dimension();
+        let x = owen_scramble(sobol_first(index), seed.wrapping_mul(0x9e37_79b9));
+        let y = owen_scramble(sobol_second(index), seed.wrapping_mul(0x85eb_ca6b));
+
+        ((x >> 8) as f32 * TO_FLOAT, (y >> 8) as f32 * TO_FLOAT)
+    }
+}
+
+/// The first dimension of the Sobol sequence, which is the base 2 radical
+/// inverse, as 32 bits of a fraction.
+fn sobol_first(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+/// The second dimension of the Sobol sequence, whose direction numbers
+/// follow from the primitive polynomial x + 1.
+fn sobol_second(mut index: u32) -> u32 {
+    let mut direction = 1u32 << 31;
+    let mut x = 0;
+
+    while index > 0 {
+        if index & 1 == 1 {
+            x ^= direction;
+        }
+        direction ^= direction >> 1;
+        index >>= 1;
+    }
+
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sobol() {
+        // the unscrambled sequence starts 0, 1/2, 1/4 and 3/4 in the first
+        // dimension and 0, 1/2, 3/4, 1/4 in the second
+        let first: Vec<u32> = (0..4).map(|i| sobol_first(i) >> 30).collect();
+        let second: Vec<u32> = (0..4).map(|i| sobol_second(i) >> 30).collect();
+        assert_eq!(first, vec![0, 2, 1, 3]);
+        assert_eq!(second, vec![0, 2, 3, 1]);
+
+        // every 2x2 quadrant gets one of the first four points of a pixel
+        let mut sampler = SobolSampler::new(0);
+        let mut quadrants = [0; 4];
+        for i in 0..4 {
+            sampler.start_sample((5, 9), i);
+            let (x, y) = sampler.get_2d();
+            quadrants[(x * 2.0) as usize + 2 * (y * 2.0) as usize] += 1;
+        }
+        assert_eq!(quadrants, [1, 1, 1, 1]);
+    }
+}
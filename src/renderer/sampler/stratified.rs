@@ -0,0 +1,84 @@
+use super::hash::{hash, permutation_element};
+use super::independent::sample_rng;
+use super::rng::Pcg32;
+use super::{Sampler, ONE_MINUS_EPSILON};
+
+/// Jittered stratified sampling. Every dimension is split into as many
+/// strata as there are samples per pixel, or a grid of about that many
+/// cells for two dimensions, and each sample is jittered inside of its own
+/// stratum. The strata are shuffled for each pixel and dimension, so that
+/// the dimensions aren't correlated with each other.
+///
+/// Samples past the samples per pixel start another round of the strata
+/// with a new shuffle.
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    grid: (u32, u32),
+    pixel: (u32, u32),
+    index: u32,
+    dimension: u32,
+    seed: u64,
+    rng: Pcg32,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: u32, seed: u64) -> StratifiedSampler {
+        let samples_per_pixel = samples_per_pixel.max(1);
+        let x = (samples_per_pixel as f32).sqrt().floor().max(1.0) as u32;
+
+        StratifiedSampler {
+            samples_per_pixel,
+            grid: (x, samples_per_pixel / x),
+            pixel: (0, 0),
+            index: 0,
+            dimension: 0,
+            seed,
+            rng: Pcg32::new(seed, 0),
+        }
+    }
+
+    /// Returns the stratum of the current sample out of n.
+    fn stratum(&mut self, n: u32) -> u32 {
+        let seed = hash(&[
+            self.seed,
+            self.pixel.0 as u64,
+            self.pixel.1 as u64,
+            self.dimension as u64,
+            (self.index / n) as u64,
+        ]);
+        self.dimension += 1;
+
+        permutation_element(self.index % n, n, seed as u32)
+    }
+
+    fn jitter(&mut self) -> f32 {
+        self.rng.uniform()
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, pixel: (u32, u32), index: u32) {
+        self.pixel = pixel;
+        self.index = index;
+        self.dimension = 0;
+        self.rng = sample_rng(self.seed, pixel, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let n = self.samples_per_pixel;
+        let stratum = self.stratum(n);
+
+        ((stratum as f32 + self.jitter()) / n as f32).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let (nx, ny) = self.grid;
+        let stratum = self.stratum(nx * ny);
+        let (x, y) = (stratum % nx, stratum / nx);
+
+        (
+            ((x as f32 + self.jitter()) / nx as f32).min(ONE_MINUS_EPSILON),
+            ((y as f32 + self.jitter()) / ny as f32).min(ONE_MINUS_EPSILON),
+        )
+    }
+}
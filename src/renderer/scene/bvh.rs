@@ -0,0 +1,439 @@
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector::Point3;
+use crate::renderer::scene::hittable::HitRecord;
+
+/// The number of buckets the centroids are binned into when evaluating
+/// the surface area heuristic.
+const BIN_COUNT: usize = 12;
+
+/// Nodes with this many primitives or fewer can become leaves when the
+/// heuristic says it is cheaper than splitting them.
+const MAX_LEAF_SIZE: usize = 4;
+
+/// The cost of traversing a node relative to intersecting a primitive.
+const TRAVERSAL_COST: f32 = 0.125;
+
+/// Past this depth, nodes are split at the median instead of with the
+/// heuristic. This bounds the depth of the tree to fit the traversal stack.
+const SAH_MAX_DEPTH: usize = 32;
+
+/// The size of the traversal stack.
+const STACK_SIZE: usize = 64;
+
+/// A node in the flattened tree. The first child of an interior node
+/// always directly follows it in the node list.
+#[derive(Copy, Clone, Debug)]
+struct BvhNode {
+    bounds: Aabb,
+    /// The first entry in the index list for a leaf, or the index of the
+    /// second child for an interior node.
+    offset: usize,
+    /// The number of primitives in a leaf. Zero for interior nodes.
+    count: usize,
+    /// The axis an interior node was split along.
+    axis: usize,
+}
+
+/// The per-primitive information needed while building the tree.
+struct BuildItem {
+    index: usize,
+    bounds: Aabb,
+    centroid: Point3,
+}
+
+/// A bounding volume hierarchy built with the surface area heuristic.
+///
+/// The hierarchy doesn't own the primitives, it only stores indices into
+/// the list of bounds it was built from. This allows it to be used for
+/// both the objects in a region and the triangles in a mesh. The caller
+/// supplies the primitive intersection in `hit()`.
+#[derive(Default, Debug)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    /// Builds the hierarchy over the bounding boxes supplied. The indices
+    /// passed back to the intersection callback are the positions in this
+    /// slice.
+    pub fn new(bounds: &[Aabb]) -> Bvh {
+        let mut items: Vec<BuildItem> = bounds
+            .iter()
+            .enumerate()
+            .map(|(index, b)| BuildItem {
+                index,
+                bounds: *b,
+                centroid: b.centroid(),
+            })
+            .collect();
+
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * items.len()),
+            indices: Vec::with_capacity(items.len()),
+        };
+
+        if !items.is_empty() {
+            bvh.build(&mut items, 0);
+        }
+
+        bvh
+    }
+
+    /// Returns whether the hierarchy contains no primitives.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Returns the bounds of everything contained in the hierarchy.
+    pub fn bounds(&self) -> Aabb {
+        match self.nodes.first() {
+            Some(node) => node.bounds,
+            None => Aabb::empty(),
+        }
+    }
+
+    /// Finds the closest hit along the ray by walking the tree front to back.
+    ///
+    /// Params:
+    /// * `r` - The ray to intersect with.
+    /// * `t_min` - The minimum t-value along the ray.
+    /// * `t_max` - The maximum t-value along the ray.
+    /// * `hit_primitive` - Called with the primitive index and the current
+    ///   t range for each primitive in the leaves the ray passes through.
+    pub fn hit<'a, F>(
+        &self,
+        r: &Ray,
+        t_min: f32,
+        t_max: f32,
+        mut hit_primitive: F,
+    ) -> Option<HitRecord<'a>>
+    where
+        F: FnMut(usize, f32, f32) -> Option<HitRecord<'a>>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let dir_is_neg = [r.invdir.x < 0.0, r.invdir.y < 0.0, r.invdir.z < 0.0];
+
+        let mut closest_so_far = t_max;
+        let mut rec: Option<HitRecord> = None;
+
+        let mut stack = [0_usize; STACK_SIZE];
+        let mut stack_size = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+
+            if node.bounds.hit(r, closest_so_far) {
+                if node.count > 0 {
+                    for &index in &self.indices[node.offset..node.offset + node.count] {
+                        if let Some(hit) = hit_primitive(index, t_min, closest_so_far) {
+                            closest_so_far = hit.t;
+                            rec = Some(hit);
+                        }
+                    }
+                } else {
+                    // visit the child closest to the ray origin first so the
+                    // far child can be culled by the closer hits.
+                    if dir_is_neg[node.axis] {
+                        stack[stack_size] = current + 1;
+                        current = node.offset;
+                    } else {
+                        stack[stack_size] = node.offset;
+                        current += 1;
+                    }
+                    stack_size += 1;
+                    continue;
+                }
+            }
+
+            if stack_size == 0 {
+                break;
+            }
+            stack_size -= 1;
+            current = stack[stack_size];
+        }
+
+        rec
+    }
+
+    /// Recursively builds the node for the items and returns its index.
+    fn build(&mut self, items: &mut [BuildItem], depth: usize) -> usize {
+        let node_index = self.nodes.len();
+
+        let mut bounds = Aabb::empty();
+        let mut centroid_bounds = Aabb::empty();
+        for item in items.iter() {
+            bounds.expand(item.bounds);
+            centroid_bounds.expand_point(item.centroid);
+        }
+
+        self.nodes.push(BvhNode {
+            bounds,
+            offset: 0,
+            count: 0,
+            axis: 0,
+        });
+
+        if items.len() == 1 {
+            self.make_leaf(node_index, items);
+            return node_index;
+        }
+
+        let axis = centroid_bounds.longest_axis();
+        let extent = centroid_bounds.box_max[axis] - centroid_bounds.box_min[axis];
+
+        let mid = if extent.is_nan() || extent <= 0.0 {
+            // all of the centroids are in the same spot, there's no good
+            // way to split them, so split them in half if needed.
+            if items.len() <= MAX_LEAF_SIZE {
+                self.make_leaf(node_index, items);
+                return node_index;
+            }
+            items.len() / 2
+        } else if depth >= SAH_MAX_DEPTH {
+            median_split(items, axis)
+        } else {
+            match sah_split(items, &bounds, &centroid_bounds, axis) {
+                Split::Leaf => {
+                    self.make_leaf(node_index, items);
+                    return node_index;
+                }
+                Split::At(mid) => mid,
+                Split::Median => median_split(items, axis),
+            }
+        };
+
+        let (left, right) = items.split_at_mut(mid);
+        self.build(left, depth + 1);
+        let second_child = self.build(right, depth + 1);
+
+        let node = &mut self.nodes[node_index];
+        node.offset = second_child;
+        node.axis = axis;
+
+        node_index
+    }
+
+    fn make_leaf(&mut self, node_index: usize, items: &[BuildItem]) {
+        let node = &mut self.nodes[node_index];
+        node.offset = self.indices.len();
+        node.count = items.len();
+        self.indices.extend(items.iter().map(|item| item.index));
+    }
+}
+
+/// The result of evaluating the surface area heuristic for a node.
+enum Split {
+    /// It's cheaper to intersect all of the primitives than to split.
+    Leaf,
+    /// Split the items at the index. The items have been partitioned.
+    At(usize),
+    /// The heuristic couldn't find a split, fall back to the median.
+    Median,
+}
+
+/// Bins the centroids along the axis and picks the bin boundary with the
+/// lowest expected intersection cost. Partitions the items around it.
+fn sah_split(items: &mut [BuildItem], bounds: &Aabb, centroid_bounds: &Aabb, axis: usize) -> Split {
+    let min = centroid_bounds.box_min[axis];
+    let extent = centroid_bounds.box_max[axis] - min;
+    let bin_of = |p: &Point3| -> usize {
+        let b = ((p[axis] - min) / extent * BIN_COUNT as f32) as usize;
+        b.min(BIN_COUNT - 1)
+    };
+
+    let mut bin_bounds = [Aabb::empty(); BIN_COUNT];
+    let mut bin_counts = [0_usize; BIN_COUNT];
+    for item in items.iter() {
+        let b = bin_of(&item.centroid);
+        bin_bounds[b].expand(item.bounds);
+        bin_counts[b] += 1;
+    }
+
+    // sweep from the right to get the area and count of everything past
+    // each split plane.
+    let mut right_area = [0.0_f32; BIN_COUNT - 1];
+    let mut right_count = [0_usize; BIN_COUNT - 1];
+    let mut acc = Aabb::empty();
+    let mut count = 0;
+    for i in (1..BIN_COUNT).rev() {
+        acc.expand(bin_bounds[i]);
+        count += bin_counts[i];
+        right_area[i - 1] = acc.surface_area();
+        right_count[i - 1] = count;
+    }
+
+    // then sweep from the left to evaluate the cost of each split.
+    let inv_area = 1.0 / bounds.surface_area();
+    let mut best_cost = f32::INFINITY;
+    let mut best_bin = None;
+    let mut acc = Aabb::empty();
+    let mut count = 0;
+    for i in 0..BIN_COUNT - 1 {
+        acc.expand(bin_bounds[i]);
+        count += bin_counts[i];
+
+        if count == 0 || right_count[i] == 0 {
+            continue;
+        }
+
+        let cost = TRAVERSAL_COST
+            + (count as f32 * acc.surface_area() + right_count[i] as f32 * right_area[i])
+                * inv_area;
+        if cost < best_cost {
+            best_cost = cost;
+            best_bin = Some(i);
+        }
+    }
+
+    let best_bin = match best_bin {
+        Some(b) => b,
+        None => return Split::Median,
+    };
+
+    if items.len() <= MAX_LEAF_SIZE && items.len() as f32 <= best_cost {
+        return Split::Leaf;
+    }
+
+    Split::At(partition(items, |item| bin_of(&item.centroid) <= best_bin))
+}
+
+/// Partitions the items around the median centroid along the axis.
+fn median_split(items: &mut [BuildItem], axis: usize) -> usize {
+    let mid = items.len() / 2;
+    items.select_nth_unstable_by(mid, |a, b| {
+        a.centroid[axis]
+            .partial_cmp(&b.centroid[axis])
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    mid
+}
+
+/// Moves the items matching the predicate to the front of the slice and
+/// returns the number of them.
+fn partition<F>(items: &mut [BuildItem], pred: F) -> usize
+where
+    F: Fn(&BuildItem) -> bool,
+{
+    let mut first = 0;
+    for i in 0..items.len() {
+        if pred(&items[i]) {
+            items.swap(first, i);
+            first += 1;
+        }
+    }
+    first
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color::Color;
+    use crate::renderer::core::vector::Vec3;
+    use crate::renderer::scene::hittable::Hittable;
+    use crate::renderer::scene::materials::Material;
+    use crate::renderer::scene::objects::sphere::Sphere;
+    use crate::renderer::scene::world::Region;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_region(rng: &mut StdRng, count: usize) -> Region {
+        let mut world = Region::new(Color::new(0.5, 0.7, 1.0));
+        for _ in 0..count {
+            world.push(Box::new(Sphere {
+                center: Point3::new(
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                ),
+                radius: rng.gen_range(0.05..0.5),
+                material: Material::Lambert {
+                    albedo: Color::new(0.5, 0.5, 0.5).into(),
+                },
+            }));
+        }
+        world
+    }
+
+    fn linear_hit<'a>(world: &'a Region, r: &Ray) -> Option<HitRecord<'a>> {
+        let mut closest_so_far = f32::INFINITY;
+        let mut rec = None;
+        for obj in world.objects.iter() {
+            if let Some(hit) = obj.hit(r, 0.001, closest_so_far) {
+                closest_so_far = hit.t;
+                rec = Some(hit);
+            }
+        }
+        rec
+    }
+
+    #[test]
+    fn test_bvh_build() {
+        let bounds = vec![
+            Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0)),
+            Aabb::new(Point3::new(5.0, 0.0, 0.0), Point3::new(6.0, 1.0, 1.0)),
+            Aabb::new(Point3::new(-4.0, -1.0, 0.0), Point3::new(-3.0, 1.0, 2.0)),
+        ];
+
+        let bvh = Bvh::new(&bounds);
+        assert!(!bvh.is_empty());
+
+        let b = bvh.bounds();
+        assert_eq!(b.box_min.x, -4.0);
+        assert_eq!(b.box_min.y, -1.0);
+        assert_eq!(b.box_min.z, 0.0);
+        assert_eq!(b.box_max.x, 6.0);
+        assert_eq!(b.box_max.y, 1.0);
+        assert_eq!(b.box_max.z, 2.0);
+
+        // every primitive has to be referenced exactly once
+        let mut indices = bvh.indices.clone();
+        indices.sort_unstable();
+        assert_eq!(indices, vec![0, 1, 2]);
+
+        assert!(Bvh::new(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_bvh_matches_linear_scan() {
+        let mut rng = StdRng::seed_from_u64(17);
+        let mut world = random_region(&mut rng, 2000);
+        world.recalculate_bounds();
+
+        for _ in 0..2000 {
+            let orig = Point3::new(
+                rng.gen_range(-15.0..15.0),
+                rng.gen_range(-15.0..15.0),
+                rng.gen_range(-15.0..15.0),
+            );
+            let dir = Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            );
+            let r = Ray::new(orig, dir);
+
+            let expected = linear_hit(&world, &r);
+            let actual = world.hit(&r, 0.001, f32::INFINITY);
+
+            match (expected, actual) {
+                (None, None) => {}
+                (Some(e), Some(a)) => {
+                    assert_eq!(e.t, a.t);
+                    assert_eq!(e.p.x, a.p.x);
+                    assert_eq!(e.p.y, a.p.y);
+                    assert_eq!(e.p.z, a.p.z);
+                    assert_eq!(e.normal.x, a.normal.x);
+                    assert_eq!(e.normal.y, a.normal.y);
+                    assert_eq!(e.normal.z, a.normal.z);
+                }
+                _ => panic!("the bvh and the linear scan disagree"),
+            }
+        }
+    }
+}
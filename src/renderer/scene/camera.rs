@@ -4,10 +4,15 @@ use serde::{Deserialize, Serialize};
 
 use crate::renderer::core::ray::Ray;
 use crate::renderer::core::vector::{self, Point3, Vec3};
+use crate::renderer::sampler::Sampler;
 
 /// The configurable values for the scene camera
 /// pulled out as a separate object for ease of
 /// serialization to a source file.
+///
+/// The shutter is open from `shutter_open` to `shutter_close`, and each
+/// ray is traced at a random time in between, which blurs the objects
+/// that move in that time. Both are 0 by default, for a still image.
 #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct CameraConfig {
     pub look_from: Vec3,
@@ -16,6 +21,10 @@ pub struct CameraConfig {
     pub vertical_fov: f32,
     pub aperture: f32,
     pub focal_distance: f32,
+    #[serde(default)]
+    pub shutter_open: f32,
+    #[serde(default)]
+    pub shutter_close: f32,
 }
 
 /// A simple camera and film simulation. It's responsible
@@ -31,6 +40,7 @@ pub struct Camera {
     pub film_height: u32,
     lens_radius: f32,
     normal_basis: (Vec3, Vec3),
+    shutter: (f32, f32),
 }
 
 impl Camera {
@@ -79,17 +89,30 @@ impl Camera {
             film_height: bounds.1,
             lens_radius: config.aperture * 0.5,
             normal_basis: (u, v),
+            shutter: (config.shutter_open, config.shutter_close),
         }
     }
 
     /// Returns a ray using the uv coordinates of the point on the film plane.
-    pub fn get_ray(&self, u: f32, v: f32, fuzz: f32) -> Ray {
-        let rd = self.lens_radius * fuzz * vector::random_in_unit_disk();
+    /// The point on the lens and the time the shutter is open are drawn
+    /// from the sampler.
+    pub fn get_ray(&self, u: f32, v: f32, fuzz: f32, sampler: &mut dyn Sampler) -> Ray {
+        let rd = self.lens_radius * fuzz * vector::random_in_unit_disk(sampler);
         let offset = self.normal_basis.0 * rd.x + self.normal_basis.1 * rd.y;
 
-        Ray::new(
+        // a still image doesn't use up a dimension of the sampler, so it
+        // renders the same as before there was a shutter
+        let (open, close) = self.shutter;
+        let time = if close > open {
+            open + sampler.get_1d() * (close - open)
+        } else {
+            open
+        };
+
+        Ray::with_time(
             self.origin + offset,
             self.ll_corner + u * self.horizontal + v * self.vertical - self.origin - offset,
+            time,
         )
     }
 }
@@ -97,6 +120,7 @@ impl Camera {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::renderer::sampler::IndependentSampler;
 
     #[test]
     fn test_camera() {
@@ -107,12 +131,14 @@ mod tests {
                 up: Vec3::new(0.0, 1.0, 0.0),
                 aperture: 2.0,
                 focal_distance: 1.0,
+                shutter_open: 0.0,
+                shutter_close: 0.0,
                 vertical_fov: 90.0,
             },
             (100, 100),
         );
 
-        let r = c.get_ray(0.0, 0.0, 0.0);
+        let r = c.get_ray(0.0, 0.0, 0.0, &mut IndependentSampler::new(0));
 
         let p = r.orig;
         assert_eq!(p.x, 0.0);
@@ -124,7 +150,7 @@ mod tests {
         assert_eq!(d.y, -1.0);
         assert_eq!(d.z, -1.0);
 
-        let r = c.get_ray(1.0, 1.0, 0.0);
+        let r = c.get_ray(1.0, 1.0, 0.0, &mut IndependentSampler::new(0));
 
         let p = r.orig;
         assert_eq!(p.x, 0.0);
@@ -135,5 +161,45 @@ mod tests {
         assert_eq!(d.x, 1.0);
         assert_eq!(d.y, 1.0);
         assert_eq!(d.z, -1.0);
+        assert_eq!(r.time, 0.0);
+    }
+
+    #[test]
+    fn test_camera_shutter() {
+        let c = Camera::new(
+            CameraConfig {
+                look_from: Vec3::new(0.0, 0.0, 0.0),
+                look_at: Vec3::new(0.0, 0.0, -1.0),
+                up: Vec3::new(0.0, 1.0, 0.0),
+                aperture: 0.0,
+                focal_distance: 1.0,
+                vertical_fov: 90.0,
+                shutter_open: 0.25,
+                shutter_close: 0.75,
+            },
+            (100, 100),
+        );
+
+        let mut sampler = IndependentSampler::new(0);
+        let times: Vec<f32> = (0..1000)
+            .map(|_| c.get_ray(0.5, 0.5, 1.0, &mut sampler).time)
+            .collect();
+        assert!(times.iter().all(|t| (0.25..=0.75).contains(t)));
+        let mean = times.iter().sum::<f32>() / times.len() as f32;
+        assert!((mean - 0.5).abs() < 0.02);
+
+        let config: CameraConfig = serde_json::from_str(
+            r#"{
+                "look_from": {"x": 0.0, "y": 0.0, "z": 0.0},
+                "look_at": {"x": 0.0, "y": 0.0, "z": -1.0},
+                "up": {"x": 0.0, "y": 1.0, "z": 0.0},
+                "vertical_fov": 90.0,
+                "aperture": 0.0,
+                "focal_distance": 1.0
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.shutter_open, 0.0);
+        assert_eq!(config.shutter_close, 0.0);
     }
 }
@@ -2,6 +2,7 @@ use crate::renderer::core::aabb::Aabb;
 use crate::renderer::core::ray::Ray;
 use crate::renderer::core::vector;
 use crate::renderer::core::vector::{Point3, Vec3};
+use crate::renderer::sampler::Sampler;
 use crate::renderer::scene::materials::Material;
 
 /// A data structure keeping track of the important
@@ -11,16 +12,22 @@ use crate::renderer::scene::materials::Material;
 /// Any new scene object must populate this data structure.
 /// Fields:
 /// * `p` - The point of intersection.
-/// * `normal` - The surface normal at the intersection.
+/// * `normal` - The shading normal at the intersection, on the side of
+///   the surface the ray came from.
+/// * `geometric_normal` - The normal of the actual surface, on the same
+///   side. Differs from the shading normal for smooth shaded meshes.
 /// * `t` - The t along the ray. Used by the algorithm to
-/// ensure objects are sorted in z.
+///   ensure objects are sorted in z.
 /// * `front_face` - Whether the hit was on the front face
-/// or the back face of a surface.
+///   or the back face of a surface.
+/// * `uv` - The surface texture coordinates at the intersection.
 pub struct HitRecord<'a> {
     pub p: Point3,
     pub normal: Vec3,
+    pub geometric_normal: Vec3,
     pub t: f32,
     pub front_face: bool,
+    pub uv: (f32, f32),
     pub material: &'a Material,
 }
 
@@ -35,6 +42,7 @@ impl HitRecord<'_> {
         } else {
             self.normal = -(*outward_normal);
         }
+        self.geometric_normal = self.normal;
     }
 
     /// Resets the record. It's important to reset this information between
@@ -42,15 +50,35 @@ impl HitRecord<'_> {
     pub fn reset(&mut self) {
         self.p = Point3::new(0.0, 0.0, 0.0);
         self.normal = Vec3::new(0.0, 0.0, 0.0);
+        self.geometric_normal = Vec3::new(0.0, 0.0, 0.0);
         self.t = f32::INFINITY;
         self.front_face = false;
+        self.uv = (0.0, 0.0);
     }
 }
 
+/// How far past a hit the next hit is looked for when walking along a ray
+/// through a surface, relative to the t of the hit.
+const INTERVAL_EPSILON: f32 = 1e-4;
+
+/// A stretch of a ray inside of a solid object, from where the ray enters
+/// the object to where it leaves it. The hits are on the surface of the
+/// solid, with `front_face` set when the ray enters.
+///
+/// Fields:
+/// * `enter` - Where the ray enters, or None if it's already inside at
+///   the start of the query.
+/// * `exit` - Where the ray leaves, or None if it's still inside at the
+///   end of the query.
+pub struct Interval<'a> {
+    pub enter: Option<HitRecord<'a>>,
+    pub exit: Option<HitRecord<'a>>,
+}
+
 /// The base trait for all renderable object types in the scene. Any new
 /// scene object should implement this trait.
 #[typetag::serde(tag = "type")]
-pub trait Hittable: Sync {
+pub trait Hittable: Send + Sync {
     /// The meat of the ray tracing algorithm for each object. The object
     /// must implement this function to calculate where the ray intersects
     /// it and return back the correct information in the output rec parameter.
@@ -61,8 +89,81 @@ pub trait Hittable: Sync {
     /// prevent calculating hits for internal reflections due to floating point inaccuracy.
     /// * `t_max` - The maximum t value along the ray. Used to avoid drawing objects that are
     /// further away over closer ones.
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord>;
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>>;
 
     /// Returns the axis aligned bounding box of this object.
     fn bounds(&self) -> Aabb;
+
+    /// Returns every stretch of the ray between t_min and t_max that's
+    /// inside of the object, in order, for combining solids. The default
+    /// walks along the ray from hit to hit, which works for any closed
+    /// surface: the ray enters through the front faces and leaves through
+    /// the back faces.
+    ///
+    /// Params:
+    /// * `r` - The ray (origin, direction) to find the intervals along.
+    /// * `t_min` - Where along the ray the query starts.
+    /// * `t_max` - Where along the ray the query ends.
+    fn intervals(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<Interval<'_>> {
+        let mut intervals = Vec::new();
+
+        // the hit the ray entered through while it's inside
+        let mut inside: Option<Option<HitRecord<'_>>> = None;
+        let mut first = true;
+        let mut t = t_min;
+        while let Some(rec) = self.hit(r, t, t_max) {
+            t = rec.t + INTERVAL_EPSILON * rec.t.abs().max(1.0);
+            if rec.front_face {
+                // a second entry in a row is a crack in the surface
+                if inside.is_none() {
+                    inside = Some(Some(rec));
+                }
+            } else if let Some(enter) = inside.take() {
+                intervals.push(Interval {
+                    enter,
+                    exit: Some(rec),
+                });
+            } else if first {
+                // leaving first, so the ray started inside
+                intervals.push(Interval {
+                    enter: None,
+                    exit: Some(rec),
+                });
+            }
+            first = false;
+        }
+
+        if let Some(enter) = inside {
+            intervals.push(Interval { enter, exit: None });
+        }
+        intervals
+    }
+
+    /// The materials of the object and of any objects inside of it. The
+    /// region gives them their ids once, before rendering, instead of for
+    /// every sample of the material id output.
+    fn materials(&self) -> Vec<&Material> {
+        Vec::new()
+    }
+
+    /// Whether the object is a light source that should be sampled
+    /// directly. Objects that return true must implement `random_direction()`
+    /// and `pdf_value()`.
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    /// Returns a random direction from the origin toward a point on the
+    /// surface of the object, or None if the object can't be sampled from
+    /// the origin. The direction doesn't need to be normalized.
+    fn random_direction(&self, _origin: &Point3, _sampler: &mut dyn Sampler) -> Option<Vec3> {
+        None
+    }
+
+    /// Returns the probability density, with respect to solid angle, of
+    /// `random_direction()` returning the direction from the origin. This
+    /// is 0 for directions that miss the object.
+    fn pdf_value(&self, _origin: &Point3, _dir: &Vec3) -> f32 {
+        0.0
+    }
 }
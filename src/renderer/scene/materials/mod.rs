@@ -1,8 +1,11 @@
 use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector::Vec3;
 use crate::renderer::core::{color, color::Color, min, vector};
+use crate::renderer::sampler::Sampler;
 use crate::renderer::scene::hittable::HitRecord;
-use rand::Rng;
+use crate::renderer::scene::textures::Texture;
 use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
 
 /// Since this is likely to be a closed set of materials,
 /// it's easier to implement this as part of an enum rather
@@ -12,34 +15,63 @@ use serde::{Deserialize, Serialize};
 /// model for dielectrics and lamberts because the
 /// size is the max for the metal. This is not likely to
 /// be an issue with the current implementation.
+///
+/// The albedo and fuzz can be textures. In the scene file, a plain color
+/// or number is a constant texture.
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum Material {
-    Lambert { albedo: Color },
-    Metal { albedo: Color, fuzz: f32 },
-    Dielectric { ior: f32 },
+    Lambert {
+        albedo: Texture,
+    },
+    Metal {
+        albedo: Texture,
+        fuzz: Texture,
+    },
+    Dielectric {
+        ior: f32,
+    },
+    /// A light source. It emits `color` scaled by `intensity` from both
+    /// sides of the surface and doesn't scatter any light.
+    Emissive {
+        color: Color,
+        intensity: f32,
+    },
 }
 
 impl Material {
-    /// Scatter the light according to the material properties.
-    pub fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+    /// Scatter the light according to the material properties, with the
+    /// random choices drawn from the sampler.
+    pub fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Ray, Color)> {
         match self {
             Material::Lambert { albedo } => {
-                let mut scatter_dir = rec.normal + vector::random_unit_vector();
+                let mut scatter_dir = rec.normal + vector::random_unit_vector(sampler);
 
                 if scatter_dir.near_zero() {
                     scatter_dir = rec.normal;
                 }
 
-                Some((Ray::new(rec.p, scatter_dir), *albedo))
+                Some((
+                    Ray::with_time(rec.p, scatter_dir, r_in.time),
+                    albedo.value(rec),
+                ))
             }
             Material::Metal { albedo, fuzz } => {
                 let reflected = vector::reflect(&r_in.dir, &rec.normal);
 
-                let scatter_dir = Ray::new(rec.p, reflected + *fuzz * vector::random_unit_vector());
+                let scatter_dir = Ray::with_time(
+                    rec.p,
+                    reflected + fuzz.scalar(rec) * vector::random_unit_vector(sampler),
+                    r_in.time,
+                );
 
                 if vector::dot(&scatter_dir.dir, &rec.normal) > 0.0 {
-                    return Some((scatter_dir, *albedo));
+                    return Some((scatter_dir, albedo.value(rec)));
                 }
                 None
             }
@@ -51,17 +83,102 @@ impl Material {
                 let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
                 let dir = if ref_ratio * sin_theta > 1.0
-                    || reflectance(cos_theta, ref_ratio) > rand::thread_rng().gen_range(0.0..1.0)
+                    || reflectance(cos_theta, ref_ratio) > sampler.get_1d()
                 {
                     vector::reflect(&unit_dir, &rec.normal)
                 } else {
                     vector::refract(&unit_dir, &rec.normal, ref_ratio)
                 };
 
-                Some((Ray::new(rec.p, dir), color::WHITE))
+                Some((Ray::with_time(rec.p, dir, r_in.time), color::WHITE))
             }
+            Material::Emissive { .. } => None,
         }
     }
+
+    /// Evaluates the scattering function times the cosine term for light
+    /// arriving from `dir` and leaving along the incoming ray. Only
+    /// meaningful for materials that aren't specular.
+    pub fn eval(&self, _r_in: &Ray, rec: &HitRecord, dir: &Vec3) -> Color {
+        match self {
+            Material::Lambert { albedo } => {
+                let cosine = vector::dot(&rec.normal, &vector::unit_vector(dir));
+                if cosine <= 0.0 {
+                    return color::BLACK;
+                }
+                (cosine / PI) * albedo.value(rec)
+            }
+            _ => color::BLACK,
+        }
+    }
+
+    /// The probability density, with respect to solid angle, of `scatter()`
+    /// choosing the direction. Only meaningful for materials that aren't
+    /// specular.
+    pub fn pdf(&self, _r_in: &Ray, rec: &HitRecord, dir: &Vec3) -> f32 {
+        match self {
+            Material::Lambert { .. } => {
+                let cosine = vector::dot(&rec.normal, &vector::unit_vector(dir));
+                if cosine <= 0.0 {
+                    return 0.0;
+                }
+                cosine / PI
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Whether the material scatters light into a single direction, or
+    /// close enough to it that it can't be evaluated for arbitrary
+    /// directions. These materials are skipped for light sampling. Fuzzy
+    /// metals are treated as specular.
+    pub fn is_specular(&self) -> bool {
+        matches!(self, Material::Metal { .. } | Material::Dielectric { .. })
+    }
+
+    /// Whether the material is a light source.
+    pub fn is_emissive(&self) -> bool {
+        matches!(self, Material::Emissive { .. })
+    }
+
+    /// The color of the surface at the hit point, regardless of the
+    /// lighting. Glass is white, and lights are the color of their light.
+    pub fn albedo(&self, rec: &HitRecord) -> Color {
+        match self {
+            Material::Lambert { albedo } => albedo.value(rec),
+            Material::Metal { albedo, .. } => albedo.value(rec),
+            Material::Dielectric { .. } => color::WHITE,
+            Material::Emissive { color, .. } => *color,
+        }
+    }
+
+    /// An id for the material, the same for all materials with the same
+    /// settings. The id is never 0 and fits into the mantissa of an f32, so
+    /// it can be stored in an image. It's a fixed hash of the settings, so
+    /// the ids stay the same from one build of the renderer to the next.
+    ///
+    /// The hash isn't cheap, `Region::material_id()` looks up the ids that
+    /// were worked out when the scene was loaded.
+    pub fn id(&self) -> u32 {
+        let settings = serde_json::to_string(self).unwrap_or_default();
+        ((fnv1a(settings.as_bytes()) & 0xff_ffff) as u32).max(1)
+    }
+
+    /// The radiance emitted by the material at the hit point. Black for
+    /// anything that isn't a light source.
+    pub fn emitted(&self, _rec: &HitRecord) -> Color {
+        match self {
+            Material::Emissive { color, intensity } => *intensity * *color,
+            _ => color::BLACK,
+        }
+    }
+}
+
+/// The 64 bit FNV-1a hash of the bytes.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
 }
 
 /// A utility function for calculating the reflectance property of the
@@ -73,3 +190,112 @@ fn reflectance(cos: f32, ref_idx: f32) -> f32 {
 
     r0 + (1.0 - r0) * (1.0 - cos).powi(5)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::vector::Point3;
+    use crate::renderer::sampler::IndependentSampler;
+
+    fn test_record(material: &Material) -> HitRecord<'_> {
+        HitRecord {
+            p: Point3::new(0.0, 0.0, -1.0),
+            normal: Vec3::new(0.0, 0.0, 1.0),
+            geometric_normal: Vec3::new(0.0, 0.0, 1.0),
+            t: 1.0,
+            front_face: true,
+            uv: (0.0, 0.0),
+            material,
+        }
+    }
+
+    #[test]
+    fn test_lambert_eval_pdf() {
+        let lambert = Material::Lambert {
+            albedo: Color::new(0.5, 0.5, 0.5).into(),
+        };
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = test_record(&lambert);
+        assert!(!lambert.is_specular());
+
+        // straight along the normal
+        let dir = Vec3::new(0.0, 0.0, 2.0);
+        assert!((lambert.pdf(&r, &rec, &dir) - 1.0 / PI).abs() < 1e-6);
+        assert!((lambert.eval(&r, &rec, &dir).x - 0.5 / PI).abs() < 1e-6);
+
+        // at 60 degrees the cosine halves both
+        let dir = Vec3::new(3.0_f32.sqrt(), 0.0, 1.0);
+        assert!((lambert.pdf(&r, &rec, &dir) - 0.5 / PI).abs() < 1e-6);
+        assert!((lambert.eval(&r, &rec, &dir).x - 0.25 / PI).abs() < 1e-6);
+
+        // nothing below the surface
+        let dir = Vec3::new(0.0, 1.0, -1.0);
+        assert_eq!(lambert.pdf(&r, &rec, &dir), 0.0);
+        assert_eq!(lambert.eval(&r, &rec, &dir).x, 0.0);
+
+        // the eval over the pdf has to match the scatter attenuation
+        let (scattered, attenuation) = lambert
+            .scatter(&r, &rec, &mut IndependentSampler::new(0))
+            .unwrap();
+        let weight = lambert.eval(&r, &rec, &scattered.dir) / lambert.pdf(&r, &rec, &scattered.dir);
+        assert!((weight.x - attenuation.x).abs() < 1e-4);
+
+        assert!(Material::Metal {
+            albedo: color::WHITE.into(),
+            fuzz: 0.1.into()
+        }
+        .is_specular());
+        assert!(Material::Dielectric { ior: 1.5 }.is_specular());
+    }
+
+    #[test]
+    fn test_emissive() {
+        let light = Material::Emissive {
+            color: Color::new(1.0, 0.5, 0.25),
+            intensity: 4.0,
+        };
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = test_record(&light);
+
+        let e = light.emitted(&rec);
+        assert_eq!(e.x, 4.0);
+        assert_eq!(e.y, 2.0);
+        assert_eq!(e.z, 1.0);
+
+        // lights don't scatter
+        assert!(light
+            .scatter(&r, &rec, &mut IndependentSampler::new(0))
+            .is_none());
+        assert!(light.is_emissive());
+
+        // and nothing else emits
+        let lambert = Material::Lambert {
+            albedo: color::WHITE.into(),
+        };
+        let e = lambert.emitted(&test_record(&lambert));
+        assert_eq!(e.x, 0.0);
+        assert_eq!(e.y, 0.0);
+        assert_eq!(e.z, 0.0);
+    }
+
+    #[test]
+    fn test_id() {
+        // the reference values of the hash
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+
+        let lambert = Material::Lambert {
+            albedo: Color::new(0.5, 0.5, 0.5).into(),
+        };
+        let same = Material::Lambert {
+            albedo: Color::new(0.5, 0.5, 0.5).into(),
+        };
+        let glass = Material::Dielectric { ior: 1.5 };
+        assert_eq!(lambert.id(), same.id());
+        assert_ne!(lambert.id(), glass.id());
+
+        // mattes keyed on the ids have to keep working with new builds
+        assert_eq!(lambert.id(), 10427763);
+        assert_eq!(glass.id(), 10761803);
+    }
+}
@@ -7,10 +7,12 @@
 //! or objects to have new materials, this module will need to be
 //! extended.
 
+pub mod bvh;
 pub mod camera;
 pub mod hittable;
 pub mod materials;
 pub mod objects;
+pub mod textures;
 pub mod world;
 
 // a utility for building a procedurally generated world
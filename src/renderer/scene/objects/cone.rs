@@ -0,0 +1,137 @@
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector::{Point3, Vec3};
+
+use crate::renderer::scene::hittable::{HitRecord, Hittable};
+use crate::renderer::scene::materials::Material;
+
+use super::quadric::{full_sweep, Cap, Frame, Revolution};
+
+use serde::{Deserialize, Serialize};
+
+/// A cone narrowing from its base up to a point, open at the base unless
+/// it's capped.
+///
+/// Fields:
+/// * `base` - The center of the base.
+/// * `axis` - The direction from the base to the tip.
+/// * `radius` - The radius of the base.
+/// * `height` - The distance from the base to the tip.
+/// * `phi_max` - How far around the axis the cone goes, in degrees. 360
+///   by default.
+/// * `capped` - Whether the base is closed with a disk.
+/// * `material` - The material of the surface.
+#[derive(Serialize, Deserialize)]
+pub struct Cone {
+    pub base: Point3,
+    pub axis: Vec3,
+    pub radius: f32,
+    pub height: f32,
+    #[serde(default = "full_sweep")]
+    pub phi_max: f32,
+    #[serde(default)]
+    pub capped: bool,
+    pub material: Material,
+}
+
+impl Cone {
+    /// The radius at z is radius * (height - z) / height.
+    fn revolution(&self) -> Revolution {
+        let k = self.radius * self.radius / (self.height * self.height);
+        let base_cap = Cap {
+            z: 0.0,
+            radius: self.radius,
+        };
+
+        Revolution {
+            a: k,
+            b: -2.0 * k * self.height,
+            c: self.radius * self.radius,
+            height: self.height,
+            phi_max: self.phi_max.to_radians(),
+            caps: [if self.capped { Some(base_cap) } else { None }, None],
+        }
+    }
+}
+
+#[typetag::serde]
+impl Hittable for Cone {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.revolution().hit(
+            &Frame::new(self.base, &self.axis),
+            r,
+            t_min,
+            t_max,
+            &self.material,
+        )
+    }
+
+    fn bounds(&self) -> Aabb {
+        Frame::new(self.base, &self.axis).bounds(
+            Point3::new(-self.radius, -self.radius, 0.0),
+            Point3::new(self.radius, self.radius, self.height),
+        )
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        vec![&self.material]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+
+    fn test_cone(capped: bool) -> Cone {
+        Cone {
+            base: Point3::new(0.0, -1.0, -5.0),
+            axis: Vec3::new(0.0, 1.0, 0.0),
+            radius: 1.0,
+            height: 2.0,
+            phi_max: 360.0,
+            capped,
+            material: Material::Lambert {
+                albedo: color::WHITE.into(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_cone_hit() {
+        let cone = test_cone(false);
+
+        // halfway up, the radius is halved
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = cone.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-5);
+        assert!(rec.front_face);
+        // the side leans back by atan(1 / 2)
+        let expected = Vec3::new(0.0, 1.0, 2.0) / 5.0_f32.sqrt();
+        assert!((rec.normal - expected).length() < 1e-5);
+
+        // the other nappe of the double cone above the tip isn't part of it
+        let r = Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(cone.hit(&r, 0.001, f32::INFINITY).is_none());
+
+        // up through the open base onto the inside
+        let r = Ray::new(Point3::new(0.5, -5.0, -5.0), Vec3::new(0.0, 1.0, 0.0));
+        let rec = cone.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.p.y - 0.0).abs() < 1e-5);
+        assert!(!rec.front_face);
+
+        let b = cone.bounds();
+        assert!((b.box_max.x - 1.0).abs() < 1e-5);
+        assert!((b.box_max.y - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_cone_cap() {
+        let cone = test_cone(true);
+        let r = Ray::new(Point3::new(0.5, -5.0, -5.0), Vec3::new(0.0, 1.0, 0.0));
+        let rec = cone.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-5);
+        assert!((rec.normal.y + 1.0).abs() < 1e-5);
+        assert!(rec.front_face);
+    }
+}
@@ -0,0 +1,459 @@
+//! Constructive solid geometry, combining two solid objects into one with
+//! a union, an intersection or a difference.
+//!
+//! The objects report every interval of a ray inside of them, and the
+//! intervals of both are merged along the ray by whether each point is
+//! inside of the combination. The surfaces keep the materials of the
+//! objects they come from, so the inside of a carved hole takes the
+//! material of the object that carved it.
+
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+
+use crate::renderer::scene::hittable::{HitRecord, Hittable, Interval};
+use crate::renderer::scene::materials::Material;
+
+use serde::{Deserialize, Serialize};
+
+/// The space inside of either of two objects.
+///
+/// Fields:
+/// * `left` - The first object.
+/// * `right` - The second object.
+#[derive(Serialize, Deserialize)]
+pub struct Union {
+    pub left: Box<dyn Hittable>,
+    pub right: Box<dyn Hittable>,
+}
+
+/// The space inside of both of two objects.
+///
+/// Fields:
+/// * `left` - The first object.
+/// * `right` - The second object.
+#[derive(Serialize, Deserialize)]
+pub struct Intersection {
+    pub left: Box<dyn Hittable>,
+    pub right: Box<dyn Hittable>,
+}
+
+/// The space inside of one object but outside of another, the first
+/// object with the second carved out of it.
+///
+/// Fields:
+/// * `left` - The object that's carved.
+/// * `right` - The object carved out of it.
+#[derive(Serialize, Deserialize)]
+pub struct Difference {
+    pub left: Box<dyn Hittable>,
+    pub right: Box<dyn Hittable>,
+}
+
+/// How the combination decides whether a point is inside of it.
+#[derive(Copy, Clone)]
+enum Operation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl Operation {
+    /// Whether a point inside of the left object or not, and inside of the
+    /// right object or not, is inside of the combination.
+    fn contains(self, left: bool, right: bool) -> bool {
+        match self {
+            Operation::Union => left || right,
+            Operation::Intersection => left && right,
+            Operation::Difference => left && !right,
+        }
+    }
+
+    /// Finds the intervals of the ray inside of the combination of the
+    /// objects. The objects are followed along the whole ray, because a
+    /// closed object the ray is inside of until past t_max would have no
+    /// hits to tell it's inside of it before t_max.
+    fn intervals<'a>(
+        self,
+        left: &'a dyn Hittable,
+        right: &'a dyn Hittable,
+        r: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Vec<Interval<'a>> {
+        let left_intervals = left.intervals(r, t_min, f32::INFINITY);
+
+        // nothing of the left object is left to carve or intersect
+        if left_intervals.is_empty() && !matches!(self, Operation::Union) {
+            return Vec::new();
+        }
+
+        let intervals = combine(
+            self,
+            left_intervals,
+            right.intervals(r, t_min, f32::INFINITY),
+        );
+        clip(intervals, t_max)
+    }
+
+    /// Returns the closest hit with the surface of the combination.
+    fn hit<'a>(
+        self,
+        left: &'a dyn Hittable,
+        right: &'a dyn Hittable,
+        r: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<HitRecord<'a>> {
+        // from inside of the combination, the first hit is on the way out
+        let first = self
+            .intervals(left, right, r, t_min, t_max)
+            .into_iter()
+            .next()?;
+        first.enter.or(first.exit)
+    }
+}
+
+/// Merges the intervals of the two objects along the ray into the
+/// intervals of their combination.
+///
+/// Walks through the hits of both objects in order, keeping track of
+/// whether the ray is inside of each one, and keeps the hits where that
+/// changes whether it's inside of the combination. A kept hit faces the
+/// ray either way, so only `front_face` has to change: a hit leaving the
+/// carving object of a difference enters the combination.
+fn combine<'a>(
+    operation: Operation,
+    left: Vec<Interval<'a>>,
+    right: Vec<Interval<'a>>,
+) -> Vec<Interval<'a>> {
+    let starts_inside = |intervals: &[Interval<'_>]| {
+        intervals
+            .first()
+            .is_some_and(|interval| interval.enter.is_none())
+    };
+    let mut in_left = starts_inside(&left);
+    let mut in_right = starts_inside(&right);
+
+    // the hits of both objects, with whether each is from the left one
+    let mut hits: Vec<(HitRecord<'a>, bool)> = Vec::new();
+    for (intervals, is_left) in [(left, true), (right, false)] {
+        for interval in intervals {
+            hits.extend(interval.enter.map(|rec| (rec, is_left)));
+            hits.extend(interval.exit.map(|rec| (rec, is_left)));
+        }
+    }
+    hits.sort_by(|a, b| a.0.t.total_cmp(&b.0.t));
+
+    let mut intervals = Vec::new();
+    let mut inside = if operation.contains(in_left, in_right) {
+        Some(None)
+    } else {
+        None
+    };
+    for (mut rec, is_left) in hits {
+        if is_left {
+            in_left = rec.front_face;
+        } else {
+            in_right = rec.front_face;
+        }
+
+        match (inside.take(), operation.contains(in_left, in_right)) {
+            (None, true) => {
+                rec.front_face = true;
+                inside = Some(Some(rec));
+            }
+            (Some(enter), false) => {
+                rec.front_face = false;
+                intervals.push(Interval {
+                    enter,
+                    exit: Some(rec),
+                });
+            }
+            (unchanged, _) => inside = unchanged,
+        }
+    }
+
+    if let Some(enter) = inside {
+        intervals.push(Interval { enter, exit: None });
+    }
+    intervals
+}
+
+/// Cuts the intervals off at t_max.
+fn clip(intervals: Vec<Interval<'_>>, t_max: f32) -> Vec<Interval<'_>> {
+    intervals
+        .into_iter()
+        .take_while(|interval| interval.enter.as_ref().is_none_or(|rec| rec.t <= t_max))
+        .map(|mut interval| {
+            if interval.exit.as_ref().is_some_and(|rec| rec.t > t_max) {
+                interval.exit = None;
+            }
+            interval
+        })
+        .collect()
+}
+
+#[typetag::serde]
+impl Hittable for Union {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        Operation::Union.hit(self.left.as_ref(), self.right.as_ref(), r, t_min, t_max)
+    }
+
+    fn bounds(&self) -> Aabb {
+        let mut b = self.left.bounds();
+        b.expand(self.right.bounds());
+        b
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        let mut materials = self.left.materials();
+        materials.extend(self.right.materials());
+        materials
+    }
+
+    fn intervals(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<Interval<'_>> {
+        Operation::Union.intervals(self.left.as_ref(), self.right.as_ref(), r, t_min, t_max)
+    }
+}
+
+#[typetag::serde]
+impl Hittable for Intersection {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        Operation::Intersection.hit(self.left.as_ref(), self.right.as_ref(), r, t_min, t_max)
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.left.bounds().intersect(&self.right.bounds())
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        let mut materials = self.left.materials();
+        materials.extend(self.right.materials());
+        materials
+    }
+
+    fn intervals(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<Interval<'_>> {
+        Operation::Intersection.intervals(self.left.as_ref(), self.right.as_ref(), r, t_min, t_max)
+    }
+}
+
+#[typetag::serde]
+impl Hittable for Difference {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        Operation::Difference.hit(self.left.as_ref(), self.right.as_ref(), r, t_min, t_max)
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.left.bounds()
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        let mut materials = self.left.materials();
+        materials.extend(self.right.materials());
+        materials
+    }
+
+    fn intervals(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<Interval<'_>> {
+        Operation::Difference.intervals(self.left.as_ref(), self.right.as_ref(), r, t_min, t_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+    use crate::renderer::core::vector::{Point3, Vec3};
+    use crate::renderer::scene::materials::Material;
+    use crate::renderer::scene::objects::cuboid::Cuboid;
+    use crate::renderer::scene::objects::plane::Plane;
+    use crate::renderer::scene::objects::sphere::Sphere;
+
+    fn white() -> Material {
+        Material::Lambert {
+            albedo: color::WHITE.into(),
+        }
+    }
+
+    fn sphere(x: f32, radius: f32) -> Box<dyn Hittable> {
+        Box::new(Sphere {
+            center: Point3::new(x, 0.0, -5.0),
+            radius,
+            material: white(),
+        })
+    }
+
+    #[test]
+    fn test_default_intervals() {
+        let s = sphere(0.0, 1.0);
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let intervals = s.intervals(&r, 0.001, f32::INFINITY);
+        assert_eq!(intervals.len(), 1);
+        assert_eq!(intervals[0].enter.as_ref().unwrap().t, 4.0);
+        assert_eq!(intervals[0].exit.as_ref().unwrap().t, 6.0);
+
+        // from the inside, and stopping inside
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, -1.0));
+        let intervals = s.intervals(&r, 0.001, f32::INFINITY);
+        assert_eq!(intervals.len(), 1);
+        assert!(intervals[0].enter.is_none());
+        assert_eq!(intervals[0].exit.as_ref().unwrap().t, 1.0);
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let intervals = s.intervals(&r, 0.001, 5.0);
+        assert_eq!(intervals.len(), 1);
+        assert!(intervals[0].exit.is_none());
+
+        // a plane is the half-space behind it
+        let plane = Plane {
+            point: Point3::new(0.0, -1.0, 0.0),
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            material: white(),
+        };
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let intervals = plane.intervals(&r, 0.001, f32::INFINITY);
+        assert_eq!(intervals.len(), 1);
+        assert_eq!(intervals[0].enter.as_ref().unwrap().t, 1.0);
+        assert!(intervals[0].exit.is_none());
+        let r = Ray::new(Point3::new(0.0, -2.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let intervals = plane.intervals(&r, 0.001, f32::INFINITY);
+        assert_eq!(intervals.len(), 1);
+        assert!(intervals[0].enter.is_none() && intervals[0].exit.is_none());
+    }
+
+    #[test]
+    fn test_union() {
+        let union = Union {
+            left: sphere(-0.5, 1.0),
+            right: sphere(0.5, 1.0),
+        };
+
+        let r = Ray::new(Point3::new(-5.0, 0.0, -5.0), Vec3::new(1.0, 0.0, 0.0));
+        let intervals = union.intervals(&r, 0.001, f32::INFINITY);
+        assert_eq!(intervals.len(), 1);
+        assert_eq!(intervals[0].enter.as_ref().unwrap().t, 3.5);
+        assert_eq!(intervals[0].exit.as_ref().unwrap().t, 6.5);
+
+        // the surfaces inside of the other sphere are gone
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = union.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert_eq!(rec.t, 1.5);
+        assert!(!rec.front_face);
+
+        let b = union.bounds();
+        assert_eq!(b.box_min.x, -1.5);
+        assert_eq!(b.box_max.x, 1.5);
+    }
+
+    #[test]
+    fn test_intersection() {
+        let lens = Intersection {
+            left: sphere(-0.5, 1.0),
+            right: sphere(0.5, 1.0),
+        };
+
+        let r = Ray::new(Point3::new(-5.0, 0.0, -5.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = lens.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert_eq!(rec.t, 4.5);
+        assert!(rec.front_face);
+        assert_eq!(rec.normal.x, -1.0);
+        let rec = lens.hit(&r, rec.t + 0.01, f32::INFINITY).unwrap();
+        assert_eq!(rec.t, 5.5);
+        assert!(!rec.front_face);
+
+        // through the tip of only one sphere
+        let r = Ray::new(Point3::new(-1.4, 5.0, -5.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(lens.hit(&r, 0.001, f32::INFINITY).is_none());
+
+        let b = lens.bounds();
+        assert_eq!(b.box_min.x, -0.5);
+        assert_eq!(b.box_max.x, 0.5);
+    }
+
+    #[test]
+    fn test_difference() {
+        // the front half of the sphere is carved away by a box
+        let carved = Difference {
+            left: sphere(0.0, 1.0),
+            right: Box::new(Cuboid {
+                min: Point3::new(-2.0, -2.0, -5.0),
+                max: Point3::new(2.0, 2.0, -2.0),
+                material: white(),
+            }),
+        };
+
+        // the cut face is on the back of the box, facing the ray
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = carved.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert_eq!(rec.t, 5.0);
+        assert!(rec.front_face);
+        assert_eq!(rec.normal.z, 1.0);
+
+        // a short ray inside of the box that ends before leaving it doesn't
+        // see the carved away front of the sphere
+        let r = Ray::new(Point3::new(0.0, 0.0, -3.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(carved.hit(&r, 0.001, 1.5).is_none());
+
+        // beside the box, the sphere is still there
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.5), Vec3::new(0.0, 0.0, 1.0));
+        let rec = carved.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert_eq!(rec.t, 0.5);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn test_hollow_sphere() {
+        // a glass shell, nested inside of a union
+        let shell = Union {
+            left: Box::new(Difference {
+                left: sphere(0.0, 0.5),
+                right: sphere(0.0, 0.4),
+            }),
+            right: sphere(5.0, 0.5),
+        };
+
+        let r = Ray::new(Point3::new(-5.0, 0.0, -5.0), Vec3::new(1.0, 0.0, 0.0));
+        let intervals = shell.intervals(&r, 0.001, f32::INFINITY);
+        let hits: Vec<&HitRecord<'_>> = intervals
+            .iter()
+            .flat_map(|i| vec![i.enter.as_ref().unwrap(), i.exit.as_ref().unwrap()])
+            .collect();
+        let expected = [4.5, 4.6, 5.4, 5.5, 9.5, 10.5];
+        assert_eq!(hits.len(), expected.len());
+        for (i, (rec, t)) in hits.iter().zip(expected.iter()).enumerate() {
+            assert!((rec.t - t).abs() < 1e-5);
+            assert_eq!(rec.front_face, i % 2 == 0);
+        }
+    }
+
+    #[test]
+    fn test_csg_deserialize() {
+        let json = r#"{
+            "type": "Difference",
+            "left": {
+                "type": "Sphere",
+                "center": {"x": 0.0, "y": 0.0, "z": -5.0},
+                "radius": 1.0,
+                "material": {"lambert": {"albedo": {"x": 0.5, "y": 0.5, "z": 0.5}}}
+            },
+            "right": {
+                "type": "Instance",
+                "object": {
+                    "type": "Box",
+                    "min": {"x": -1.0, "y": -1.0, "z": -1.0},
+                    "max": {"x": 1.0, "y": 1.0, "z": 1.0},
+                    "material": {"lambert": {"albedo": {"x": 0.5, "y": 0.5, "z": 0.5}}}
+                },
+                "transform": [
+                    {"scale": {"x": 2.0, "y": 2.0, "z": 1.5}},
+                    {"translate": {"x": 0.0, "y": 0.0, "z": -3.5}}
+                ]
+            }
+        }"#;
+
+        let obj: Box<dyn Hittable> = serde_json::from_str(json).unwrap();
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = obj.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 5.0).abs() < 1e-5);
+        assert!(rec.front_face);
+    }
+}
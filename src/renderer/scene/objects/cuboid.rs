@@ -0,0 +1,275 @@
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector;
+use crate::renderer::core::vector::{Point3, Vec3};
+use crate::renderer::sampler::Sampler;
+
+use crate::renderer::scene::hittable::{HitRecord, Hittable};
+use crate::renderer::scene::materials::Material;
+
+use super::quad::intersect_quad;
+
+use serde::{Deserialize, Serialize};
+
+/// An axis-aligned box, called `Box` in the scene file. Rotated boxes are
+/// instances of it.
+///
+/// Fields:
+/// * `min` - The corner with the smallest coordinates.
+/// * `max` - The corner with the largest coordinates.
+/// * `material` - The material of all of the sides.
+///
+/// The texture coordinates go from 0 to 1 across each side.
+#[derive(Serialize, Deserialize)]
+pub struct Cuboid {
+    pub min: Point3,
+    pub max: Point3,
+    pub material: Material,
+}
+
+/// Returns the vector along the axis with the length.
+fn along_axis(axis: usize, length: f32) -> Vec3 {
+    match axis {
+        0 => Vec3::new(length, 0.0, 0.0),
+        1 => Vec3::new(0.0, length, 0.0),
+        _ => Vec3::new(0.0, 0.0, length),
+    }
+}
+
+impl Cuboid {
+    /// Returns the total area of the sides.
+    pub fn area(&self) -> f32 {
+        Aabb::new(self.min, self.max).surface_area()
+    }
+
+    /// Returns the six sides as a corner and two edges each.
+    fn sides(&self) -> [(Point3, Vec3, Vec3); 6] {
+        let d = self.max - self.min;
+        let side = |axis: usize, far: bool| {
+            let corner = if far {
+                self.min + along_axis(axis, d[axis])
+            } else {
+                self.min
+            };
+            let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+            (corner, along_axis(b, d[b]), along_axis(c, d[c]))
+        };
+
+        [
+            side(0, false),
+            side(0, true),
+            side(1, false),
+            side(1, true),
+            side(2, false),
+            side(2, true),
+        ]
+    }
+}
+
+#[typetag::serde(name = "Box")]
+impl Hittable for Cuboid {
+    /// Intersects the slabs between the sides along each axis. The ray
+    /// enters the box at the last slab it enters, and leaves it at the
+    /// first one it leaves.
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let mut enter = (f32::NEG_INFINITY, 0);
+        let mut exit = (f32::INFINITY, 0);
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - r.orig[axis]) * r.invdir[axis];
+            let t1 = (self.max[axis] - r.orig[axis]) * r.invdir[axis];
+            let (near, far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            if near > enter.0 {
+                enter = (near, axis);
+            }
+            if far < exit.0 {
+                exit = (far, axis);
+            }
+        }
+        if enter.0 > exit.0 {
+            return None;
+        }
+
+        // from the inside, the hit is on the way out
+        let (t, axis, sign) = if enter.0 >= t_min && enter.0 <= t_max {
+            (enter.0, enter.1, -r.dir[enter.1].signum())
+        } else if exit.0 >= t_min && exit.0 <= t_max {
+            (exit.0, exit.1, r.dir[exit.1].signum())
+        } else {
+            return None;
+        };
+
+        let p = r.at(t);
+        let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+        let uv = (
+            (p[b] - self.min[b]) / (self.max[b] - self.min[b]),
+            (p[c] - self.min[c]) / (self.max[c] - self.min[c]),
+        );
+
+        let mut rec = HitRecord {
+            p,
+            t,
+            normal: Vec3::default(),
+            geometric_normal: Vec3::default(),
+            front_face: false,
+            uv,
+            material: &self.material,
+        };
+        rec.set_face_normal(r, &along_axis(axis, sign));
+
+        Some(rec)
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::new(self.min, self.max)
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        vec![&self.material]
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    /// Picks a side proportionally to its area, then a uniform point on
+    /// it, so the points are uniformly distributed over the whole box.
+    fn random_direction(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let total_area = self.area();
+        if total_area <= 0.0 {
+            return None;
+        }
+
+        let mut target = sampler.get_1d() * total_area;
+        let sides = self.sides();
+        let mut chosen = sides[5];
+        for side in sides.iter() {
+            let area = vector::cross(&side.1, &side.2).length();
+            if target < area {
+                chosen = *side;
+                break;
+            }
+            target -= area;
+        }
+
+        let (corner, u, v) = chosen;
+        let (u1, u2) = sampler.get_2d();
+        Some(corner + u1 * u + u2 * v - *origin)
+    }
+
+    /// The direction passes through two of the sides, and the point could
+    /// have been sampled on either of them.
+    fn pdf_value(&self, origin: &Point3, dir: &Vec3) -> f32 {
+        let r = Ray::new(*origin, *dir);
+        let total_area = self.area();
+
+        self.sides()
+            .iter()
+            .filter_map(|(corner, u, v)| {
+                let (t, _, _) = intersect_quad(&r, corner, u, v, 0.001, f32::INFINITY)?;
+                Some(super::area_pdf(&vector::cross(u, v), t, dir, total_area))
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+    use crate::renderer::sampler::IndependentSampler;
+
+    fn test_box(material: Material) -> Cuboid {
+        Cuboid {
+            min: Point3::new(-1.0, -1.0, -3.0),
+            max: Point3::new(1.0, 1.0, -1.0),
+            material,
+        }
+    }
+
+    #[test]
+    fn test_box_hit() {
+        let cuboid = test_box(Material::Lambert {
+            albedo: color::WHITE.into(),
+        });
+
+        let r = Ray::new(Point3::new(0.5, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = cuboid.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert_eq!(rec.t, 1.0);
+        assert_eq!(rec.normal.z, 1.0);
+        assert!(rec.front_face);
+        assert!((rec.uv.0 - 0.75).abs() < 1e-5);
+        assert!((rec.uv.1 - 0.5).abs() < 1e-5);
+
+        // from the side
+        let r = Ray::new(Point3::new(-5.0, 0.0, -2.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = cuboid.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert_eq!(rec.t, 4.0);
+        assert_eq!(rec.normal.x, -1.0);
+
+        // from the inside, the far side faces back at the ray
+        let r = Ray::new(Point3::new(0.0, 0.0, -2.0), Vec3::new(0.0, 1.0, 0.0));
+        let rec = cuboid.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert_eq!(rec.t, 1.0);
+        assert!(!rec.front_face);
+        assert_eq!(rec.normal.y, -1.0);
+
+        let r = Ray::new(Point3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(cuboid.hit(&r, 0.001, f32::INFINITY).is_none());
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(cuboid.hit(&r, 0.001, 0.5).is_none());
+    }
+
+    #[test]
+    fn test_box_sampling() {
+        let cuboid = test_box(Material::Emissive {
+            color: color::WHITE,
+            intensity: 1.0,
+        });
+        assert!(cuboid.is_emissive());
+        assert_eq!(cuboid.area(), 24.0);
+
+        // the pdf integrates to 1 over the directions: the average of
+        // 1 / pdf is the solid angle, which the uniform sphere directions
+        // estimate as well
+        let origin = Point3::new(0.0, 0.5, 0.0);
+        let mut sampler = IndependentSampler::new(0);
+        let n = 20000;
+        let mut inverse_pdf = 0.0;
+        let mut hits = 0;
+        for _ in 0..n {
+            let dir = cuboid.random_direction(&origin, &mut sampler).unwrap();
+            inverse_pdf += 1.0 / cuboid.pdf_value(&origin, &dir);
+
+            let uniform = vector::random_unit_vector(&mut sampler);
+            if cuboid
+                .hit(&Ray::new(origin, uniform), 0.001, f32::INFINITY)
+                .is_some()
+            {
+                hits += 1;
+            }
+        }
+
+        let solid_angle = 4.0 * std::f32::consts::PI * hits as f32 / n as f32;
+        let estimate = inverse_pdf / n as f32;
+        assert!(
+            (estimate - solid_angle).abs() < 0.05 * solid_angle,
+            "{} {}",
+            estimate,
+            solid_angle
+        );
+    }
+
+    #[test]
+    fn test_box_deserialize() {
+        let json = r#"{
+            "type": "Box",
+            "min": {"x": -1.0, "y": -1.0, "z": -3.0},
+            "max": {"x": 1.0, "y": 1.0, "z": -1.0},
+            "material": {"lambert": {"albedo": {"x": 0.5, "y": 0.5, "z": 0.5}}}
+        }"#;
+
+        let obj: Box<dyn Hittable> = serde_json::from_str(json).unwrap();
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(obj.hit(&r, 0.001, f32::INFINITY).is_some());
+    }
+}
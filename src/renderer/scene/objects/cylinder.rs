@@ -0,0 +1,184 @@
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector::{Point3, Vec3};
+
+use crate::renderer::scene::hittable::{HitRecord, Hittable};
+use crate::renderer::scene::materials::Material;
+
+use super::quadric::{full_sweep, Cap, Frame, Revolution};
+
+use serde::{Deserialize, Serialize};
+
+/// A cylinder around an axis, open at the ends unless it's capped.
+///
+/// Fields:
+/// * `base` - The center of the bottom end.
+/// * `axis` - The direction from the bottom end to the top end.
+/// * `radius` - The radius of the cylinder.
+/// * `height` - The distance between the ends.
+/// * `phi_max` - How far around the axis the cylinder goes, in degrees.
+///   360 by default.
+/// * `capped` - Whether the ends are closed with disks.
+/// * `material` - The material of the surface.
+#[derive(Serialize, Deserialize)]
+pub struct Cylinder {
+    pub base: Point3,
+    pub axis: Vec3,
+    pub radius: f32,
+    pub height: f32,
+    #[serde(default = "full_sweep")]
+    pub phi_max: f32,
+    #[serde(default)]
+    pub capped: bool,
+    pub material: Material,
+}
+
+impl Cylinder {
+    fn revolution(&self) -> Revolution {
+        let caps = if self.capped {
+            [
+                Some(Cap {
+                    z: 0.0,
+                    radius: self.radius,
+                }),
+                Some(Cap {
+                    z: self.height,
+                    radius: self.radius,
+                }),
+            ]
+        } else {
+            [None, None]
+        };
+
+        Revolution {
+            a: 0.0,
+            b: 0.0,
+            c: self.radius * self.radius,
+            height: self.height,
+            phi_max: self.phi_max.to_radians(),
+            caps,
+        }
+    }
+}
+
+#[typetag::serde]
+impl Hittable for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.revolution().hit(
+            &Frame::new(self.base, &self.axis),
+            r,
+            t_min,
+            t_max,
+            &self.material,
+        )
+    }
+
+    fn bounds(&self) -> Aabb {
+        Frame::new(self.base, &self.axis).bounds(
+            Point3::new(-self.radius, -self.radius, 0.0),
+            Point3::new(self.radius, self.radius, self.height),
+        )
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        vec![&self.material]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+
+    fn test_cylinder(phi_max: f32, capped: bool) -> Cylinder {
+        Cylinder {
+            base: Point3::new(0.0, -1.0, -5.0),
+            axis: Vec3::new(0.0, 1.0, 0.0),
+            radius: 1.0,
+            height: 2.0,
+            phi_max,
+            capped,
+            material: Material::Lambert {
+                albedo: color::WHITE.into(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_cylinder_hit() {
+        let cylinder = test_cylinder(360.0, false);
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = cylinder.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-5);
+        assert!((rec.normal.z - 1.0).abs() < 1e-5);
+        assert!(rec.front_face);
+        assert!((rec.uv.1 - 0.5).abs() < 1e-5);
+
+        // above the top, and down through the open ends onto the inside
+        let r = Ray::new(Point3::new(0.0, 1.5, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(cylinder.hit(&r, 0.001, f32::INFINITY).is_none());
+        let r = Ray::new(Point3::new(0.0, 5.0, -5.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(cylinder.hit(&r, 0.001, f32::INFINITY).is_none());
+        let r = Ray::new(Point3::new(0.0, 5.0, -5.5), Vec3::new(0.0, -1.0, 0.3));
+        let rec = cylinder.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!(!rec.front_face);
+
+        let b = cylinder.bounds();
+        assert!((b.box_min.y + 1.0).abs() < 1e-5);
+        assert!((b.box_max.y - 1.0).abs() < 1e-5);
+        assert!((b.box_min.z + 6.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_cylinder_caps() {
+        let cylinder = test_cylinder(360.0, true);
+        let r = Ray::new(Point3::new(0.5, 5.0, -5.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = cylinder.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-5);
+        assert!((rec.normal.y - 1.0).abs() < 1e-5);
+        assert!(rec.front_face);
+
+        // from the inside, the bottom faces back up
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = cylinder.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 1.0).abs() < 1e-5);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn test_cylinder_sweep() {
+        // half of a cylinder, the sweep around the y axis keeps the half
+        // behind the axis
+        let half = test_cylinder(180.0, false);
+        let full = test_cylinder(360.0, false);
+
+        let r = Ray::new(Point3::new(-5.0, 0.0, -4.5), Vec3::new(1.0, 0.0, 0.0));
+        assert!(half.hit(&r, 0.001, f32::INFINITY).is_none());
+        assert!(full.hit(&r, 0.001, f32::INFINITY).is_some());
+        let r = Ray::new(Point3::new(-5.0, 0.0, -5.5), Vec3::new(1.0, 0.0, 0.0));
+        assert!(half.hit(&r, 0.001, f32::INFINITY).is_some());
+
+        // through the missing front onto the inside of the back
+        let r = Ray::new(Point3::new(0.6, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = half.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 5.8).abs() < 1e-4);
+        assert!(!rec.front_face);
+    }
+
+    #[test]
+    fn test_cylinder_deserialize() {
+        let json = r#"{
+            "type": "Cylinder",
+            "base": {"x": 0.0, "y": -1.0, "z": -5.0},
+            "axis": {"x": 0.0, "y": 1.0, "z": 0.0},
+            "radius": 1.0,
+            "height": 2.0,
+            "material": {"lambert": {"albedo": {"x": 0.5, "y": 0.5, "z": 0.5}}}
+        }"#;
+
+        let obj: Box<dyn Hittable> = serde_json::from_str(json).unwrap();
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(obj.hit(&r, 0.001, f32::INFINITY).is_some());
+    }
+}
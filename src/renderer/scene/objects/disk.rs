@@ -0,0 +1,188 @@
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector;
+use crate::renderer::core::vector::{Point3, Vec3};
+use crate::renderer::sampler::Sampler;
+
+use crate::renderer::scene::hittable::{HitRecord, Hittable};
+use crate::renderer::scene::materials::Material;
+
+use super::plane::intersect_plane;
+
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// A flat disk, or an annulus with a hole in the middle.
+///
+/// Fields:
+/// * `center` - The center of the disk.
+/// * `normal` - The direction the front face of the disk faces.
+/// * `radius` - The outer radius.
+/// * `inner_radius` - The radius of the hole in the middle, 0 by default.
+/// * `material` - The material of the surface.
+///
+/// The u texture coordinate goes around the disk, and v goes from the
+/// outer edge at 0 to the inner edge at 1.
+#[derive(Serialize, Deserialize)]
+pub struct Disk {
+    pub center: Point3,
+    pub normal: Vec3,
+    pub radius: f32,
+    #[serde(default)]
+    pub inner_radius: f32,
+    pub material: Material,
+}
+
+impl Disk {
+    /// Returns the area of the disk, without the hole.
+    pub fn area(&self) -> f32 {
+        PI * (self.radius * self.radius - self.inner_radius * self.inner_radius)
+    }
+
+    fn hit_t(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<f32> {
+        let normal = vector::unit_vector(&self.normal);
+        let t = intersect_plane(r, &self.center, &normal, t_min, t_max)?;
+
+        let distance_squared = (r.at(t) - self.center).length_squared();
+        if distance_squared > self.radius * self.radius
+            || distance_squared < self.inner_radius * self.inner_radius
+        {
+            return None;
+        }
+
+        Some(t)
+    }
+}
+
+#[typetag::serde]
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let t = self.hit_t(r, t_min, t_max)?;
+        let normal = vector::unit_vector(&self.normal);
+
+        let p = r.at(t);
+        let (tangent, bitangent) = vector::orthonormal_basis(&normal);
+        let offset = p - self.center;
+        let phi = vector::dot(&offset, &bitangent).atan2(vector::dot(&offset, &tangent));
+        let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+        let v = (self.radius - offset.length()) / (self.radius - self.inner_radius);
+
+        let mut rec = HitRecord {
+            p,
+            t,
+            normal: Vec3::default(),
+            geometric_normal: Vec3::default(),
+            front_face: false,
+            uv: (phi / (2.0 * PI), v),
+            material: &self.material,
+        };
+        rec.set_face_normal(r, &normal);
+
+        Some(rec)
+    }
+
+    fn bounds(&self) -> Aabb {
+        // the disk reaches less far along the axes it's tilted toward
+        let n = vector::unit_vector(&self.normal);
+        let extent = Vec3::new(
+            self.radius * (1.0 - n.x * n.x).max(0.0).sqrt(),
+            self.radius * (1.0 - n.y * n.y).max(0.0).sqrt(),
+            self.radius * (1.0 - n.z * n.z).max(0.0).sqrt(),
+        );
+        Aabb::new(self.center - extent, self.center + extent)
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        vec![&self.material]
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    /// Samples a uniform point on the disk.
+    fn random_direction(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let (u1, u2) = sampler.get_2d();
+        let inner_squared = self.inner_radius * self.inner_radius;
+        let r = (inner_squared + u1 * (self.radius * self.radius - inner_squared)).sqrt();
+        let phi = 2.0 * PI * u2;
+
+        let (tangent, bitangent) = vector::orthonormal_basis(&vector::unit_vector(&self.normal));
+        let p = self.center + r * phi.cos() * tangent + r * phi.sin() * bitangent;
+        Some(p - *origin)
+    }
+
+    fn pdf_value(&self, origin: &Point3, dir: &Vec3) -> f32 {
+        match self.hit_t(&Ray::new(*origin, *dir), 0.001, f32::INFINITY) {
+            Some(t) => super::area_pdf(&self.normal, t, dir, self.area()),
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+    use crate::renderer::sampler::IndependentSampler;
+
+    fn test_disk(material: Material) -> Disk {
+        Disk {
+            center: Point3::new(0.0, 0.0, -2.0),
+            normal: Vec3::new(0.0, 0.0, 1.0),
+            radius: 1.0,
+            inner_radius: 0.5,
+            material,
+        }
+    }
+
+    #[test]
+    fn test_disk_hit() {
+        let disk = test_disk(Material::Lambert {
+            albedo: color::WHITE.into(),
+        });
+
+        let r = Ray::new(Point3::new(0.75, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = disk.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert_eq!(rec.t, 2.0);
+        assert_eq!(rec.normal.z, 1.0);
+        assert!((rec.uv.1 - 0.5).abs() < 1e-5);
+
+        // through the hole and past the edge
+        let r = Ray::new(Point3::new(0.25, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(disk.hit(&r, 0.001, f32::INFINITY).is_none());
+        let r = Ray::new(Point3::new(1.25, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(disk.hit(&r, 0.001, f32::INFINITY).is_none());
+
+        let b = disk.bounds();
+        assert_eq!(b.box_max.x, 1.0);
+        assert_eq!(b.box_min.y, -1.0);
+        assert_eq!(b.box_min.z, -2.0);
+        assert_eq!(b.box_max.z, -2.0);
+    }
+
+    #[test]
+    fn test_disk_sampling() {
+        let disk = test_disk(Material::Emissive {
+            color: color::WHITE,
+            intensity: 1.0,
+        });
+        assert!(disk.is_emissive());
+        assert!((disk.area() - 0.75 * PI).abs() < 1e-5);
+
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let mut sampler = IndependentSampler::new(0);
+        for _ in 0..100 {
+            let dir = disk.random_direction(&origin, &mut sampler).unwrap();
+            let rec = disk.hit(&Ray::new(origin, dir), 0.001, f32::INFINITY);
+            assert!(rec.is_some());
+            assert!(disk.pdf_value(&origin, &dir) > 0.0);
+        }
+
+        // straight on through the ring, the pdf is d^2 / (cos * area)
+        let pdf = disk.pdf_value(&origin, &Vec3::new(0.0, 0.75, -2.0));
+        let d = Vec3::new(0.0, 0.75, -2.0).length();
+        let expected = d * d / ((2.0 / d) * disk.area());
+        assert!((pdf - expected).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector::{Point3, Vec3};
+
+use crate::renderer::scene::hittable::{HitRecord, Hittable};
+use crate::renderer::scene::materials::Material;
+
+use super::quadric::{full_sweep, Cap, Frame, Revolution};
+
+use serde::{Deserialize, Serialize};
+
+/// A hyperboloid of one sheet, like a cooling tower, narrowest halfway up
+/// and open at the ends unless it's capped. With an end radius smaller
+/// than the waist, it bulges out like a barrel instead.
+///
+/// Fields:
+/// * `base` - The center of the bottom end.
+/// * `axis` - The direction from the bottom end to the top end.
+/// * `radius` - The radius of the waist halfway up.
+/// * `end_radius` - The radius of the ends.
+/// * `height` - The distance between the ends.
+/// * `phi_max` - How far around the axis the hyperboloid goes, in degrees.
+///   360 by default.
+/// * `capped` - Whether the ends are closed with disks.
+/// * `material` - The material of the surface.
+#[derive(Serialize, Deserialize)]
+pub struct Hyperboloid {
+    pub base: Point3,
+    pub axis: Vec3,
+    pub radius: f32,
+    pub end_radius: f32,
+    pub height: f32,
+    #[serde(default = "full_sweep")]
+    pub phi_max: f32,
+    #[serde(default)]
+    pub capped: bool,
+    pub material: Material,
+}
+
+impl Hyperboloid {
+    /// The squared radius at z is radius^2 + k (z - height / 2)^2, with the
+    /// k that reaches the end radius at the ends.
+    fn revolution(&self) -> Revolution {
+        let half = 0.5 * self.height;
+        let k = (self.end_radius * self.end_radius - self.radius * self.radius) / (half * half);
+        let caps = if self.capped {
+            [
+                Some(Cap {
+                    z: 0.0,
+                    radius: self.end_radius,
+                }),
+                Some(Cap {
+                    z: self.height,
+                    radius: self.end_radius,
+                }),
+            ]
+        } else {
+            [None, None]
+        };
+
+        Revolution {
+            a: k,
+            b: -2.0 * k * half,
+            c: self.radius * self.radius + k * half * half,
+            height: self.height,
+            phi_max: self.phi_max.to_radians(),
+            caps,
+        }
+    }
+}
+
+#[typetag::serde]
+impl Hittable for Hyperboloid {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.revolution().hit(
+            &Frame::new(self.base, &self.axis),
+            r,
+            t_min,
+            t_max,
+            &self.material,
+        )
+    }
+
+    fn bounds(&self) -> Aabb {
+        let radius = self.radius.max(self.end_radius);
+        Frame::new(self.base, &self.axis).bounds(
+            Point3::new(-radius, -radius, 0.0),
+            Point3::new(radius, radius, self.height),
+        )
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        vec![&self.material]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+
+    fn test_hyperboloid(capped: bool) -> Hyperboloid {
+        Hyperboloid {
+            base: Point3::new(0.0, -1.0, -5.0),
+            axis: Vec3::new(0.0, 1.0, 0.0),
+            radius: 1.0,
+            end_radius: 2.0,
+            height: 2.0,
+            phi_max: 360.0,
+            capped,
+            material: Material::Lambert {
+                albedo: color::WHITE.into(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_hyperboloid_hit() {
+        let tower = test_hyperboloid(false);
+
+        // at the waist
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = tower.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-5);
+        assert!((rec.normal.z - 1.0).abs() < 1e-5);
+        assert!(rec.front_face);
+
+        // near the top it's wider, and the side leans out
+        let r = Ray::new(Point3::new(0.0, 0.9, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = tower.hit(&r, 0.001, f32::INFINITY).unwrap();
+        let radius = (1.0 + 3.0 * 0.81_f32).sqrt();
+        assert!((rec.t - (5.0 - radius)).abs() < 1e-4);
+        assert!(rec.normal.y < 0.0);
+
+        // in through the open top onto the inside of the wall
+        let r = Ray::new(Point3::new(-1.5, 1.5, -5.0), Vec3::new(1.0, -0.3, 0.0));
+        let rec = tower.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!(!rec.front_face);
+
+        let b = tower.bounds();
+        assert!((b.box_max.x - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_hyperboloid_caps() {
+        let tower = test_hyperboloid(true);
+        let r = Ray::new(Point3::new(1.5, 5.0, -5.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = tower.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-5);
+        assert!(rec.front_face);
+    }
+}
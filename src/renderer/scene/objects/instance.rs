@@ -0,0 +1,545 @@
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::transform::{AnimatedTransform, Transform};
+use crate::renderer::core::vector::{self, Point3, Vec3};
+use crate::renderer::sampler::Sampler;
+
+use crate::renderer::scene::bvh::Bvh;
+use crate::renderer::scene::hittable::{HitRecord, Hittable, Interval};
+use crate::renderer::scene::materials::Material;
+
+use super::default_end_time;
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Another object moved, rotated or scaled into place by an affine
+/// transform. The object is shared, so any number of instances of it can
+/// be placed around the scene while it's only stored once.
+///
+/// The rays are transformed into the space of the object, so the object
+/// itself doesn't know it's been moved.
+///
+/// With an end transform, the instance moves from its transform at the
+/// start time to the end transform at the end time, and is blurred along
+/// the way. Moving instances aren't sampled directly as lights, since the
+/// direction toward a light is sampled without knowing the time.
+///
+/// Fields:
+/// * `object` - The shared object, in its own space.
+/// * `transform` - From the space of the object into the world.
+/// * `end_transform` - Where the transform ends up, if the instance moves.
+/// * `start_time` - When the instance starts moving, 0 by default.
+/// * `end_time` - When the instance stops moving, 1 by default.
+#[derive(Serialize, Deserialize)]
+#[serde(from = "InstanceData")]
+pub struct Instance {
+    pub object: Arc<dyn Hittable>,
+    pub transform: Transform,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_transform: Option<Transform>,
+    pub start_time: f32,
+    pub end_time: f32,
+    #[serde(skip)]
+    motion: Option<AnimatedTransform>,
+}
+
+/// The serialized form of the instance. The interpolation of the motion
+/// is set up when it's converted into an Instance.
+#[derive(Deserialize)]
+struct InstanceData {
+    object: Arc<dyn Hittable>,
+    transform: Transform,
+    #[serde(default)]
+    end_transform: Option<Transform>,
+    #[serde(default)]
+    start_time: f32,
+    #[serde(default = "default_end_time")]
+    end_time: f32,
+}
+
+impl From<InstanceData> for Instance {
+    fn from(data: InstanceData) -> Self {
+        match data.end_transform {
+            Some(end_transform) => Instance::moving(
+                data.object,
+                data.transform,
+                end_transform,
+                data.start_time,
+                data.end_time,
+            ),
+            None => Instance::new(data.object, data.transform),
+        }
+    }
+}
+
+impl Instance {
+    /// Creates an instance of a shared object.
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Instance {
+        Instance {
+            object,
+            transform,
+            end_transform: None,
+            start_time: 0.0,
+            end_time: default_end_time(),
+            motion: None,
+        }
+    }
+
+    /// Creates an instance of a shared object moving from the transform at
+    /// the start time to the end transform at the end time.
+    pub fn moving(
+        object: Arc<dyn Hittable>,
+        transform: Transform,
+        end_transform: Transform,
+        start_time: f32,
+        end_time: f32,
+    ) -> Instance {
+        Instance {
+            object,
+            transform,
+            end_transform: Some(end_transform),
+            start_time,
+            end_time,
+            motion: Some(AnimatedTransform::new(
+                transform,
+                end_transform,
+                start_time,
+                end_time,
+            )),
+        }
+    }
+
+    /// Returns the transform at the time.
+    fn transform_at(&self, time: f32) -> Transform {
+        match &self.motion {
+            Some(motion) => motion.at(time),
+            None => self.transform,
+        }
+    }
+}
+
+#[typetag::serde]
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let transform = self.transform_at(r.time);
+        hit_transformed(self.object.as_ref(), &transform, r, t_min, t_max)
+    }
+
+    fn bounds(&self) -> Aabb {
+        let object_bounds = self.object.bounds();
+        match &self.motion {
+            Some(motion) => motion.bounds(&object_bounds),
+            None => self.transform.bounds(&object_bounds),
+        }
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        self.object.materials()
+    }
+
+    fn intervals(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<Interval<'_>> {
+        let transform = self.transform_at(r.time);
+        let mut intervals = self
+            .object
+            .intervals(&transform.ray_to_object(r), t_min, t_max);
+        for interval in intervals.iter_mut() {
+            for rec in interval.enter.iter_mut().chain(interval.exit.iter_mut()) {
+                record_to_world(&transform, rec);
+            }
+        }
+        intervals
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.motion.is_none() && self.object.is_emissive()
+    }
+
+    fn random_direction(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        random_direction_transformed(self.object.as_ref(), &self.transform, origin, sampler)
+    }
+
+    fn pdf_value(&self, origin: &Point3, dir: &Vec3) -> f32 {
+        pdf_value_transformed(self.object.as_ref(), &self.transform, origin, dir)
+    }
+}
+
+/// Many instances of the same object, for scattering thousands of copies
+/// around a scene. It's the same as an `Instance` per transform, but it
+/// only needs the object once in the scene file and it carries its own
+/// bounding volume hierarchy over the copies.
+///
+/// Fields:
+/// * `object` - The shared object, in its own space.
+/// * `transforms` - The transform of each copy.
+#[derive(Serialize, Deserialize)]
+#[serde(from = "InstanceGroupData")]
+pub struct InstanceGroup {
+    pub object: Arc<dyn Hittable>,
+    pub transforms: Vec<Transform>,
+    #[serde(skip)]
+    bvh: Bvh,
+}
+
+/// The serialized form of the group. The acceleration structure is built
+/// when it's converted into an InstanceGroup.
+#[derive(Deserialize)]
+struct InstanceGroupData {
+    object: Arc<dyn Hittable>,
+    transforms: Vec<Transform>,
+}
+
+impl From<InstanceGroupData> for InstanceGroup {
+    fn from(data: InstanceGroupData) -> Self {
+        InstanceGroup::new(data.object, data.transforms)
+    }
+}
+
+impl InstanceGroup {
+    /// Creates the group and builds its acceleration structure.
+    pub fn new(object: Arc<dyn Hittable>, transforms: Vec<Transform>) -> InstanceGroup {
+        let object_bounds = object.bounds();
+        let bounds: Vec<Aabb> = transforms
+            .iter()
+            .map(|t| t.bounds(&object_bounds))
+            .collect();
+
+        InstanceGroup {
+            object,
+            transforms,
+            bvh: Bvh::new(&bounds),
+        }
+    }
+}
+
+#[typetag::serde]
+impl Hittable for InstanceGroup {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.bvh.hit(r, t_min, t_max, |index, t_min, t_max| {
+            hit_transformed(
+                self.object.as_ref(),
+                &self.transforms[index],
+                r,
+                t_min,
+                t_max,
+            )
+        })
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        self.object.materials()
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.object.is_emissive() && !self.transforms.is_empty()
+    }
+
+    /// Picks one of the copies uniformly and samples it.
+    fn random_direction(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        if self.transforms.is_empty() {
+            return None;
+        }
+
+        let count = self.transforms.len();
+        let index = ((sampler.get_1d() * count as f32) as usize).min(count - 1);
+        random_direction_transformed(
+            self.object.as_ref(),
+            &self.transforms[index],
+            origin,
+            sampler,
+        )
+    }
+
+    fn pdf_value(&self, origin: &Point3, dir: &Vec3) -> f32 {
+        if self.transforms.is_empty() {
+            return 0.0;
+        }
+
+        let sum: f32 = self
+            .transforms
+            .iter()
+            .map(|t| pdf_value_transformed(self.object.as_ref(), t, origin, dir))
+            .sum();
+        sum / self.transforms.len() as f32
+    }
+}
+
+/// Hits the object in its own space and brings the hit back into the
+/// world. The direction of the ray isn't normalized in between, so the t
+/// stays the same.
+fn hit_transformed<'a>(
+    object: &'a dyn Hittable,
+    transform: &Transform,
+    r: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord<'a>> {
+    let mut rec = object.hit(&transform.ray_to_object(r), t_min, t_max)?;
+    record_to_world(transform, &mut rec);
+    Some(rec)
+}
+
+/// Brings a hit in the space of the object back into the world.
+fn record_to_world(transform: &Transform, rec: &mut HitRecord<'_>) {
+    // the inverse transpose keeps the normals on the side of the ray
+    rec.p = transform.point(&rec.p);
+    rec.normal = vector::unit_vector(&transform.normal(&rec.normal));
+    rec.geometric_normal = vector::unit_vector(&transform.normal(&rec.geometric_normal));
+}
+
+/// Samples the object from the origin taken into its space, and brings
+/// the direction back into the world.
+fn random_direction_transformed(
+    object: &dyn Hittable,
+    transform: &Transform,
+    origin: &Point3,
+    sampler: &mut dyn Sampler,
+) -> Option<Vec3> {
+    let local_origin = transform.inverse.transform_point(origin);
+    let dir = object.random_direction(&local_origin, sampler)?;
+    Some(transform.vector(&dir))
+}
+
+/// The pdf of `random_direction_transformed()`. A transform that doesn't
+/// scale evenly stretches the directions, so the density is corrected by
+/// how much the solid angle around the direction grows, which is
+/// |det M| / |M w|^3 for a unit direction w in object space.
+fn pdf_value_transformed(
+    object: &dyn Hittable,
+    transform: &Transform,
+    origin: &Point3,
+    dir: &Vec3,
+) -> f32 {
+    let local_origin = transform.inverse.transform_point(origin);
+    let local_dir = transform.inverse.transform_vector(dir);
+    let pdf = object.pdf_value(&local_origin, &local_dir);
+    if pdf == 0.0 {
+        return 0.0;
+    }
+
+    let stretch = transform.vector(&vector::unit_vector(&local_dir)).length();
+    pdf * stretch.powi(3) / transform.matrix.determinant3().abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+    use crate::renderer::sampler::IndependentSampler;
+    use crate::renderer::scene::materials::Material;
+    use crate::renderer::scene::objects::sphere::Sphere;
+
+    fn unit_sphere(material: Material) -> Arc<dyn Hittable> {
+        Arc::new(Sphere {
+            center: Point3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material,
+        })
+    }
+
+    #[test]
+    fn test_instance_hit() {
+        let sphere = unit_sphere(Material::Lambert {
+            albedo: color::WHITE.into(),
+        });
+
+        // squashed into an ellipsoid 2 wide and moved back
+        let transform = Transform::scale(Vec3::new(2.0, 1.0, 1.0))
+            .unwrap()
+            .then(&Transform::translate(Vec3::new(0.0, 0.0, -5.0)));
+        let instance = Instance::new(sphere.clone(), transform);
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = instance.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-5);
+        assert!((rec.p.z + 4.0).abs() < 1e-5);
+        assert!((rec.normal.z - 1.0).abs() < 1e-5);
+        assert!(rec.front_face);
+
+        // the side of the ellipsoid is 2 away from the center
+        let r = Ray::new(Point3::new(5.0, 0.0, -5.0), Vec3::new(-1.0, 0.0, 0.0));
+        let rec = instance.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 3.0).abs() < 1e-5);
+        assert!((rec.normal.x - 1.0).abs() < 1e-5);
+
+        let b = instance.bounds();
+        assert!((b.box_min.x + 2.0).abs() < 1e-5);
+        assert!((b.box_max.x - 2.0).abs() < 1e-5);
+        assert!((b.box_max.z + 4.0).abs() < 1e-5);
+
+        // the sphere is shared, not copied
+        assert_eq!(Arc::strong_count(&sphere), 2);
+    }
+
+    #[test]
+    fn test_instance_group() {
+        let sphere = unit_sphere(Material::Lambert {
+            albedo: color::WHITE.into(),
+        });
+        let transforms = (0..100)
+            .map(|i| Transform::translate(Vec3::new(3.0 * i as f32, 0.0, -5.0)))
+            .collect();
+        let group = InstanceGroup::new(sphere.clone(), transforms);
+        assert_eq!(Arc::strong_count(&sphere), 2);
+
+        for i in [0, 42, 99].iter() {
+            let x = 3.0 * *i as f32;
+            let r = Ray::new(Point3::new(x, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+            let rec = group.hit(&r, 0.001, f32::INFINITY).unwrap();
+            assert!((rec.p.x - x).abs() < 1e-4);
+            assert!((rec.t - 4.0).abs() < 1e-4);
+        }
+
+        // between the spheres
+        let r = Ray::new(Point3::new(1.5, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(group.hit(&r, 0.001, f32::INFINITY).is_none());
+        assert!((group.bounds().box_max.x - 298.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_instance_sampling() {
+        let light = unit_sphere(Material::Emissive {
+            color: color::WHITE,
+            intensity: 1.0,
+        });
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let mut sampler = IndependentSampler::new(0);
+
+        // moving and evenly scaling doesn't change the pdf of the cone
+        let scaled = Instance::new(
+            light.clone(),
+            Transform::scale(Vec3::new(5.0, 5.0, 5.0))
+                .unwrap()
+                .then(&Transform::translate(Vec3::new(0.0, 0.0, -10.0))),
+        );
+        let sphere = Sphere {
+            center: Point3::new(0.0, 0.0, -10.0),
+            radius: 5.0,
+            material: Material::Emissive {
+                color: color::WHITE,
+                intensity: 1.0,
+            },
+        };
+        assert!(scaled.is_emissive());
+        for _ in 0..100 {
+            let dir = scaled.random_direction(&origin, &mut sampler).unwrap();
+            assert!(scaled
+                .hit(&Ray::new(origin, dir), 0.001, f32::INFINITY)
+                .is_some());
+            let expected = sphere.pdf_value(&origin, &dir);
+            assert!((scaled.pdf_value(&origin, &dir) - expected).abs() < 1e-3 * expected);
+        }
+
+        // squashed, the pdf still integrates to 1 over the directions:
+        // the average of 1 / pdf over the samples is the solid angle,
+        // which the uniform sphere directions estimate as well
+        let squashed = Instance::new(
+            light,
+            Transform::scale(Vec3::new(3.0, 1.0, 1.0))
+                .unwrap()
+                .then(&Transform::translate(Vec3::new(0.0, 0.0, -4.0))),
+        );
+        let n = 20000;
+        let mut inverse_pdf = 0.0;
+        let mut hits = 0;
+        for _ in 0..n {
+            let dir = squashed.random_direction(&origin, &mut sampler).unwrap();
+            inverse_pdf += 1.0 / squashed.pdf_value(&origin, &dir);
+
+            let uniform = vector::random_unit_vector(&mut sampler);
+            if squashed
+                .hit(&Ray::new(origin, uniform), 0.001, f32::INFINITY)
+                .is_some()
+            {
+                hits += 1;
+            }
+        }
+        let solid_angle = 4.0 * std::f32::consts::PI * hits as f32 / n as f32;
+        let estimate = inverse_pdf / n as f32;
+        assert!(
+            (estimate - solid_angle).abs() < 0.05 * solid_angle,
+            "{} {}",
+            estimate,
+            solid_angle
+        );
+    }
+
+    #[test]
+    fn test_instance_motion() {
+        let sphere = unit_sphere(Material::Emissive {
+            color: color::WHITE,
+            intensity: 1.0,
+        });
+        let instance = Instance::moving(
+            sphere,
+            Transform::translate(Vec3::new(0.0, 0.0, -5.0)),
+            Transform::translate(Vec3::new(4.0, 0.0, -5.0)),
+            0.0,
+            1.0,
+        );
+
+        let dir = Vec3::new(0.0, 0.0, -1.0);
+        for (x, time, hit) in [
+            (0.0, 0.0, true),
+            (0.0, 1.0, false),
+            (4.0, 1.0, true),
+            (2.0, 0.5, true),
+        ] {
+            let r = Ray::with_time(Point3::new(x, 0.0, 0.0), dir, time);
+            assert_eq!(instance.hit(&r, 0.001, f32::INFINITY).is_some(), hit);
+        }
+
+        let b = instance.bounds();
+        assert!(b.box_min.x <= -1.0 && b.box_max.x >= 5.0);
+        assert!(b.box_max.x < 5.1);
+
+        // the direction toward a light can't follow it around
+        assert!(!instance.is_emissive());
+
+        // written out and read back, still moving
+        let instance: Box<dyn Hittable> = Box::new(instance);
+        let json = serde_json::to_string(&instance).unwrap();
+        let obj: Box<dyn Hittable> = serde_json::from_str(&json).unwrap();
+        let r = Ray::with_time(Point3::new(4.0, 0.0, 0.0), dir, 1.0);
+        assert!(obj.hit(&r, 0.001, f32::INFINITY).is_some());
+        let r = Ray::with_time(Point3::new(4.0, 0.0, 0.0), dir, 0.0);
+        assert!(obj.hit(&r, 0.001, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_instance_deserialize() {
+        let json = r#"{
+            "type": "InstanceGroup",
+            "object": {
+                "type": "Sphere",
+                "center": {"x": 0.0, "y": 0.0, "z": 0.0},
+                "radius": 1.0,
+                "material": {"lambert": {"albedo": {"x": 0.5, "y": 0.5, "z": 0.5}}}
+            },
+            "transforms": [
+                [{"translate": {"x": 0.0, "y": 0.0, "z": -5.0}}],
+                [{"translate": {"x": 3.0, "y": 0.0, "z": -5.0}}]
+            ]
+        }"#;
+
+        let obj: Box<dyn Hittable> = serde_json::from_str(json).unwrap();
+        let r = Ray::new(Point3::new(3.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(obj.hit(&r, 0.001, f32::INFINITY).is_some());
+
+        // written out and read back as a single instance
+        let instance: Box<dyn Hittable> = Box::new(Instance::new(
+            unit_sphere(Material::Lambert {
+                albedo: color::WHITE.into(),
+            }),
+            Transform::translate(Vec3::new(0.0, 0.0, -5.0)),
+        ));
+        let json = serde_json::to_string(&instance).unwrap();
+        let obj: Box<dyn Hittable> = serde_json::from_str(&json).unwrap();
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(obj.hit(&r, 0.001, f32::INFINITY).is_some());
+    }
+}
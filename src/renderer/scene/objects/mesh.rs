@@ -0,0 +1,440 @@
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector::{Point3, Vec3};
+use crate::renderer::sampler::Sampler;
+
+use crate::renderer::scene::bvh::Bvh;
+use crate::renderer::scene::hittable::{HitRecord, Hittable};
+use crate::renderer::scene::materials::Material;
+
+use super::triangle;
+
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// An indexed triangle mesh as a hittable object.
+///
+/// The vertex attributes are stored once in buffers shared by all of the
+/// triangles, and each triangle is three indices into them. The normals
+/// and uvs are optional, but when they are supplied they must have one
+/// entry per position.
+///
+/// The mesh carries its own bounding volume hierarchy over the triangles,
+/// so a mesh with millions of triangles is a single object to the region.
+///
+/// Fields:
+/// * `positions` - The vertex positions.
+/// * `normals` - The per vertex normals for smooth shading. Empty for flat shading.
+/// * `uvs` - The per vertex texture coordinates. Can be empty.
+/// * `indices` - The vertex indices of each triangle, counter-clockwise is the front face.
+/// * `material` - The material of the whole mesh.
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "TriangleMeshData")]
+pub struct TriangleMesh {
+    pub positions: Vec<Point3>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub normals: Vec<Vec3>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub uvs: Vec<(f32, f32)>,
+    pub indices: Vec<[u32; 3]>,
+    pub material: Material,
+    #[serde(skip)]
+    bvh: Bvh,
+    /// The running sum of the triangle areas, used to pick triangles
+    /// proportionally to their area when the mesh is sampled as a light.
+    #[serde(skip)]
+    area_cdf: Vec<f32>,
+}
+
+/// The serialized form of the mesh. It's validated and the acceleration
+/// structure is built when it's converted into a TriangleMesh.
+#[derive(Deserialize)]
+struct TriangleMeshData {
+    positions: Vec<Point3>,
+    #[serde(default)]
+    normals: Vec<Vec3>,
+    #[serde(default)]
+    uvs: Vec<(f32, f32)>,
+    indices: Vec<[u32; 3]>,
+    material: Material,
+}
+
+impl TryFrom<TriangleMeshData> for TriangleMesh {
+    type Error = String;
+
+    fn try_from(data: TriangleMeshData) -> Result<Self, Self::Error> {
+        TriangleMesh::new(
+            data.positions,
+            data.normals,
+            data.uvs,
+            data.indices,
+            data.material,
+        )
+    }
+}
+
+impl TriangleMesh {
+    /// Creates the mesh and builds its acceleration structure.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem if an index is outside of the
+    /// position buffer, or if the normal or uv buffers are supplied but
+    /// don't match the number of positions.
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f32, f32)>,
+        indices: Vec<[u32; 3]>,
+        material: Material,
+    ) -> Result<TriangleMesh, String> {
+        if !normals.is_empty() && normals.len() != positions.len() {
+            return Err(format!(
+                "Mesh has {} normals for {} positions",
+                normals.len(),
+                positions.len()
+            ));
+        }
+        if !uvs.is_empty() && uvs.len() != positions.len() {
+            return Err(format!(
+                "Mesh has {} uvs for {} positions",
+                uvs.len(),
+                positions.len()
+            ));
+        }
+        if let Some(i) = indices
+            .iter()
+            .flatten()
+            .find(|i| **i as usize >= positions.len())
+        {
+            return Err(format!(
+                "Mesh index {} is out of range for {} positions",
+                i,
+                positions.len()
+            ));
+        }
+
+        let mut mesh = TriangleMesh {
+            positions,
+            normals,
+            uvs,
+            indices,
+            material,
+            bvh: Bvh::default(),
+            area_cdf: Vec::new(),
+        };
+
+        let bounds: Vec<Aabb> = (0..mesh.indices.len())
+            .map(|i| {
+                let mut b = Aabb::empty();
+                for v in mesh.vertices(i).iter() {
+                    b.expand_point(*v);
+                }
+                b
+            })
+            .collect();
+        mesh.bvh = Bvh::new(&bounds);
+
+        let mut total_area = 0.0;
+        mesh.area_cdf = (0..mesh.indices.len())
+            .map(|i| {
+                total_area += triangle::area(&mesh.vertices(i));
+                total_area
+            })
+            .collect();
+
+        Ok(mesh)
+    }
+
+    /// Returns the number of triangles in the mesh.
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Returns whether the mesh has no triangles.
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Returns the positions of the corners of a triangle.
+    fn vertices(&self, triangle: usize) -> [Point3; 3] {
+        let [i0, i1, i2] = self.indices[triangle];
+        [
+            self.positions[i0 as usize],
+            self.positions[i1 as usize],
+            self.positions[i2 as usize],
+        ]
+    }
+
+    /// Returns the vertex normals of a triangle if the mesh has them.
+    fn vertex_normals(&self, triangle: usize) -> Option<[Vec3; 3]> {
+        if self.normals.is_empty() {
+            return None;
+        }
+
+        let [i0, i1, i2] = self.indices[triangle];
+        Some([
+            self.normals[i0 as usize],
+            self.normals[i1 as usize],
+            self.normals[i2 as usize],
+        ])
+    }
+
+    /// Returns the texture coordinates of a triangle if the mesh has them.
+    fn vertex_uvs(&self, triangle: usize) -> Option<[(f32, f32); 3]> {
+        if self.uvs.is_empty() {
+            return None;
+        }
+
+        let [i0, i1, i2] = self.indices[triangle];
+        Some([
+            self.uvs[i0 as usize],
+            self.uvs[i1 as usize],
+            self.uvs[i2 as usize],
+        ])
+    }
+
+    /// Returns the total surface area of the mesh.
+    pub fn area(&self) -> f32 {
+        *self.area_cdf.last().unwrap_or(&0.0)
+    }
+
+    fn hit_triangle(
+        &self,
+        triangle: usize,
+        r: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<HitRecord<'_>> {
+        let vertices = self.vertices(triangle);
+        let (t, b1, b2) = triangle::intersect(r, &vertices, t_min, t_max)?;
+
+        Some(triangle::hit_record(
+            r,
+            t,
+            (b1, b2),
+            &vertices,
+            self.vertex_normals(triangle).as_ref(),
+            self.vertex_uvs(triangle).as_ref(),
+            &self.material,
+        ))
+    }
+}
+
+#[typetag::serde]
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.bvh.hit(r, t_min, t_max, |triangle, t_min, t_max| {
+            self.hit_triangle(triangle, r, t_min, t_max)
+        })
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        vec![&self.material]
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    /// Picks a triangle proportionally to its area, then a uniform point on
+    /// it, so the points are uniformly distributed over the whole mesh.
+    fn random_direction(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let total_area = self.area();
+        if total_area <= 0.0 {
+            return None;
+        }
+
+        let target = sampler.get_1d() * total_area;
+        let triangle = self
+            .area_cdf
+            .partition_point(|a| *a <= target)
+            .min(self.indices.len() - 1);
+
+        let (u1, u2) = sampler.get_2d();
+        let p = triangle::sample_point(&self.vertices(triangle), u1, u2);
+        Some(p - *origin)
+    }
+
+    /// Sums the densities of all of the triangles along the direction,
+    /// since `random_direction()` could have picked any of them when the
+    /// mesh isn't convex.
+    fn pdf_value(&self, origin: &Point3, dir: &Vec3) -> f32 {
+        let r = Ray::new(*origin, *dir);
+        let total_area = self.area();
+
+        // reporting no hits keeps the hierarchy from culling the triangles
+        // behind the closest one
+        let mut pdf = 0.0;
+        self.bvh
+            .hit(&r, 0.001, f32::INFINITY, |triangle, t_min, t_max| {
+                if let Some(rec) = self.hit_triangle(triangle, &r, t_min, t_max) {
+                    pdf += triangle::area_pdf(&self.vertices(triangle), rec.t, dir, total_area);
+                }
+                None
+            });
+        pdf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+    use crate::renderer::sampler::IndependentSampler;
+
+    /// A unit square in the xy plane at z = -1 made of two triangles.
+    fn test_quad(normals: Vec<Vec3>) -> TriangleMesh {
+        TriangleMesh::new(
+            vec![
+                Point3::new(-1.0, -1.0, -1.0),
+                Point3::new(1.0, -1.0, -1.0),
+                Point3::new(1.0, 1.0, -1.0),
+                Point3::new(-1.0, 1.0, -1.0),
+            ],
+            normals,
+            vec![],
+            vec![[0, 1, 2], [0, 2, 3]],
+            Material::Lambert {
+                albedo: color::WHITE.into(),
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_mesh_hit() {
+        let mesh = test_quad(vec![]);
+        assert_eq!(mesh.len(), 2);
+
+        // one ray into each triangle
+        for x in [-0.5, 0.5].iter() {
+            let r = Ray::new(Point3::new(*x, 0.1, 0.0), Vec3::new(0.0, 0.0, -1.0));
+            let rec = mesh.hit(&r, 0.001, f32::INFINITY).unwrap();
+            assert_eq!(rec.t, 1.0);
+            assert_eq!(rec.p.x, *x);
+            assert!(rec.front_face);
+            assert_eq!(rec.normal.z, 1.0);
+        }
+
+        let r = Ray::new(Point3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(mesh.hit(&r, 0.001, f32::INFINITY).is_none());
+
+        let b = mesh.bounds();
+        assert_eq!(b.box_min.x, -1.0);
+        assert_eq!(b.box_max.y, 1.0);
+        assert_eq!(b.box_max.z, -1.0);
+    }
+
+    #[test]
+    fn test_mesh_smooth_normals() {
+        let n = Vec3::new(0.0, 1.0, 1.0);
+        let mesh = test_quad(vec![n, n, n, n]);
+
+        let r = Ray::new(Point3::new(0.5, 0.1, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = mesh.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.normal.y - 0.5_f32.sqrt()).abs() < 1e-5);
+        assert!((rec.normal.z - 0.5_f32.sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_mesh_sampling() {
+        let mut mesh = test_quad(vec![]);
+        mesh.material = Material::Emissive {
+            color: color::WHITE,
+            intensity: 1.0,
+        };
+        assert!(mesh.is_emissive());
+        assert_eq!(mesh.area(), 4.0);
+
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let mut sampler = IndependentSampler::new(0);
+        let mut left = 0;
+        for _ in 0..1000 {
+            let dir = mesh.random_direction(&origin, &mut sampler).unwrap();
+            assert!(mesh
+                .hit(&Ray::new(origin, dir), 0.001, f32::INFINITY)
+                .is_some());
+            if dir.x < 0.0 {
+                left += 1;
+            }
+        }
+        // both halves of the quad get sampled
+        assert!(left > 300 && left < 700);
+
+        // straight on at a distance of 1, the pdf is d^2 / (cos * area)
+        let pdf = mesh.pdf_value(&origin, &Vec3::new(0.1, 0.2, -1.0));
+        let expected = (1.0 + 0.01 + 0.04) / ((1.0 / 1.05_f32.sqrt()) * 4.0);
+        assert!((pdf - expected).abs() < 1e-4);
+
+        // with a second square behind the first, both count
+        let mut vertices = mesh.positions.clone();
+        vertices.extend(
+            mesh.positions
+                .iter()
+                .map(|p| *p + Vec3::new(0.0, 0.0, -1.0)),
+        );
+        let stacked = TriangleMesh::new(
+            vertices,
+            vec![],
+            vec![],
+            vec![[0, 1, 2], [0, 2, 3], [4, 5, 6], [4, 6, 7]],
+            Material::Lambert {
+                albedo: color::WHITE.into(),
+            },
+        )
+        .unwrap();
+        let pdf = stacked.pdf_value(&origin, &Vec3::new(0.1, 0.2, -1.0));
+        let expected = (1.0 + 4.0) * 1.05 / ((1.0 / 1.05_f32.sqrt()) * 8.0);
+        assert!((pdf - expected).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_mesh_validation() {
+        let material = Material::Lambert {
+            albedo: color::WHITE.into(),
+        };
+        let positions = vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ];
+
+        assert!(
+            TriangleMesh::new(positions.clone(), vec![], vec![], vec![[0, 1, 3]], material)
+                .is_err()
+        );
+
+        let material = Material::Lambert {
+            albedo: color::WHITE.into(),
+        };
+        let normals = vec![Vec3::new(0.0, 0.0, 1.0)];
+        assert!(TriangleMesh::new(positions, normals, vec![], vec![[0, 1, 2]], material).is_err());
+    }
+
+    #[test]
+    fn test_mesh_deserialize() {
+        let json = r#"{
+            "type": "TriangleMesh",
+            "positions": [
+                {"x": -1.0, "y": -1.0, "z": -1.0},
+                {"x": 1.0, "y": -1.0, "z": -1.0},
+                {"x": 0.0, "y": 1.0, "z": -1.0}
+            ],
+            "indices": [[0, 1, 2]],
+            "material": {"lambert": {"albedo": {"x": 0.5, "y": 0.5, "z": 0.5}}}
+        }"#;
+
+        let obj: Box<dyn Hittable> = serde_json::from_str(json).unwrap();
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(obj.hit(&r, 0.001, f32::INFINITY).is_some());
+
+        // out of range indices are rejected while parsing
+        let json = json.replace("[[0, 1, 2]]", "[[0, 1, 7]]");
+        assert!(serde_json::from_str::<Box<dyn Hittable>>(&json).is_err());
+    }
+}
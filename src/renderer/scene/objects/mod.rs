@@ -1,3 +1,41 @@
 //! Where we would add the objects supported by the ray tracer,
 //! could be objects like a triangle, torus, cube, cylinder, etc.
+pub mod cone;
+pub mod csg;
+pub mod cuboid;
+pub mod cylinder;
+pub mod disk;
+pub mod hyperboloid;
+pub mod instance;
+pub mod mesh;
+pub mod moving_sphere;
+pub mod paraboloid;
+pub mod plane;
+pub mod quad;
+mod quadric;
 pub mod sphere;
+pub mod torus;
+pub mod triangle;
+
+use crate::renderer::core::vector::{self, Vec3};
+
+/// The default time moving objects reach the end of their motion, so a
+/// shutter open from 0 to 1 sees the whole motion.
+pub(crate) fn default_end_time() -> f32 {
+    1.0
+}
+
+/// Converts the density of uniformly sampling a point on a surface of the
+/// total area into a density with respect to solid angle, for the point
+/// hit at t along the direction on a flat piece of the surface with the
+/// normal. The normal doesn't need to be normalized.
+pub(crate) fn area_pdf(normal: &Vec3, t: f32, dir: &Vec3, total_area: f32) -> f32 {
+    let length = dir.length();
+    let distance_squared = t * t * length * length;
+    let cosine = (vector::dot(&vector::unit_vector(normal), dir) / length).abs();
+    if cosine <= 0.0 || total_area <= 0.0 {
+        return 0.0;
+    }
+
+    distance_squared / (cosine * total_area)
+}
@@ -0,0 +1,143 @@
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector::Point3;
+
+use crate::renderer::scene::hittable::{HitRecord, Hittable};
+use crate::renderer::scene::materials::Material;
+
+use super::default_end_time;
+use super::sphere::hit_sphere;
+
+use serde::{Deserialize, Serialize};
+
+/// A sphere moving in a straight line while the shutter is open, which is
+/// blurred along its path.
+///
+/// Fields:
+/// * `start_center` - The center at the start time.
+/// * `end_center` - The center at the end time.
+/// * `start_time` - When the sphere starts moving, 0 by default. Before
+///   then it's at the start.
+/// * `end_time` - When the sphere stops moving, 1 by default. After then
+///   it's at the end.
+/// * `radius` - The radius of the sphere.
+/// * `material` - The material of the surface.
+///
+/// Moving spheres aren't sampled directly as lights, since the direction
+/// toward a light is sampled without knowing the time.
+#[derive(Serialize, Deserialize)]
+pub struct MovingSphere {
+    pub start_center: Point3,
+    pub end_center: Point3,
+    #[serde(default)]
+    pub start_time: f32,
+    #[serde(default = "default_end_time")]
+    pub end_time: f32,
+    pub radius: f32,
+    pub material: Material,
+}
+
+impl MovingSphere {
+    /// Returns the center of the sphere at the time.
+    pub fn center(&self, time: f32) -> Point3 {
+        if self.end_time <= self.start_time {
+            return self.start_center;
+        }
+
+        let t = ((time - self.start_time) / (self.end_time - self.start_time)).clamp(0.0, 1.0);
+        self.start_center + t * (self.end_center - self.start_center)
+    }
+}
+
+#[typetag::serde]
+impl Hittable for MovingSphere {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        hit_sphere(
+            &self.center(r.time),
+            self.radius,
+            &self.material,
+            r,
+            t_min,
+            t_max,
+        )
+    }
+
+    /// The box around the sphere at both ends of its path.
+    fn bounds(&self) -> Aabb {
+        let radius = self.radius.abs();
+        let mut b = Aabb::new(self.start_center - radius, self.start_center + radius);
+        b.expand(Aabb::new(
+            self.end_center - radius,
+            self.end_center + radius,
+        ));
+        b
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        vec![&self.material]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+    use crate::renderer::core::vector::Vec3;
+
+    fn test_sphere() -> MovingSphere {
+        MovingSphere {
+            start_center: Point3::new(0.0, 0.0, -5.0),
+            end_center: Point3::new(4.0, 0.0, -5.0),
+            start_time: 0.0,
+            end_time: 1.0,
+            radius: 1.0,
+            material: Material::Lambert {
+                albedo: color::WHITE.into(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_moving_sphere_hit() {
+        let sphere = test_sphere();
+        let dir = Vec3::new(0.0, 0.0, -1.0);
+
+        // the sphere has left the ray by the end
+        let r = Ray::with_time(Point3::new(0.0, 0.0, 0.0), dir, 0.0);
+        assert_eq!(sphere.hit(&r, 0.001, f32::INFINITY).unwrap().t, 4.0);
+        let r = Ray::with_time(Point3::new(0.0, 0.0, 0.0), dir, 1.0);
+        assert!(sphere.hit(&r, 0.001, f32::INFINITY).is_none());
+
+        // and reached the other one
+        let r = Ray::with_time(Point3::new(4.0, 0.0, 0.0), dir, 1.0);
+        let rec = sphere.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert_eq!(rec.t, 4.0);
+        assert_eq!(rec.normal.z, 1.0);
+
+        // halfway, and staying put past the end
+        assert_eq!(sphere.center(0.5).x, 2.0);
+        assert_eq!(sphere.center(2.0).x, 4.0);
+        assert_eq!(sphere.center(-1.0).x, 0.0);
+
+        let b = sphere.bounds();
+        assert_eq!(b.box_min.x, -1.0);
+        assert_eq!(b.box_max.x, 5.0);
+    }
+
+    #[test]
+    fn test_moving_sphere_deserialize() {
+        let json = r#"{
+            "type": "MovingSphere",
+            "start_center": {"x": 0.0, "y": 0.0, "z": -5.0},
+            "end_center": {"x": 0.0, "y": 2.0, "z": -5.0},
+            "radius": 1.0,
+            "material": {"lambert": {"albedo": {"x": 0.5, "y": 0.5, "z": 0.5}}}
+        }"#;
+
+        let obj: Box<dyn Hittable> = serde_json::from_str(json).unwrap();
+        let r = Ray::with_time(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, 0.0, -1.0), 1.0);
+        assert!(obj.hit(&r, 0.001, f32::INFINITY).is_some());
+        let r = Ray::with_time(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, 0.0, -1.0), 0.0);
+        assert!(obj.hit(&r, 0.001, f32::INFINITY).is_none());
+    }
+}
@@ -0,0 +1,130 @@
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector::{Point3, Vec3};
+
+use crate::renderer::scene::hittable::{HitRecord, Hittable};
+use crate::renderer::scene::materials::Material;
+
+use super::quadric::{full_sweep, Cap, Frame, Revolution};
+
+use serde::{Deserialize, Serialize};
+
+/// A paraboloid opening up from its base like a dish, open at the top
+/// unless it's capped.
+///
+/// Fields:
+/// * `base` - The bottom of the dish.
+/// * `axis` - The direction the dish opens toward.
+/// * `radius` - The radius of the rim.
+/// * `height` - The distance from the bottom up to the rim.
+/// * `phi_max` - How far around the axis the paraboloid goes, in degrees.
+///   360 by default.
+/// * `capped` - Whether the top is closed with a disk.
+/// * `material` - The material of the surface.
+#[derive(Serialize, Deserialize)]
+pub struct Paraboloid {
+    pub base: Point3,
+    pub axis: Vec3,
+    pub radius: f32,
+    pub height: f32,
+    #[serde(default = "full_sweep")]
+    pub phi_max: f32,
+    #[serde(default)]
+    pub capped: bool,
+    pub material: Material,
+}
+
+impl Paraboloid {
+    /// The squared radius at z is radius^2 * z / height.
+    fn revolution(&self) -> Revolution {
+        let rim = Cap {
+            z: self.height,
+            radius: self.radius,
+        };
+
+        Revolution {
+            a: 0.0,
+            b: self.radius * self.radius / self.height,
+            c: 0.0,
+            height: self.height,
+            phi_max: self.phi_max.to_radians(),
+            caps: [None, if self.capped { Some(rim) } else { None }],
+        }
+    }
+}
+
+#[typetag::serde]
+impl Hittable for Paraboloid {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.revolution().hit(
+            &Frame::new(self.base, &self.axis),
+            r,
+            t_min,
+            t_max,
+            &self.material,
+        )
+    }
+
+    fn bounds(&self) -> Aabb {
+        Frame::new(self.base, &self.axis).bounds(
+            Point3::new(-self.radius, -self.radius, 0.0),
+            Point3::new(self.radius, self.radius, self.height),
+        )
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        vec![&self.material]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+
+    fn test_paraboloid(capped: bool) -> Paraboloid {
+        Paraboloid {
+            base: Point3::new(0.0, -1.0, -5.0),
+            axis: Vec3::new(0.0, 1.0, 0.0),
+            radius: 2.0,
+            height: 4.0,
+            phi_max: 360.0,
+            capped,
+            material: Material::Lambert {
+                albedo: color::WHITE.into(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_paraboloid_hit() {
+        let dish = test_paraboloid(false);
+
+        // one up from the bottom, the radius is 1
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = dish.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 4.0).abs() < 1e-5);
+        assert!(rec.front_face);
+        assert!((rec.uv.1 - 0.25).abs() < 1e-5);
+
+        // down into the dish onto the inside of the bottom
+        let r = Ray::new(Point3::new(0.0, 5.0, -5.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = dish.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 6.0).abs() < 1e-5);
+        assert!(!rec.front_face);
+        assert!((rec.normal.y - 1.0).abs() < 1e-5);
+
+        let b = dish.bounds();
+        assert!((b.box_max.y - 3.0).abs() < 1e-5);
+        assert!((b.box_min.x + 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_paraboloid_cap() {
+        let dish = test_paraboloid(true);
+        let r = Ray::new(Point3::new(0.0, 5.0, -5.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = dish.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 2.0).abs() < 1e-5);
+        assert!(rec.front_face);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector;
+use crate::renderer::core::vector::{Point3, Vec3};
+
+use crate::renderer::scene::hittable::{HitRecord, Hittable, Interval};
+use crate::renderer::scene::materials::Material;
+
+use serde::{Deserialize, Serialize};
+
+/// Directions closer to parallel to a flat surface than this miss it.
+pub(crate) const PARALLEL_EPSILON: f32 = 1e-8;
+
+/// An infinite plane, for floors and walls that go on forever. Planes
+/// can't be bounded, so the region tests them against every ray on their
+/// own, and they can't be sampled as lights.
+///
+/// Fields:
+/// * `point` - Any point on the plane.
+/// * `normal` - The direction the front face of the plane faces.
+/// * `material` - The material of the surface.
+///
+/// The texture coordinates are the distances from the point along two
+/// directions in the plane, so textures repeat in world units.
+#[derive(Serialize, Deserialize)]
+pub struct Plane {
+    pub point: Point3,
+    pub normal: Vec3,
+    pub material: Material,
+}
+
+/// Returns the t where the ray crosses the plane through the point with
+/// the normal, if it's in the range.
+pub(crate) fn intersect_plane(
+    r: &Ray,
+    point: &Point3,
+    normal: &Vec3,
+    t_min: f32,
+    t_max: f32,
+) -> Option<f32> {
+    let denominator = vector::dot(normal, &r.dir);
+    if denominator.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+
+    let t = vector::dot(&(*point - r.orig), normal) / denominator;
+    if t < t_min || t_max < t {
+        return None;
+    }
+
+    Some(t)
+}
+
+#[typetag::serde]
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let normal = vector::unit_vector(&self.normal);
+        let t = intersect_plane(r, &self.point, &normal, t_min, t_max)?;
+
+        let p = r.at(t);
+        let (tangent, bitangent) = vector::orthonormal_basis(&normal);
+        let offset = p - self.point;
+
+        let mut rec = HitRecord {
+            p,
+            t,
+            normal: Vec3::default(),
+            geometric_normal: Vec3::default(),
+            front_face: false,
+            uv: (
+                vector::dot(&offset, &tangent),
+                vector::dot(&offset, &bitangent),
+            ),
+            material: &self.material,
+        };
+        rec.set_face_normal(r, &normal);
+
+        Some(rec)
+    }
+
+    fn bounds(&self) -> Aabb {
+        Aabb::infinite()
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        vec![&self.material]
+    }
+
+    /// The plane is the boundary of the half-space behind it, which the
+    /// ray can be inside of without ever crossing the plane.
+    fn intervals(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<Interval<'_>> {
+        match self.hit(r, t_min, t_max) {
+            Some(rec) if rec.front_face => vec![Interval {
+                enter: Some(rec),
+                exit: None,
+            }],
+            Some(rec) => vec![Interval {
+                enter: None,
+                exit: Some(rec),
+            }],
+            None if vector::dot(&(r.at(t_min) - self.point), &self.normal) < 0.0 => {
+                vec![Interval {
+                    enter: None,
+                    exit: None,
+                }]
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+
+    #[test]
+    fn test_plane_hit() {
+        let plane = Plane {
+            point: Point3::new(0.0, -1.0, 0.0),
+            normal: Vec3::new(0.0, 2.0, 0.0),
+            material: Material::Lambert {
+                albedo: color::WHITE.into(),
+            },
+        };
+
+        let r = Ray::new(Point3::new(3.0, 1.0, -7.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = plane.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert_eq!(rec.t, 2.0);
+        assert_eq!(rec.p.y, -1.0);
+        assert_eq!(rec.normal.y, 1.0);
+        assert!(rec.front_face);
+
+        // far away hits are still exact, unlike on a huge sphere
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1000.0, -1.0, 0.0));
+        let rec = plane.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert_eq!(rec.p.y, -1.0);
+
+        // from below and parallel
+        let r = Ray::new(Point3::new(0.0, -2.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert!(!plane.hit(&r, 0.001, f32::INFINITY).unwrap().front_face);
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(plane.hit(&r, 0.001, f32::INFINITY).is_none());
+
+        // the texture coordinates are distances in the plane
+        let r = Ray::new(Point3::new(3.0, 1.0, 4.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = plane.hit(&r, 0.001, f32::INFINITY).unwrap();
+        let distance = (rec.uv.0 * rec.uv.0 + rec.uv.1 * rec.uv.1).sqrt();
+        assert!((distance - 5.0).abs() < 1e-5);
+
+        assert!(!plane.bounds().is_finite());
+    }
+}
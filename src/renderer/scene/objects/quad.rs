@@ -0,0 +1,259 @@
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector;
+use crate::renderer::core::vector::{Point3, Vec3};
+use crate::renderer::sampler::Sampler;
+
+use crate::renderer::scene::hittable::{HitRecord, Hittable};
+use crate::renderer::scene::materials::Material;
+
+use super::plane::intersect_plane;
+
+use serde::{Deserialize, Serialize};
+
+/// A flat parallelogram, spanned by two edges from a corner. With edges
+/// along the axes, it's an axis-aligned rectangle.
+///
+/// Fields:
+/// * `corner` - One corner of the quad.
+/// * `u` - The edge from the corner to the next corner.
+/// * `v` - The edge from the corner to the previous corner. The front face
+///   is the side `u` turns counter-clockwise to `v` from.
+/// * `material` - The material of the surface.
+///
+/// The texture coordinates go from 0 to 1 along the edges.
+#[derive(Serialize, Deserialize)]
+pub struct Quad {
+    pub corner: Point3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub material: Material,
+}
+
+/// Returns the t and the coordinates along the edges of a ray hitting the
+/// parallelogram at the corner with the edges u and v.
+pub(crate) fn intersect_quad(
+    r: &Ray,
+    corner: &Point3,
+    u: &Vec3,
+    v: &Vec3,
+    t_min: f32,
+    t_max: f32,
+) -> Option<(f32, f32, f32)> {
+    let n = vector::cross(u, v);
+    let t = intersect_plane(r, corner, &n, t_min, t_max)?;
+
+    // the coordinates of the hit in the frame of the edges
+    let w = n / n.length_squared();
+    let offset = r.at(t) - *corner;
+    let alpha = vector::dot(&w, &vector::cross(&offset, v));
+    let beta = vector::dot(&w, &vector::cross(u, &offset));
+    if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+        return None;
+    }
+
+    Some((t, alpha, beta))
+}
+
+impl Quad {
+    /// Creates an axis-aligned rectangle from two opposite corners that
+    /// only differ along two of the axes. Seen from the positive side of
+    /// the flat axis, it's the front face.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem if the corners aren't in a
+    /// plane perpendicular to one of the axes.
+    pub fn rect(a: Point3, b: Point3, material: Material) -> Result<Quad, String> {
+        let d = b - a;
+        let (u, v) = if d.x == 0.0 && d.y != 0.0 && d.z != 0.0 {
+            (Vec3::new(0.0, d.y, 0.0), Vec3::new(0.0, 0.0, d.z))
+        } else if d.y == 0.0 && d.x != 0.0 && d.z != 0.0 {
+            (Vec3::new(0.0, 0.0, d.z), Vec3::new(d.x, 0.0, 0.0))
+        } else if d.z == 0.0 && d.x != 0.0 && d.y != 0.0 {
+            (Vec3::new(d.x, 0.0, 0.0), Vec3::new(0.0, d.y, 0.0))
+        } else {
+            return Err(format!(
+                "The corners {:?} and {:?} aren't an axis-aligned rectangle",
+                a, b
+            ));
+        };
+
+        // the edges can point backward when the corners are swapped, which
+        // would flip the face
+        let n = vector::cross(&u, &v);
+        let (corner, u) = if n.x + n.y + n.z < 0.0 {
+            (a + u, -u)
+        } else {
+            (a, u)
+        };
+
+        Ok(Quad {
+            corner,
+            u,
+            v,
+            material,
+        })
+    }
+
+    /// Returns the area of the quad.
+    pub fn area(&self) -> f32 {
+        vector::cross(&self.u, &self.v).length()
+    }
+}
+
+#[typetag::serde]
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (t, alpha, beta) = intersect_quad(r, &self.corner, &self.u, &self.v, t_min, t_max)?;
+
+        let mut rec = HitRecord {
+            p: r.at(t),
+            t,
+            normal: Vec3::default(),
+            geometric_normal: Vec3::default(),
+            front_face: false,
+            uv: (alpha, beta),
+            material: &self.material,
+        };
+        let outward_normal = vector::unit_vector(&vector::cross(&self.u, &self.v));
+        rec.set_face_normal(r, &outward_normal);
+
+        Some(rec)
+    }
+
+    fn bounds(&self) -> Aabb {
+        let mut b = Aabb::empty();
+        for p in [
+            self.corner,
+            self.corner + self.u,
+            self.corner + self.v,
+            self.corner + self.u + self.v,
+        ]
+        .iter()
+        {
+            b.expand_point(*p);
+        }
+        b
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        vec![&self.material]
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn random_direction(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let (u1, u2) = sampler.get_2d();
+        let p = self.corner + u1 * self.u + u2 * self.v;
+        Some(p - *origin)
+    }
+
+    fn pdf_value(&self, origin: &Point3, dir: &Vec3) -> f32 {
+        let r = Ray::new(*origin, *dir);
+        match intersect_quad(&r, &self.corner, &self.u, &self.v, 0.001, f32::INFINITY) {
+            Some((t, _, _)) => {
+                super::area_pdf(&vector::cross(&self.u, &self.v), t, dir, self.area())
+            }
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+    use crate::renderer::sampler::IndependentSampler;
+
+    fn white() -> Material {
+        Material::Lambert {
+            albedo: color::WHITE.into(),
+        }
+    }
+
+    #[test]
+    fn test_quad_hit() {
+        // a parallelogram leaning to the right
+        let quad = Quad {
+            corner: Point3::new(-1.0, -1.0, -2.0),
+            u: Vec3::new(2.0, 0.0, 0.0),
+            v: Vec3::new(1.0, 2.0, 0.0),
+            material: white(),
+        };
+
+        let r = Ray::new(Point3::new(0.5, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = quad.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert_eq!(rec.t, 2.0);
+        assert_eq!(rec.normal.z, 1.0);
+        assert!(rec.front_face);
+        assert!((rec.uv.0 - 0.5).abs() < 1e-5);
+        assert!((rec.uv.1 - 0.5).abs() < 1e-5);
+
+        // left of the slanted edge
+        let r = Ray::new(Point3::new(-0.9, 0.9, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(quad.hit(&r, 0.001, f32::INFINITY).is_none());
+
+        let b = quad.bounds();
+        assert_eq!(b.box_min.x, -1.0);
+        assert_eq!(b.box_max.x, 2.0);
+        assert_eq!(b.box_max.y, 1.0);
+    }
+
+    #[test]
+    fn test_quad_rect() {
+        // a floor, facing up whichever opposite corners are given
+        for (x, z) in [(-1.0, -1.0), (1.0, 1.0), (-1.0, 1.0), (1.0, -1.0)].iter() {
+            let rect = Quad::rect(
+                Point3::new(*x, 0.0, *z),
+                Point3::new(-*x, 0.0, -*z),
+                white(),
+            )
+            .unwrap();
+            assert_eq!(rect.area(), 4.0);
+
+            let r = Ray::new(Point3::new(0.5, 1.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+            let rec = rect.hit(&r, 0.001, f32::INFINITY).unwrap();
+            assert!(rec.front_face);
+            assert_eq!(rec.normal.y, 1.0);
+        }
+
+        assert!(Quad::rect(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 1.0),
+            white()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_quad_sampling() {
+        let quad = Quad {
+            corner: Point3::new(-1.0, -1.0, -1.0),
+            u: Vec3::new(2.0, 0.0, 0.0),
+            v: Vec3::new(0.0, 2.0, 0.0),
+            material: Material::Emissive {
+                color: color::WHITE,
+                intensity: 1.0,
+            },
+        };
+        assert!(quad.is_emissive());
+
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let mut sampler = IndependentSampler::new(0);
+        for _ in 0..100 {
+            let dir = quad.random_direction(&origin, &mut sampler).unwrap();
+            assert!(quad
+                .hit(&Ray::new(origin, dir), 0.001, f32::INFINITY)
+                .is_some());
+        }
+
+        // the same as the two triangle mesh of the same square
+        let pdf = quad.pdf_value(&origin, &Vec3::new(0.1, 0.2, -1.0));
+        let expected = (1.0 + 0.01 + 0.04) / ((1.0 / 1.05_f32.sqrt()) * 4.0);
+        assert!((pdf - expected).abs() < 1e-4);
+        assert_eq!(quad.pdf_value(&origin, &Vec3::new(0.0, 0.0, 1.0)), 0.0);
+    }
+}
@@ -0,0 +1,259 @@
+//! The parts shared by the quadric surfaces of revolution: the cylinder,
+//! the cone, the paraboloid and the hyperboloid.
+//!
+//! Each of them is placed in the scene by the center of its base and the
+//! axis it's revolved around. In its own space, the base is at the origin
+//! and the axis is z, and the surface is x^2 + y^2 = f(z) for a quadratic
+//! f, between z = 0 and the height. Like pbrt's quadrics, the surface can
+//! be swept only part of the way around the axis with `phi_max`.
+
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector;
+use crate::renderer::core::vector::{Point3, Vec3};
+
+use crate::renderer::scene::hittable::HitRecord;
+use crate::renderer::scene::materials::Material;
+
+use std::f32::consts::PI;
+
+/// The default sweep of the surfaces, all of the way around.
+pub(crate) fn full_sweep() -> f32 {
+    360.0
+}
+
+/// The space of an object with its base at the origin and its axis along z.
+pub(crate) struct Frame {
+    origin: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Frame {
+    /// Creates the frame of an object from the center of its base and its
+    /// axis, which doesn't need to be normalized.
+    pub(crate) fn new(base: Point3, axis: &Vec3) -> Frame {
+        let w = vector::unit_vector(axis);
+        let (u, v) = vector::orthonormal_basis(&w);
+        Frame {
+            origin: base,
+            u,
+            v,
+            w,
+        }
+    }
+
+    /// Takes a point in the world into the frame.
+    pub(crate) fn point_to_local(&self, p: &Point3) -> Point3 {
+        self.vector_to_local(&(*p - self.origin))
+    }
+
+    /// Takes a direction in the world into the frame.
+    pub(crate) fn vector_to_local(&self, d: &Vec3) -> Vec3 {
+        Vec3::new(
+            vector::dot(d, &self.u),
+            vector::dot(d, &self.v),
+            vector::dot(d, &self.w),
+        )
+    }
+
+    /// Takes a direction in the frame into the world.
+    pub(crate) fn vector_to_world(&self, d: &Vec3) -> Vec3 {
+        d.x * self.u + d.y * self.v + d.z * self.w
+    }
+
+    /// Returns the world space box around a box in the frame.
+    pub(crate) fn bounds(&self, min: Point3, max: Point3) -> Aabb {
+        let mut b = Aabb::empty();
+        for corner in 0..8 {
+            let p = Vec3::new(
+                if corner & 1 == 0 { min.x } else { max.x },
+                if corner & 2 == 0 { min.y } else { max.y },
+                if corner & 4 == 0 { min.z } else { max.z },
+            );
+            b.expand_point(self.origin + self.vector_to_world(&p));
+        }
+        b
+    }
+}
+
+/// Returns the angle of the point around the z axis, from 0 to 2 pi, if
+/// it's inside of the sweep. The sweep is in radians.
+pub(crate) fn sweep_angle(p: &Point3, phi_max: f32) -> Option<f32> {
+    let phi = p.y.atan2(p.x);
+    let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+    if phi > phi_max {
+        return None;
+    }
+
+    Some(phi)
+}
+
+/// Returns the real roots of a t^2 + b t + c = 0 in increasing order, or
+/// the single root twice when a is 0.
+pub(crate) fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        return Some((-c / b, -c / b));
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    // avoids the cancellation of the textbook formula
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some(if t0 < t1 { (t0, t1) } else { (t1, t0) })
+}
+
+/// A flat end closing a surface of revolution in its own space.
+#[derive(Copy, Clone)]
+pub(crate) struct Cap {
+    pub z: f32,
+    pub radius: f32,
+}
+
+/// A surface of revolution x^2 + y^2 = a z^2 + b z + c in its own space,
+/// from z = 0 up to the height.
+///
+/// Fields:
+/// * `a`, `b`, `c` - The coefficients of the squared radius at z.
+/// * `height` - Where the surface ends along the axis.
+/// * `phi_max` - The sweep around the axis, in radians.
+/// * `caps` - The ends, when the surface is closed.
+pub(crate) struct Revolution {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub height: f32,
+    pub phi_max: f32,
+    pub caps: [Option<Cap>; 2],
+}
+
+impl Revolution {
+    /// Finds the closest hit of the ray with the surface and its caps. The
+    /// u texture coordinate goes around the axis. On the surface, v goes up
+    /// the axis from 0 to 1, and on the caps, it goes from the rim at 0 to
+    /// the center at 1.
+    pub(crate) fn hit<'a>(
+        &self,
+        frame: &Frame,
+        r: &Ray,
+        t_min: f32,
+        t_max: f32,
+        material: &'a Material,
+    ) -> Option<HitRecord<'a>> {
+        let o = frame.point_to_local(&r.orig);
+        let d = frame.vector_to_local(&r.dir);
+
+        // the closest hit so far, with the normal and uv in the frame
+        let mut closest: Option<(f32, Vec3, (f32, f32))> = None;
+        let mut t_max = t_max;
+
+        let qa = d.x * d.x + d.y * d.y - self.a * d.z * d.z;
+        let qb = 2.0 * (o.x * d.x + o.y * d.y - self.a * o.z * d.z) - self.b * d.z;
+        let qc = o.x * o.x + o.y * o.y - self.a * o.z * o.z - self.b * o.z - self.c;
+        if let Some((t0, t1)) = solve_quadratic(qa, qb, qc) {
+            for t in [t0, t1].iter() {
+                if *t < t_min || t_max < *t {
+                    continue;
+                }
+                let p = o + *t * d;
+                if p.z < 0.0 || p.z > self.height {
+                    continue;
+                }
+                if let Some(phi) = sweep_angle(&p, self.phi_max) {
+                    // the gradient of x^2 + y^2 - f(z) points outward
+                    let normal = Vec3::new(p.x, p.y, -0.5 * (2.0 * self.a * p.z + self.b));
+                    closest = Some((*t, normal, (phi / self.phi_max, p.z / self.height)));
+                    t_max = *t;
+                    break;
+                }
+            }
+        }
+
+        for (cap, side) in self.caps.iter().zip([-1.0, 1.0].iter()) {
+            let cap = match cap {
+                Some(cap) if d.z != 0.0 => cap,
+                _ => continue,
+            };
+            let t = (cap.z - o.z) / d.z;
+            if t < t_min || t_max < t {
+                continue;
+            }
+            let p = o + t * d;
+            let distance = (p.x * p.x + p.y * p.y).sqrt();
+            if distance > cap.radius {
+                continue;
+            }
+            if let Some(phi) = sweep_angle(&p, self.phi_max) {
+                let normal = Vec3::new(0.0, 0.0, *side);
+                closest = Some((t, normal, (phi / self.phi_max, 1.0 - distance / cap.radius)));
+                t_max = t;
+            }
+        }
+
+        let (t, normal, uv) = closest?;
+        let mut rec = HitRecord {
+            p: r.at(t),
+            t,
+            normal: Vec3::default(),
+            geometric_normal: Vec3::default(),
+            front_face: false,
+            uv,
+            material,
+        };
+        let outward_normal = vector::unit_vector(&frame.vector_to_world(&normal));
+        rec.set_face_normal(r, &outward_normal);
+
+        Some(rec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame() {
+        let frame = Frame::new(Point3::new(1.0, 2.0, 3.0), &Vec3::new(0.0, 2.0, 0.0));
+
+        // the axis is z in the frame
+        let p = frame.point_to_local(&Point3::new(1.0, 5.0, 3.0));
+        assert!(p.x.abs() < 1e-6 && p.y.abs() < 1e-6);
+        assert!((p.z - 3.0).abs() < 1e-6);
+
+        let d = Vec3::new(0.3, -0.2, 0.9);
+        let back = frame.vector_to_world(&frame.vector_to_local(&d));
+        assert!((back - d).length() < 1e-6);
+
+        let b = frame.bounds(Point3::new(-1.0, -1.0, 0.0), Point3::new(1.0, 1.0, 4.0));
+        assert!((b.box_min.y - 2.0).abs() < 1e-5);
+        assert!((b.box_max.y - 6.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_solve_quadratic() {
+        let (t0, t1) = solve_quadratic(1.0, -3.0, 2.0).unwrap();
+        assert_eq!((t0, t1), (1.0, 2.0));
+        let (t0, t1) = solve_quadratic(-1.0, 3.0, -2.0).unwrap();
+        assert_eq!((t0, t1), (1.0, 2.0));
+        assert_eq!(solve_quadratic(0.0, 2.0, -4.0), Some((2.0, 2.0)));
+        assert!(solve_quadratic(1.0, 0.0, 1.0).is_none());
+        assert!(solve_quadratic(0.0, 0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn test_sweep_angle() {
+        let half = PI;
+        assert!(sweep_angle(&Point3::new(0.0, 1.0, 0.0), half).is_some());
+        assert!(sweep_angle(&Point3::new(0.0, -1.0, 0.0), half).is_none());
+        let phi = sweep_angle(&Point3::new(0.0, -1.0, 0.0), 2.0 * PI).unwrap();
+        assert!((phi - 1.5 * PI).abs() < 1e-5);
+    }
+}
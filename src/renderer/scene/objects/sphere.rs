@@ -1,12 +1,14 @@
 use crate::renderer::core::aabb::Aabb;
 use crate::renderer::core::ray::Ray;
 use crate::renderer::core::vector;
-use crate::renderer::core::vector::Point3;
+use crate::renderer::core::vector::{Point3, Vec3};
+use crate::renderer::sampler::Sampler;
 
 use crate::renderer::scene::hittable::{HitRecord, Hittable};
 use crate::renderer::scene::materials::Material;
 
 use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
 
 /// An implementation of a sphere form as a hittable object.
 /// Implements the Hittable trait.
@@ -17,50 +19,134 @@ pub struct Sphere {
     pub material: Material,
 }
 
-#[typetag::serde]
-impl Hittable for Sphere {
-    /// Calculates a sphere hit.
-    /// Solves dot((r.orig + t * r.dir - center), (r.orig + t * r.dir - center)) = r^2 to do so.
+/// Calculates a sphere hit.
+/// Solves dot((r.orig + t * r.dir - center), (r.orig + t * r.dir - center)) = r^2 to do so.
+pub(crate) fn hit_sphere<'a>(
+    center: &Point3,
+    radius: f32,
+    material: &'a Material,
+    r: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord<'a>> {
+    let oc = r.orig - *center;
+    let a = r.dir.length_squared();
+    let half_b = vector::dot(&oc, &r.dir);
+    let c = oc.length_squared() - radius * radius;
 
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let oc = r.orig - self.center;
-        let a = r.dir.length_squared();
-        let half_b = vector::dot(&oc, &r.dir);
-        let c = oc.length_squared() - self.radius * self.radius;
+    // slightly optimized quadric solve
+    let discriminant = half_b * half_b - a * c;
 
-        // slightly optimized quadric solve
-        let discriminant = half_b * half_b - a * c;
-
-        if discriminant < 0.0 {
-            return None;
-        }
+    if discriminant < 0.0 {
+        return None;
+    }
 
-        let sqrtd = f32::sqrt(discriminant);
+    let sqrtd = f32::sqrt(discriminant);
 
-        let mut root = (-half_b - sqrtd) / a;
+    let mut root = (-half_b - sqrtd) / a;
+    if root < t_min || t_max < root {
+        root = (-half_b + sqrtd) / a;
         if root < t_min || t_max < root {
-            root = (-half_b + sqrtd) / a;
-            if root < t_min || t_max < root {
-                return None;
-            }
+            return None;
         }
+    }
 
-        // calculate the hit record
-        let mut rec = HitRecord {
-            p: r.at(root),
-            t: root,
-            normal: vector::Vec3::default(),
-            front_face: false,
-            material: &self.material,
-        };
-        let outward_normal = (rec.p - self.center) / self.radius;
-        rec.set_face_normal(r, &outward_normal);
+    // calculate the hit record
+    let mut rec = HitRecord {
+        p: r.at(root),
+        t: root,
+        normal: vector::Vec3::default(),
+        geometric_normal: vector::Vec3::default(),
+        front_face: false,
+        uv: (0.0, 0.0),
+        material,
+    };
+    let outward_normal = (rec.p - *center) / radius;
+    rec.set_face_normal(r, &outward_normal);
+    rec.uv = sphere_uv(&((rec.p - *center) / radius.abs()));
+
+    Some(rec)
+}
 
-        Some(rec)
+#[typetag::serde]
+impl Hittable for Sphere {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        hit_sphere(&self.center, self.radius, &self.material, r, t_min, t_max)
     }
 
     fn bounds(&self) -> Aabb {
-        Aabb::new(self.center - self.radius, self.center + self.radius)
+        // a negative radius is used to flip the normals for hollow spheres,
+        // the bounds have to cover the same volume either way.
+        let radius = self.radius.abs();
+        Aabb::new(self.center - radius, self.center + radius)
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        vec![&self.material]
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    /// Samples the cone of directions from the origin that hit the sphere.
+    /// The sphere can't be sampled from the inside.
+    fn random_direction(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let direction = self.center - *origin;
+        let distance_squared = direction.length_squared();
+        let one_minus_cos_max = self.one_minus_cos_theta_max(distance_squared)?;
+
+        let (r1, r2) = sampler.get_2d();
+
+        // uniformly sample the spherical cap of the cone around the z axis
+        let z = 1.0 - r2 * one_minus_cos_max;
+        let sin_theta = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * r1;
+
+        let w = vector::unit_vector(&direction);
+        let (u, v) = vector::orthonormal_basis(&w);
+
+        Some(sin_theta * phi.cos() * u + sin_theta * phi.sin() * v + z * w)
+    }
+
+    fn pdf_value(&self, origin: &Point3, dir: &Vec3) -> f32 {
+        if self
+            .hit(&Ray::new(*origin, *dir), 0.001, f32::INFINITY)
+            .is_none()
+        {
+            return 0.0;
+        }
+
+        let distance_squared = (self.center - *origin).length_squared();
+        match self.one_minus_cos_theta_max(distance_squared) {
+            Some(one_minus_cos_max) => 1.0 / (2.0 * PI * one_minus_cos_max),
+            None => 0.0,
+        }
+    }
+}
+
+/// Calculates the texture coordinates of a point on the unit sphere. u
+/// goes around the y axis starting from -x, and v goes from the bottom
+/// of the sphere to the top.
+fn sphere_uv(p: &Point3) -> (f32, f32) {
+    let theta = (-p.y).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z).atan2(p.x) + PI;
+
+    (phi / (2.0 * PI), theta / PI)
+}
+
+impl Sphere {
+    /// Returns 1 - cos of the half angle of the cone from a point at the
+    /// squared distance to the center that contains the sphere, or None if
+    /// the point is inside of the sphere. Written to avoid cancellation for
+    /// small, far away spheres.
+    fn one_minus_cos_theta_max(&self, distance_squared: f32) -> Option<f32> {
+        let sin2_theta_max = self.radius * self.radius / distance_squared;
+        if sin2_theta_max >= 1.0 {
+            return None;
+        }
+
+        Some(sin2_theta_max / (1.0 + (1.0 - sin2_theta_max).sqrt()))
     }
 }
 
@@ -68,6 +154,7 @@ impl Hittable for Sphere {
 mod tests {
     use super::*;
     use crate::renderer::core::{color, vector::Vec3};
+    use crate::renderer::sampler::IndependentSampler;
 
     #[test]
     fn test_sphere_hit() {
@@ -75,7 +162,7 @@ mod tests {
             center: Point3::new(0.0, 0.0, -10.0),
             radius: 5.0,
             material: Material::Lambert {
-                albedo: color::WHITE,
+                albedo: color::WHITE.into(),
             },
         };
         let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
@@ -104,4 +191,66 @@ mod tests {
         let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(10.0, 10.0, -1.0));
         assert!(s1.hit(&r, 0.001, f32::INFINITY).is_none());
     }
+
+    #[test]
+    fn test_sphere_uv() {
+        let check = |p: Point3, u: f32, v: f32| {
+            let uv = sphere_uv(&p);
+            assert!((uv.0 - u).abs() < 1e-5);
+            assert!((uv.1 - v).abs() < 1e-5);
+        };
+
+        check(Point3::new(1.0, 0.0, 0.0), 0.5, 0.5);
+        check(Point3::new(-1.0, 0.0, 0.0), 0.0, 0.5);
+        check(Point3::new(0.0, 1.0, 0.0), 0.5, 1.0);
+        check(Point3::new(0.0, -1.0, 0.0), 0.5, 0.0);
+        check(Point3::new(0.0, 0.0, 1.0), 0.25, 0.5);
+        check(Point3::new(0.0, 0.0, -1.0), 0.75, 0.5);
+
+        // the hit fills in the coordinates
+        let s1 = Sphere {
+            center: Point3::new(0.0, 0.0, -2.0),
+            radius: 1.0,
+            material: Material::Lambert {
+                albedo: color::WHITE.into(),
+            },
+        };
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = s1.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.uv.0 - 0.25).abs() < 1e-5);
+        assert!((rec.uv.1 - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_sphere_sampling() {
+        let s1 = Sphere {
+            center: Point3::new(0.0, 0.0, -10.0),
+            radius: 5.0,
+            material: Material::Emissive {
+                color: color::WHITE,
+                intensity: 1.0,
+            },
+        };
+        assert!(s1.is_emissive());
+
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let mut sampler = IndependentSampler::new(0);
+
+        // the cone has a half angle of 30 degrees
+        let expected_pdf = 1.0 / (2.0 * PI * (1.0 - 0.75_f32.sqrt()));
+
+        for _ in 0..100 {
+            let dir = s1.random_direction(&origin, &mut sampler).unwrap();
+            let r = Ray::new(origin, dir);
+            assert!(s1.hit(&r, 0.001, f32::INFINITY).is_some());
+            assert!((s1.pdf_value(&origin, &dir) - expected_pdf).abs() < 1e-3);
+        }
+
+        assert_eq!(s1.pdf_value(&origin, &Vec3::new(0.0, 1.0, 0.0)), 0.0);
+
+        // no sampling from the inside
+        assert!(s1
+            .random_direction(&Point3::new(0.0, 0.0, -9.0), &mut sampler)
+            .is_none());
+    }
 }
@@ -0,0 +1,357 @@
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector;
+use crate::renderer::core::vector::{Point3, Vec3};
+
+use crate::renderer::scene::hittable::{HitRecord, Hittable};
+use crate::renderer::scene::materials::Material;
+
+use super::quadric::{full_sweep, sweep_angle, Frame};
+
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// Coefficients smaller than this are treated as 0 by the polynomial
+/// solvers.
+const EPSILON: f64 = 1e-12;
+
+/// A torus, a tube bent into a ring around an axis.
+///
+/// Fields:
+/// * `center` - The center of the ring.
+/// * `axis` - The axis the ring goes around.
+/// * `major_radius` - The distance from the center to the middle of the
+///   tube.
+/// * `minor_radius` - The radius of the tube.
+/// * `phi_max` - How far around the axis the ring goes, in degrees. 360
+///   by default. The ends of a partial ring are open.
+/// * `material` - The material of the surface.
+///
+/// The u texture coordinate goes around the ring, and v around the tube,
+/// starting from the outside.
+#[derive(Serialize, Deserialize)]
+pub struct Torus {
+    pub center: Point3,
+    pub axis: Vec3,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+    #[serde(default = "full_sweep")]
+    pub phi_max: f32,
+    pub material: Material,
+}
+
+#[typetag::serde]
+impl Hittable for Torus {
+    /// Solves (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2) for the point p
+    /// along the ray in the space of the torus, a quartic in t. The solve
+    /// is in double precision, starting from where the ray enters the
+    /// sphere around the torus, to keep the coefficients from losing the
+    /// hits of far away rays.
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let frame = Frame::new(self.center, &self.axis);
+        let o = frame.point_to_local(&r.orig);
+        let d = frame.vector_to_local(&r.dir);
+
+        let major = self.major_radius as f64;
+        let minor = self.minor_radius as f64;
+
+        // skip ahead to the bounding sphere
+        let bound = major + minor;
+        let (ox, oy, oz) = (o.x as f64, o.y as f64, o.z as f64);
+        let (dx, dy, dz) = (d.x as f64, d.y as f64, d.z as f64);
+        let dd = dx * dx + dy * dy + dz * dz;
+        let od = ox * dx + oy * dy + oz * dz;
+        let oo = ox * ox + oy * oy + oz * oz;
+        let discriminant = od * od - dd * (oo - bound * bound);
+        if discriminant < 0.0 {
+            return None;
+        }
+        let start = ((-od - discriminant.sqrt()) / dd).max(0.0);
+
+        let (ox, oy, oz) = (ox + start * dx, oy + start * dy, oz + start * dz);
+        let od = ox * dx + oy * dy + oz * dz;
+        let oo = ox * ox + oy * oy + oz * oz;
+        let k = oo + major * major - minor * minor;
+        let four_r2 = 4.0 * major * major;
+
+        let coefficients = [
+            k * k - four_r2 * (ox * ox + oy * oy),
+            4.0 * od * k - 2.0 * four_r2 * (ox * dx + oy * dy),
+            4.0 * od * od + 2.0 * dd * k - four_r2 * (dx * dx + dy * dy),
+            4.0 * dd * od,
+            dd * dd,
+        ];
+
+        let mut roots = solve_quartic(&coefficients);
+        roots.sort_by(|a, b| a.total_cmp(b));
+
+        let phi_max = self.phi_max.to_radians();
+        for root in roots {
+            let t = (root + start) as f32;
+            if t < t_min || t_max < t {
+                continue;
+            }
+
+            let p = o + t * d;
+            let phi = match sweep_angle(&p, phi_max) {
+                Some(phi) => phi,
+                None => continue,
+            };
+
+            // the normal points away from the middle of the tube
+            let radial = (p.x * p.x + p.y * p.y).sqrt();
+            let middle = if radial > 0.0 {
+                Vec3::new(p.x, p.y, 0.0) * (self.major_radius / radial)
+            } else {
+                Vec3::new(self.major_radius, 0.0, 0.0)
+            };
+            let theta = p.z.atan2(radial - self.major_radius);
+            let theta = if theta < 0.0 { theta + 2.0 * PI } else { theta };
+
+            let mut rec = HitRecord {
+                p: r.at(t),
+                t,
+                normal: Vec3::default(),
+                geometric_normal: Vec3::default(),
+                front_face: false,
+                uv: (phi / phi_max, theta / (2.0 * PI)),
+                material: &self.material,
+            };
+            let outward_normal = vector::unit_vector(&frame.vector_to_world(&(p - middle)));
+            rec.set_face_normal(r, &outward_normal);
+
+            return Some(rec);
+        }
+
+        None
+    }
+
+    fn bounds(&self) -> Aabb {
+        let radius = self.major_radius + self.minor_radius;
+        Frame::new(self.center, &self.axis).bounds(
+            Point3::new(-radius, -radius, -self.minor_radius),
+            Point3::new(radius, radius, self.minor_radius),
+        )
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        vec![&self.material]
+    }
+}
+
+/// Returns the real roots of c[3] x^3 + c[2] x^2 + c[1] x + c[0] = 0,
+/// with Cardano's formula after Schwarze, "Cubic and Quartic Roots",
+/// Graphics Gems 1990.
+fn solve_cubic(c: &[f64; 4]) -> Vec<f64> {
+    // the normal form x^3 + a x^2 + b x + c = 0
+    let a = c[2] / c[3];
+    let b = c[1] / c[3];
+    let c = c[0] / c[3];
+
+    // substitute x = y - a / 3 to eliminate the quadric term,
+    // y^3 + 3 p y + 2 q = 0
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + c) / 2.0;
+
+    let cb_p = p * p * p;
+    let discriminant = q * q + cb_p;
+
+    let mut roots = if discriminant.abs() < EPSILON {
+        if q.abs() < EPSILON {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if discriminant < 0.0 {
+        // three real roots
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        vec![
+            t * phi.cos(),
+            -t * (phi + std::f64::consts::PI / 3.0).cos(),
+            -t * (phi - std::f64::consts::PI / 3.0).cos(),
+        ]
+    } else {
+        // one real root
+        let sqrt_d = discriminant.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+
+    for root in roots.iter_mut() {
+        *root -= a / 3.0;
+    }
+    roots
+}
+
+/// Returns the real roots of the quadratic c[2] x^2 + c[1] x + c[0] = 0
+/// in double precision.
+fn solve_quadratic(c: &[f64; 3]) -> Vec<f64> {
+    let p = c[1] / (2.0 * c[2]);
+    let q = c[0] / c[2];
+    let discriminant = p * p - q;
+
+    if discriminant.abs() < EPSILON {
+        vec![-p]
+    } else if discriminant < 0.0 {
+        Vec::new()
+    } else {
+        let sqrt_d = discriminant.sqrt();
+        vec![sqrt_d - p, -sqrt_d - p]
+    }
+}
+
+/// Returns the real roots of the quartic with the coefficients from the
+/// constant term up, with Ferrari's method after Schwarze, "Cubic and
+/// Quartic Roots", Graphics Gems 1990. The roots are polished with a few
+/// Newton steps, which the closed form needs in single precision scenes.
+fn solve_quartic(c: &[f64; 5]) -> Vec<f64> {
+    // the normal form x^4 + a x^3 + b x^2 + c x + d = 0
+    let a = c[3] / c[4];
+    let b = c[2] / c[4];
+    let cc = c[1] / c[4];
+    let d = c[0] / c[4];
+
+    // substitute x = y - a / 4 to eliminate the cubic term,
+    // y^4 + p y^2 + q y + r = 0
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + cc;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * cc / 4.0 + d;
+
+    let mut roots = if r.abs() < EPSILON {
+        // no absolute term, y (y^3 + p y + q) = 0
+        let mut roots = solve_cubic(&[q, p, 0.0, 1.0]);
+        roots.push(0.0);
+        roots
+    } else {
+        // solve the resolvent cubic and take one real root to build two
+        // quadratics from
+        let z = solve_cubic(&[r * p / 2.0 - q * q / 8.0, -r, -p / 2.0, 1.0])[0];
+
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if u.abs() < EPSILON {
+            0.0
+        } else if u > 0.0 {
+            u.sqrt()
+        } else {
+            return Vec::new();
+        };
+        let v = if v.abs() < EPSILON {
+            0.0
+        } else if v > 0.0 {
+            v.sqrt()
+        } else {
+            return Vec::new();
+        };
+
+        let v = if q < 0.0 { -v } else { v };
+        let mut roots = solve_quadratic(&[z - u, v, 1.0]);
+        roots.extend(solve_quadratic(&[z + u, -v, 1.0]));
+        roots
+    };
+
+    let evaluate = |x: f64| (((c[4] * x + c[3]) * x + c[2]) * x + c[1]) * x + c[0];
+    let derivative = |x: f64| ((4.0 * c[4] * x + 3.0 * c[3]) * x + 2.0 * c[2]) * x + c[1];
+    for root in roots.iter_mut() {
+        *root -= a / 4.0;
+        for _ in 0..2 {
+            let slope = derivative(*root);
+            if slope.abs() > EPSILON {
+                *root -= evaluate(*root) / slope;
+            }
+        }
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+
+    fn test_torus(phi_max: f32) -> Torus {
+        Torus {
+            center: Point3::new(0.0, 0.0, -5.0),
+            axis: Vec3::new(0.0, 1.0, 0.0),
+            major_radius: 2.0,
+            minor_radius: 0.5,
+            phi_max,
+            material: Material::Lambert {
+                albedo: color::WHITE.into(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_solve_quartic() {
+        // (x - 1)(x - 2)(x - 3)(x + 4)
+        let mut roots = solve_quartic(&[-24.0, 38.0, -13.0, -2.0, 1.0]);
+        roots.sort_by(|a, b| a.total_cmp(b));
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip([-4.0, 1.0, 2.0, 3.0].iter()) {
+            assert!((root - expected).abs() < 1e-9);
+        }
+
+        // x^4 + 1 has no real roots
+        assert!(solve_quartic(&[1.0, 0.0, 0.0, 0.0, 1.0]).is_empty());
+
+        let mut roots = solve_cubic(&[-6.0, 11.0, -6.0, 1.0]);
+        roots.sort_by(|a, b| a.total_cmp(b));
+        assert_eq!(roots.len(), 3);
+        assert!((roots[2] - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_torus_hit() {
+        let torus = test_torus(360.0);
+
+        // straight through the ring in the plane of the torus, the outside
+        // of the tube comes first
+        let r = Ray::new(Point3::new(-5.0, 0.0, -5.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = torus.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 2.5).abs() < 1e-4);
+        assert!((rec.normal.x + 1.0).abs() < 1e-4);
+        assert!(rec.front_face);
+
+        // the next hit is leaving the tube on the inside of the ring
+        let rec = torus.hit(&r, rec.t + 0.01, f32::INFINITY).unwrap();
+        assert!((rec.t - 3.5).abs() < 1e-4);
+        assert!(!rec.front_face);
+
+        // down the axis through the hole
+        let r = Ray::new(Point3::new(0.0, 5.0, -5.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(torus.hit(&r, 0.001, f32::INFINITY).is_none());
+
+        // down onto the top of the tube
+        let r = Ray::new(Point3::new(2.0, 5.0, -5.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = torus.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.t - 4.5).abs() < 1e-4);
+        assert!((rec.normal.y - 1.0).abs() < 1e-4);
+
+        // far away rays still find it
+        let r = Ray::new(Point3::new(2.0, 5000.0, -5.0), Vec3::new(0.0, -1.0, 0.0));
+        let rec = torus.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.p.y - 0.5).abs() < 1e-2);
+
+        let b = torus.bounds();
+        assert!((b.box_max.x - 2.5).abs() < 1e-5);
+        assert!((b.box_max.y - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_torus_sweep() {
+        // half of the ring stays, on one side of the axis
+        let torus = test_torus(180.0);
+        let hits = [-7.0, -3.0]
+            .iter()
+            .filter(|z| {
+                let r = Ray::new(Point3::new(0.0, 5.0, **z), Vec3::new(0.0, -1.0, 0.0));
+                torus.hit(&r, 0.001, f32::INFINITY).is_some()
+            })
+            .count();
+        assert_eq!(hits, 1);
+    }
+}
@@ -0,0 +1,345 @@
+use crate::renderer::core::aabb::Aabb;
+use crate::renderer::core::ray::Ray;
+use crate::renderer::core::vector;
+use crate::renderer::core::vector::{Point3, Vec3};
+use crate::renderer::sampler::Sampler;
+
+use crate::renderer::scene::hittable::{HitRecord, Hittable};
+use crate::renderer::scene::materials::Material;
+
+use serde::{Deserialize, Serialize};
+
+/// Determinants smaller than this are treated as the ray being parallel
+/// to the triangle.
+const PARALLEL_EPSILON: f32 = 1e-8;
+
+/// An implementation of a single triangle as a hittable object. For
+/// anything with more than a handful of triangles, use a TriangleMesh
+/// instead.
+///
+/// Fields:
+/// * `vertices` - The three corners of the triangle. The winding order
+///   determines the front face, counter-clockwise is the front.
+/// * `normals` - Optional per vertex normals used for smooth shading.
+/// * `uvs` - Optional per vertex texture coordinates.
+/// * `material` - The material of the surface.
+#[derive(Serialize, Deserialize)]
+pub struct Triangle {
+    pub vertices: [Point3; 3],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normals: Option<[Vec3; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uvs: Option<[(f32, f32); 3]>,
+    pub material: Material,
+}
+
+#[typetag::serde]
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (t, b1, b2) = intersect(r, &self.vertices, t_min, t_max)?;
+
+        Some(hit_record(
+            r,
+            t,
+            (b1, b2),
+            &self.vertices,
+            self.normals.as_ref(),
+            self.uvs.as_ref(),
+            &self.material,
+        ))
+    }
+
+    fn bounds(&self) -> Aabb {
+        let mut b = Aabb::empty();
+        for v in self.vertices.iter() {
+            b.expand_point(*v);
+        }
+        b
+    }
+
+    fn materials(&self) -> Vec<&Material> {
+        vec![&self.material]
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn random_direction(&self, origin: &Point3, sampler: &mut dyn Sampler) -> Option<Vec3> {
+        let (u1, u2) = sampler.get_2d();
+        let p = sample_point(&self.vertices, u1, u2);
+        Some(p - *origin)
+    }
+
+    fn pdf_value(&self, origin: &Point3, dir: &Vec3) -> f32 {
+        match intersect(
+            &Ray::new(*origin, *dir),
+            &self.vertices,
+            0.001,
+            f32::INFINITY,
+        ) {
+            Some((t, _, _)) => area_pdf(&self.vertices, t, dir, area(&self.vertices)),
+            None => 0.0,
+        }
+    }
+}
+
+/// Returns the area of the triangle.
+pub(crate) fn area(vertices: &[Point3; 3]) -> f32 {
+    0.5 * vector::cross(&(vertices[1] - vertices[0]), &(vertices[2] - vertices[0])).length()
+}
+
+/// Maps two uniform random numbers in [0, 1) to a uniformly distributed
+/// point on the triangle.
+pub(crate) fn sample_point(vertices: &[Point3; 3], u1: f32, u2: f32) -> Point3 {
+    let su1 = u1.sqrt();
+    let b0 = 1.0 - su1;
+    let b1 = u2 * su1;
+
+    b0 * vertices[0] + b1 * vertices[1] + (1.0 - b0 - b1) * vertices[2]
+}
+
+/// Converts the density of uniformly sampling a point on a surface of the
+/// total area into a density with respect to solid angle, for the point
+/// on the triangle hit at t along the direction.
+pub(crate) fn area_pdf(vertices: &[Point3; 3], t: f32, dir: &Vec3, total_area: f32) -> f32 {
+    let normal = vector::cross(&(vertices[1] - vertices[0]), &(vertices[2] - vertices[0]));
+    super::area_pdf(&normal, t, dir, total_area)
+}
+
+/// Calculates a ray triangle intersection using the Möller–Trumbore
+/// algorithm. Returns the t along the ray and the barycentric coordinates
+/// of the second and third vertices.
+pub(crate) fn intersect(
+    r: &Ray,
+    vertices: &[Point3; 3],
+    t_min: f32,
+    t_max: f32,
+) -> Option<(f32, f32, f32)> {
+    let edge1 = vertices[1] - vertices[0];
+    let edge2 = vertices[2] - vertices[0];
+
+    let pvec = vector::cross(&r.dir, &edge2);
+    let det = vector::dot(&edge1, &pvec);
+
+    // no culling, both faces are hittable
+    if det.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = r.orig - vertices[0];
+    let b1 = vector::dot(&tvec, &pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = vector::cross(&tvec, &edge1);
+    let b2 = vector::dot(&r.dir, &qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = vector::dot(&edge2, &qvec) * inv_det;
+    if t < t_min || t_max < t {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+/// Builds the hit record for a triangle hit. The face is determined by the
+/// geometric normal, and when vertex normals are supplied, the interpolated
+/// normal is used for shading on the same side of the surface. Without
+/// vertex texture coordinates, the corners get (0, 0), (1, 0) and (1, 1).
+pub(crate) fn hit_record<'a>(
+    r: &Ray,
+    t: f32,
+    barycentric: (f32, f32),
+    vertices: &[Point3; 3],
+    normals: Option<&[Vec3; 3]>,
+    uvs: Option<&[(f32, f32); 3]>,
+    material: &'a Material,
+) -> HitRecord<'a> {
+    let (b1, b2) = barycentric;
+    let b0 = 1.0 - b1 - b2;
+
+    let uv = match uvs {
+        Some(uv) => (
+            b0 * uv[0].0 + b1 * uv[1].0 + b2 * uv[2].0,
+            b0 * uv[0].1 + b1 * uv[1].1 + b2 * uv[2].1,
+        ),
+        None => (b1 + b2, b2),
+    };
+
+    let mut rec = HitRecord {
+        p: b0 * vertices[0] + b1 * vertices[1] + b2 * vertices[2],
+        t,
+        normal: Vec3::default(),
+        geometric_normal: Vec3::default(),
+        front_face: false,
+        uv,
+        material,
+    };
+
+    let outward_normal = vector::unit_vector(&vector::cross(
+        &(vertices[1] - vertices[0]),
+        &(vertices[2] - vertices[0]),
+    ));
+    rec.set_face_normal(r, &outward_normal);
+
+    if let Some(n) = normals {
+        let shading = b0 * n[0] + b1 * n[1] + b2 * n[2];
+        if !shading.near_zero() {
+            let shading = vector::unit_vector(&shading);
+            // keep the shading normal on the side of the surface the ray hit
+            rec.normal = if vector::dot(&shading, &rec.normal) < 0.0 {
+                -shading
+            } else {
+                shading
+            };
+        }
+    }
+
+    rec
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+    use crate::renderer::sampler::IndependentSampler;
+
+    fn test_triangle() -> Triangle {
+        Triangle {
+            vertices: [
+                Point3::new(-1.0, -1.0, -2.0),
+                Point3::new(1.0, -1.0, -2.0),
+                Point3::new(0.0, 1.0, -2.0),
+            ],
+            normals: None,
+            uvs: None,
+            material: Material::Lambert {
+                albedo: color::WHITE.into(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_triangle_hit() {
+        let tri = test_triangle();
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = tri.hit(&r, 0.001, f32::INFINITY).unwrap();
+
+        assert_eq!(rec.t, 2.0);
+        assert_eq!(rec.p.x, 0.0);
+        assert_eq!(rec.p.y, 0.0);
+        assert_eq!(rec.p.z, -2.0);
+
+        // counter-clockwise winding faces the ray
+        assert!(rec.front_face);
+        assert_eq!(rec.normal.x, 0.0);
+        assert_eq!(rec.normal.y, 0.0);
+        assert_eq!(rec.normal.z, 1.0);
+
+        // from behind it's the back face, and the normal points back at the ray
+        let r = Ray::new(Point3::new(0.0, 0.0, -4.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = tri.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!(!rec.front_face);
+        assert_eq!(rec.normal.z, -1.0);
+
+        // outside of the edges
+        let r = Ray::new(Point3::new(0.9, 0.9, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(tri.hit(&r, 0.001, f32::INFINITY).is_none());
+
+        // parallel to the triangle
+        let r = Ray::new(Point3::new(0.0, 0.0, -2.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(tri.hit(&r, 0.001, f32::INFINITY).is_none());
+
+        // beyond t_max
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(tri.hit(&r, 0.001, 1.0).is_none());
+    }
+
+    #[test]
+    fn test_triangle_smooth_normals() {
+        let mut tri = test_triangle();
+        tri.normals = Some([
+            Vec3::new(-1.0, 0.0, 1.0),
+            Vec3::new(1.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ]);
+
+        // hitting the first vertex gives back its normal
+        let r = Ray::new(Point3::new(-1.0, -1.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = tri.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.normal.x + 0.5_f32.sqrt()).abs() < 1e-5);
+        assert!((rec.normal.z - 0.5_f32.sqrt()).abs() < 1e-5);
+        assert_eq!(rec.geometric_normal.z, 1.0);
+
+        // in between the first two vertices they cancel out in x
+        let r = Ray::new(Point3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = tri.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!(rec.normal.x.abs() < 1e-5);
+        assert!((rec.normal.z - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_triangle_uv() {
+        let mut tri = test_triangle();
+
+        // the default coordinates at the second vertex
+        let r = Ray::new(Point3::new(1.0, -1.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = tri.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.uv.0 - 1.0).abs() < 1e-5);
+        assert!(rec.uv.1.abs() < 1e-5);
+
+        tri.uvs = Some([(0.0, 0.0), (1.0, 0.0), (0.5, 1.0)]);
+
+        // halfway up the triangle
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = tri.hit(&r, 0.001, f32::INFINITY).unwrap();
+        assert!((rec.uv.0 - 0.5).abs() < 1e-5);
+        assert!((rec.uv.1 - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_triangle_sampling() {
+        let mut tri = test_triangle();
+        tri.material = Material::Emissive {
+            color: color::WHITE,
+            intensity: 1.0,
+        };
+        assert!(tri.is_emissive());
+        assert_eq!(area(&tri.vertices), 2.0);
+
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let mut sampler = IndependentSampler::new(0);
+        for _ in 0..100 {
+            let dir = tri.random_direction(&origin, &mut sampler).unwrap();
+            assert!(tri
+                .hit(&Ray::new(origin, dir), 0.001, f32::INFINITY)
+                .is_some());
+            assert!(tri.pdf_value(&origin, &dir) > 0.0);
+        }
+
+        // straight on at a distance of 2, the pdf is d^2 / (cos * area)
+        let pdf = tri.pdf_value(&origin, &Vec3::new(0.0, 0.0, -1.0));
+        assert!((pdf - 2.0).abs() < 1e-5);
+
+        assert_eq!(tri.pdf_value(&origin, &Vec3::new(0.0, 0.0, 1.0)), 0.0);
+    }
+
+    #[test]
+    fn test_triangle_bounds() {
+        let b = test_triangle().bounds();
+
+        assert_eq!(b.box_min.x, -1.0);
+        assert_eq!(b.box_min.y, -1.0);
+        assert_eq!(b.box_min.z, -2.0);
+        assert_eq!(b.box_max.x, 1.0);
+        assert_eq!(b.box_max.y, 1.0);
+        assert_eq!(b.box_max.z, -2.0);
+    }
+}
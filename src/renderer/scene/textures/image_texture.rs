@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::renderer::core::color::Color;
+use crate::renderer::post::tonemap::srgb_eotf;
+
+/// A texture read from an image file with the image crate. The image is
+/// treated as sRGB and converted to linear colors when it's loaded. Lookups
+/// use the closest pixel and repeat the image outside of [0, 1].
+///
+/// Fields:
+/// * `file` - The path to the image, relative to the working directory.
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "ImageTextureData")]
+pub struct ImageTexture {
+    pub file: String,
+    #[serde(skip)]
+    width: usize,
+    #[serde(skip)]
+    height: usize,
+    #[serde(skip)]
+    pixels: Vec<Color>,
+}
+
+/// The serialized form of an image texture. Deserializing goes through
+/// this so the image gets loaded.
+#[derive(Deserialize)]
+struct ImageTextureData {
+    file: String,
+}
+
+impl TryFrom<ImageTextureData> for ImageTexture {
+    type Error = String;
+
+    fn try_from(data: ImageTextureData) -> Result<Self, Self::Error> {
+        ImageTexture::open(&data.file)
+    }
+}
+
+impl fmt::Debug for ImageTexture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImageTexture")
+            .field("file", &self.file)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish()
+    }
+}
+
+impl ImageTexture {
+    /// Loads the image file as a texture.
+    pub fn open(file: &str) -> Result<ImageTexture, String> {
+        let img = image::open(file)
+            .map_err(|e| format!("Unable to load texture {}: {}", file, e))?
+            .to_rgb8();
+
+        let pixels = img
+            .pixels()
+            .map(|p| {
+                Color::new(
+                    srgb_eotf(p[0] as f32 / 255.0),
+                    srgb_eotf(p[1] as f32 / 255.0),
+                    srgb_eotf(p[2] as f32 / 255.0),
+                )
+            })
+            .collect();
+
+        ImageTexture::new(file, img.width() as usize, img.height() as usize, pixels)
+    }
+
+    /// Creates a texture from linear pixels, in rows from the top of the
+    /// image.
+    pub fn new(
+        file: &str,
+        width: usize,
+        height: usize,
+        pixels: Vec<Color>,
+    ) -> Result<ImageTexture, String> {
+        if width == 0 || height == 0 || pixels.len() != width * height {
+            return Err(format!("Texture {} has no pixels", file));
+        }
+
+        Ok(ImageTexture {
+            file: file.to_string(),
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Looks up the color at the texture coordinates. v goes up from the
+    /// bottom of the image.
+    pub fn value(&self, uv: (f32, f32)) -> Color {
+        let (u, v) = uv;
+        let u = u - u.floor();
+        let v = 1.0 - (v - v.floor());
+
+        let i = ((u * self.width as f32) as usize).min(self.width - 1);
+        let j = ((v * self.height as f32) as usize).min(self.height - 1);
+
+        self.pixels[j * self.width + i]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+
+    #[test]
+    fn test_image_texture() {
+        // red and green on the top row, blue and white on the bottom
+        let tex = ImageTexture::new(
+            "test",
+            2,
+            2,
+            vec![
+                Color::new(1.0, 0.0, 0.0),
+                Color::new(0.0, 1.0, 0.0),
+                Color::new(0.0, 0.0, 1.0),
+                color::WHITE,
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(tex.value((0.25, 0.75)).x, 1.0);
+        assert_eq!(tex.value((0.75, 0.75)).y, 1.0);
+        assert_eq!(tex.value((0.25, 0.25)).z, 1.0);
+        assert_eq!(tex.value((0.75, 0.25)).x, 1.0);
+        assert_eq!(tex.value((0.75, 0.25)).z, 1.0);
+
+        // the edges and outside of the image repeat
+        assert_eq!(tex.value((1.0, 1.0)).z, 1.0);
+        let c = tex.value((1.25, -0.25));
+        assert_eq!(c.x, 1.0);
+        assert_eq!(c.y, 0.0);
+
+        assert!(ImageTexture::new("empty", 0, 0, vec![]).is_err());
+        assert!(ImageTexture::open("test_input/missing.png").is_err());
+    }
+}
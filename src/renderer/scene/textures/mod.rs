@@ -0,0 +1,268 @@
+//! Textures that vary the parameters of the materials over a surface.
+
+pub mod image_texture;
+pub mod noise;
+
+use serde::{Deserialize, Serialize};
+
+use crate::renderer::core::color::Color;
+use crate::renderer::core::vector::Point3;
+use crate::renderer::scene::hittable::HitRecord;
+
+use image_texture::ImageTexture;
+
+/// A material parameter that can vary over the surface. In the scene
+/// file, a plain number or color is a constant, and anything else is one
+/// of the procedural textures, tagged by its `type`. This keeps scene
+/// files written before textures existed working.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Texture {
+    Constant(Color),
+    Scalar(f32),
+    Procedural(ProceduralTexture),
+}
+
+/// The textures that are calculated for every lookup.
+///
+/// * `Checker` - Alternates between two textures. The squares are `size`
+///   wide, in texture coordinates if `uv` is set and otherwise in world
+///   space, which works on objects without texture coordinates.
+/// * `Image` - An image file mapped with the texture coordinates.
+/// * `Noise` - Perlin noise at the frequency of `scale`.
+/// * `Turbulence` - Several `octaves` of Perlin noise summed together.
+/// * `Marble` - Sine bands along z, distorted by turbulence.
+/// * `Worley` - The distance to the closest feature point of cellular noise.
+///
+/// The noise textures scale `color`, white by default, by their value.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ProceduralTexture {
+    Checker {
+        even: Box<Texture>,
+        odd: Box<Texture>,
+        size: f32,
+        #[serde(default)]
+        uv: bool,
+    },
+    Image(ImageTexture),
+    Noise {
+        scale: f32,
+        #[serde(default)]
+        color: Color,
+    },
+    Turbulence {
+        scale: f32,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default)]
+        color: Color,
+    },
+    Marble {
+        scale: f32,
+        #[serde(default)]
+        color: Color,
+    },
+    Worley {
+        scale: f32,
+        #[serde(default)]
+        color: Color,
+    },
+}
+
+fn default_octaves() -> u32 {
+    7
+}
+
+impl Texture {
+    /// Looks up the color of the texture at the hit.
+    pub fn value(&self, rec: &HitRecord) -> Color {
+        self.lookup(rec.uv, &rec.p)
+    }
+
+    /// Looks up a scalar parameter at the hit. Textures with color are
+    /// averaged over the channels.
+    pub fn scalar(&self, rec: &HitRecord) -> f32 {
+        match self {
+            Texture::Scalar(s) => *s,
+            _ => {
+                let c = self.value(rec);
+                (c.x + c.y + c.z) / 3.0
+            }
+        }
+    }
+
+    /// Looks up the color of the texture at the texture coordinates and
+    /// point in world space.
+    pub fn lookup(&self, uv: (f32, f32), p: &Point3) -> Color {
+        match self {
+            Texture::Constant(c) => *c,
+            Texture::Scalar(s) => Color::new(*s, *s, *s),
+            Texture::Procedural(t) => t.lookup(uv, p),
+        }
+    }
+}
+
+impl ProceduralTexture {
+    fn lookup(&self, uv: (f32, f32), p: &Point3) -> Color {
+        match self {
+            ProceduralTexture::Checker {
+                even,
+                odd,
+                size,
+                uv: in_uv,
+            } => {
+                let cells = if *in_uv {
+                    (uv.0 / size).floor() + (uv.1 / size).floor()
+                } else {
+                    (p.x / size).floor() + (p.y / size).floor() + (p.z / size).floor()
+                };
+
+                if (cells as i64).rem_euclid(2) == 0 {
+                    even.lookup(uv, p)
+                } else {
+                    odd.lookup(uv, p)
+                }
+            }
+            ProceduralTexture::Image(image) => image.value(uv),
+            ProceduralTexture::Noise { scale, color } => {
+                (0.5 * (1.0 + noise::perlin(&(*scale * *p)))) * *color
+            }
+            ProceduralTexture::Turbulence {
+                scale,
+                octaves,
+                color,
+            } => noise::turbulence(&(*scale * *p), *octaves) * *color,
+            ProceduralTexture::Marble { scale, color } => {
+                let phase = *scale * p.z + 10.0 * noise::turbulence(p, default_octaves());
+                (0.5 * (1.0 + phase.sin())) * *color
+            }
+            ProceduralTexture::Worley { scale, color } => noise::worley(&(*scale * *p)) * *color,
+        }
+    }
+}
+
+impl From<Color> for Texture {
+    fn from(c: Color) -> Self {
+        Texture::Constant(c)
+    }
+}
+
+impl From<f32> for Texture {
+    fn from(s: f32) -> Self {
+        Texture::Scalar(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::core::color;
+
+    #[test]
+    fn test_constant() {
+        let p = Point3::new(1.0, 2.0, 3.0);
+
+        let t: Texture = Color::new(0.1, 0.2, 0.3).into();
+        assert_eq!(t.lookup((0.5, 0.5), &p).y, 0.2);
+
+        let t: Texture = 0.4.into();
+        assert_eq!(t.lookup((0.5, 0.5), &p).z, 0.4);
+    }
+
+    #[test]
+    fn test_checker() {
+        let t = Texture::Procedural(ProceduralTexture::Checker {
+            even: Box::new(color::WHITE.into()),
+            odd: Box::new(color::BLACK.into()),
+            size: 0.5,
+            uv: false,
+        });
+
+        assert_eq!(t.lookup((0.0, 0.0), &Point3::new(0.1, 0.1, 0.1)).x, 1.0);
+        assert_eq!(t.lookup((0.0, 0.0), &Point3::new(0.6, 0.1, 0.1)).x, 0.0);
+        assert_eq!(t.lookup((0.0, 0.0), &Point3::new(0.6, 0.6, 0.1)).x, 1.0);
+        assert_eq!(t.lookup((0.0, 0.0), &Point3::new(-0.1, 0.1, 0.1)).x, 0.0);
+
+        let t = Texture::Procedural(ProceduralTexture::Checker {
+            even: Box::new(color::WHITE.into()),
+            odd: Box::new(color::BLACK.into()),
+            size: 0.25,
+            uv: true,
+        });
+        let p = Point3::new(0.0, 0.0, 0.0);
+        assert_eq!(t.lookup((0.1, 0.1), &p).x, 1.0);
+        assert_eq!(t.lookup((0.3, 0.1), &p).x, 0.0);
+        assert_eq!(t.lookup((0.3, 0.3), &p).x, 1.0);
+    }
+
+    #[test]
+    fn test_noise_textures() {
+        let textures = [
+            ProceduralTexture::Noise {
+                scale: 4.0,
+                color: color::WHITE,
+            },
+            ProceduralTexture::Turbulence {
+                scale: 4.0,
+                octaves: 7,
+                color: color::WHITE,
+            },
+            ProceduralTexture::Marble {
+                scale: 4.0,
+                color: color::WHITE,
+            },
+            ProceduralTexture::Worley {
+                scale: 4.0,
+                color: color::WHITE,
+            },
+        ];
+
+        for t in textures.iter() {
+            for i in 0..100 {
+                let f = i as f32 * 0.173;
+                let c = t.lookup((0.0, 0.0), &Point3::new(f, 0.3 * f, -f));
+                assert!(c.x >= 0.0 && c.x <= 2.0);
+                assert_eq!(c.x, c.y);
+            }
+        }
+
+        let colored = ProceduralTexture::Noise {
+            scale: 1.0,
+            color: Color::new(1.0, 0.0, 0.0),
+        };
+        let c = colored.lookup((0.0, 0.0), &Point3::new(0.0, 0.0, 0.0));
+        assert_eq!(c.x, 0.5);
+        assert_eq!(c.y, 0.0);
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let t: Texture = serde_json::from_str(r#"{"x": 0.1, "y": 0.2, "z": 0.3}"#).unwrap();
+        assert!(matches!(t, Texture::Constant(_)));
+
+        let t: Texture = serde_json::from_str("0.3").unwrap();
+        assert!(matches!(t, Texture::Scalar(_)));
+
+        let t: Texture = serde_json::from_str(
+            r#"{"type": "checker", "size": 1.0,
+                "even": {"x": 1.0, "y": 1.0, "z": 1.0},
+                "odd": {"type": "marble", "scale": 4.0}}"#,
+        )
+        .unwrap();
+        match t {
+            Texture::Procedural(ProceduralTexture::Checker { odd, uv, .. }) => {
+                assert!(!uv);
+                assert!(matches!(
+                    *odd,
+                    Texture::Procedural(ProceduralTexture::Marble { .. })
+                ));
+            }
+            _ => panic!("expected a checker texture"),
+        }
+
+        let t: Result<Texture, _> =
+            serde_json::from_str(r#"{"type": "image", "file": "test_input/missing.png"}"#);
+        assert!(t.is_err());
+    }
+}
@@ -0,0 +1,226 @@
+//! Deterministic procedural noise functions for the textures. They only
+//! depend on the point, so the same scene always renders the same way.
+
+use crate::renderer::core::vector::Point3;
+
+/// Ken Perlin's reference permutation of 0 to 255.
+const PERMUTATION: [u8; 256] = [
+    151, 160, 137, 91, 90, 15, 131, 13, 201, 95, 96, 53, 194, 233, 7, 225, 140, 36, 103, 30, 69,
+    142, 8, 99, 37, 240, 21, 10, 23, 190, 6, 148, 247, 120, 234, 75, 0, 26, 197, 62, 94, 252, 219,
+    203, 117, 35, 11, 32, 57, 177, 33, 88, 237, 149, 56, 87, 174, 20, 125, 136, 171, 168, 68, 175,
+    74, 165, 71, 134, 139, 48, 27, 166, 77, 146, 158, 231, 83, 111, 229, 122, 60, 211, 133, 230,
+    220, 105, 92, 41, 55, 46, 245, 40, 244, 102, 143, 54, 65, 25, 63, 161, 1, 216, 80, 73, 209, 76,
+    132, 187, 208, 89, 18, 169, 200, 196, 135, 130, 116, 188, 159, 86, 164, 100, 109, 198, 173,
+    186, 3, 64, 52, 217, 226, 250, 124, 123, 5, 202, 38, 147, 118, 126, 255, 82, 85, 212, 207, 206,
+    59, 227, 47, 16, 58, 17, 182, 189, 28, 42, 223, 183, 170, 213, 119, 248, 152, 2, 44, 154, 163,
+    70, 221, 153, 101, 155, 167, 43, 172, 9, 129, 22, 39, 253, 19, 98, 108, 110, 79, 113, 224, 232,
+    178, 185, 112, 104, 218, 246, 97, 228, 251, 34, 242, 193, 238, 210, 144, 12, 191, 179, 162,
+    241, 81, 51, 145, 235, 249, 14, 239, 107, 49, 192, 214, 31, 181, 199, 106, 157, 184, 84, 204,
+    176, 115, 121, 50, 45, 127, 4, 150, 254, 138, 236, 205, 93, 222, 114, 67, 29, 24, 72, 243, 141,
+    128, 195, 78, 66, 215, 61, 156, 180,
+];
+
+#[inline]
+fn perm(i: usize) -> usize {
+    PERMUTATION[i & 255] as usize
+}
+
+#[inline]
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+#[inline]
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+/// Picks one of the 12 gradients on the edges of a cube and dots it with
+/// the offset.
+#[inline]
+fn grad(hash: usize, x: f32, y: f32, z: f32) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// Ken Perlin's improved gradient noise. Returns a value in about
+/// [-1, 1], which is 0 at every integer lattice point.
+pub fn perlin(p: &Point3) -> f32 {
+    let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+
+    // the lattice wraps every 256 units
+    let xi = (fx as i64 & 255) as usize;
+    let yi = (fy as i64 & 255) as usize;
+    let zi = (fz as i64 & 255) as usize;
+
+    let (x, y, z) = (p.x - fx, p.y - fy, p.z - fz);
+    let (u, v, w) = (fade(x), fade(y), fade(z));
+
+    let a = perm(xi) + yi;
+    let aa = perm(a) + zi;
+    let ab = perm(a + 1) + zi;
+    let b = perm(xi + 1) + yi;
+    let ba = perm(b) + zi;
+    let bb = perm(b + 1) + zi;
+
+    lerp(
+        w,
+        lerp(
+            v,
+            lerp(u, grad(perm(aa), x, y, z), grad(perm(ba), x - 1.0, y, z)),
+            lerp(
+                u,
+                grad(perm(ab), x, y - 1.0, z),
+                grad(perm(bb), x - 1.0, y - 1.0, z),
+            ),
+        ),
+        lerp(
+            v,
+            lerp(
+                u,
+                grad(perm(aa + 1), x, y, z - 1.0),
+                grad(perm(ba + 1), x - 1.0, y, z - 1.0),
+            ),
+            lerp(
+                u,
+                grad(perm(ab + 1), x, y - 1.0, z - 1.0),
+                grad(perm(bb + 1), x - 1.0, y - 1.0, z - 1.0),
+            ),
+        ),
+    )
+}
+
+/// Sums the absolute value of the noise over the octaves, each with half
+/// of the weight and double the frequency of the last. Returns a value in
+/// about [0, 1].
+pub fn turbulence(p: &Point3, octaves: u32) -> f32 {
+    let mut accum = 0.0;
+    let mut point = *p;
+    let mut weight = 1.0;
+
+    for _ in 0..octaves {
+        accum += weight * perlin(&point).abs();
+        weight *= 0.5;
+        point *= 2.0;
+    }
+
+    accum
+}
+
+/// Worley, or cellular, noise. Every unit cell has one randomly placed
+/// feature point and this returns the distance to the closest one,
+/// clamped to [0, 1].
+pub fn worley(p: &Point3) -> f32 {
+    let (cx, cy, cz) = (p.x.floor() as i32, p.y.floor() as i32, p.z.floor() as i32);
+    let mut closest = f32::INFINITY;
+
+    for dz in -1..=1 {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let (x, y, z) = (cx + dx, cy + dy, cz + dz);
+                let feature = Point3::new(
+                    x as f32 + cell_random(x, y, z, 0),
+                    y as f32 + cell_random(x, y, z, 1),
+                    z as f32 + cell_random(x, y, z, 2),
+                );
+
+                closest = closest.min((feature - *p).length_squared());
+            }
+        }
+    }
+
+    closest.sqrt().min(1.0)
+}
+
+/// A random number in [0, 1) for the cell, from an integer hash of its
+/// coordinates.
+fn cell_random(x: i32, y: i32, z: i32, seed: u32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ (z as u32).wrapping_mul(0xcb1a_b31f)
+        ^ seed.wrapping_mul(0x9e37_79b9);
+
+    // finalizer from murmur3 to mix the bits
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+
+    (h >> 8) as f32 / (1 << 24) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permutation() {
+        let mut seen = [false; 256];
+        for p in PERMUTATION.iter() {
+            assert!(!seen[*p as usize]);
+            seen[*p as usize] = true;
+        }
+    }
+
+    #[test]
+    fn test_perlin() {
+        // zero on the lattice
+        assert_eq!(perlin(&Point3::new(0.0, 0.0, 0.0)), 0.0);
+        assert_eq!(perlin(&Point3::new(3.0, -7.0, 12.0)), 0.0);
+
+        let mut min = f32::INFINITY;
+        let mut max = -f32::INFINITY;
+        for i in 0..1000 {
+            let t = i as f32 * 0.137;
+            let p = Point3::new(t, 0.5 * t + 0.3, -0.7 * t);
+            let n = perlin(&p);
+            min = min.min(n);
+            max = max.max(n);
+
+            // the same point always gives the same value
+            assert_eq!(n, perlin(&p));
+        }
+        assert!((-1.1..-0.2).contains(&min));
+        assert!((0.2..1.1).contains(&max));
+
+        // the noise is continuous
+        let p = Point3::new(1.3, 2.7, -0.4);
+        let q = Point3::new(1.3001, 2.7, -0.4);
+        assert!((perlin(&p) - perlin(&q)).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_turbulence() {
+        let p = Point3::new(0.3, 0.6, 0.9);
+        assert_eq!(turbulence(&p, 1), perlin(&p).abs());
+        assert!(turbulence(&p, 7) >= turbulence(&p, 1));
+        assert_eq!(turbulence(&p, 0), 0.0);
+    }
+
+    #[test]
+    fn test_worley() {
+        for i in 0..1000 {
+            let t = i as f32 * 0.071;
+            let n = worley(&Point3::new(t, -t, 0.5 * t));
+            assert!((0.0..=1.0).contains(&n));
+        }
+
+        // right on a feature point the distance is 0
+        let feature = Point3::new(
+            cell_random(0, 0, 0, 0),
+            cell_random(0, 0, 0, 1),
+            cell_random(0, 0, 0, 2),
+        );
+        assert_eq!(worley(&feature), 0.0);
+    }
+}
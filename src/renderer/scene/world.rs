@@ -1,21 +1,51 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 use crate::renderer::core::aabb::Aabb;
 use crate::renderer::core::color;
 use crate::renderer::core::color::Color;
 use crate::renderer::core::ray::Ray;
-use crate::renderer::core::vector::Point3;
-use crate::renderer::core::vector::{self, Vec3};
+use crate::renderer::core::vector::{self, Point3, Vec3};
+use crate::renderer::integrator::IntegratorConfig;
+use crate::renderer::post::tonemap::ToneMapping;
+use crate::renderer::sampler::Sampler;
 
+use crate::renderer::scene::bvh::Bvh;
 use crate::renderer::scene::hittable::{HitRecord, Hittable};
+use crate::renderer::scene::materials::Material;
 
 use super::camera::CameraConfig;
 
+/// The light coming from the directions where a ray doesn't hit
+/// anything.
+///
+/// * `Sky` - A gradient from white at the horizon to the region's
+///   background color at the zenith.
+/// * `Black` - No light at all, for scenes lit only by emissive materials.
+/// * `Solid` - A single color in every direction.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Background {
+    #[default]
+    Sky,
+    Black,
+    Solid(Color),
+}
+
 /// A data structure representing a region of the scene. This can
 /// be the whole scene or a self-contained portion of the scene.
 ///
-/// This will be used to implement k-d trees to improve the
-/// performance further.
+/// The objects are indexed by a bounding volume hierarchy to avoid
+/// testing every object for every ray. The hierarchy is built by
+/// `recalculate_bounds()`, so it must be called once all of the objects
+/// have been added. Until then, hits fall back to a linear scan.
+///
+/// `recalculate_bounds()` also collects the emissive objects so they can
+/// be sampled directly as lights, and works out the ids of the materials.
+///
+/// Objects without finite bounds, like infinite planes, would make the
+/// bounding box and the hierarchy infinite as well, so they're kept out of
+/// both and tested against every ray on their own.
 ///
 // REVIEW: Here's where we would write the custom serde juice to
 // marshall this region/world struct
@@ -24,7 +54,26 @@ pub struct Region {
     pub objects: Vec<Box<dyn Hittable>>,
     bounding_box: Aabb,
     background_color: Color,
+    #[serde(default)]
+    pub background: Background,
     pub camera_config: CameraConfig,
+    #[serde(default)]
+    pub tone_mapping: ToneMapping,
+    #[serde(default)]
+    pub integrator: IntegratorConfig,
+    #[serde(skip)]
+    bvh: Bvh,
+    /// The index of the object of each primitive in the hierarchy, which
+    /// are all of the objects with finite bounds.
+    #[serde(skip)]
+    bounded: Vec<usize>,
+    #[serde(skip)]
+    unbounded: Vec<usize>,
+    #[serde(skip)]
+    lights: Vec<usize>,
+    /// The id of each material, by its address.
+    #[serde(skip)]
+    material_ids: HashMap<usize, u32>,
 }
 
 impl Region {
@@ -34,11 +83,9 @@ impl Region {
     pub fn new(background_color: Color) -> Region {
         Region {
             objects: Vec::new(),
-            bounding_box: Aabb::new(
-                Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
-                Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
-            ),
+            bounding_box: Aabb::empty(),
             background_color,
+            background: Background::Sky,
             camera_config: CameraConfig {
                 look_from: Vec3::new(0.0, 0.0, 0.0),
                 look_at: Vec3::new(0.0, 0.0, -1.0),
@@ -46,16 +93,35 @@ impl Region {
                 vertical_fov: 90.0,
                 aperture: 1.0,
                 focal_distance: 1.0,
+                shutter_open: 0.0,
+                shutter_close: 0.0,
             },
+            tone_mapping: ToneMapping::default(),
+            integrator: IntegratorConfig::default(),
+            bvh: Bvh::default(),
+            bounded: Vec::new(),
+            unbounded: Vec::new(),
+            lights: Vec::new(),
+            material_ids: HashMap::new(),
         }
     }
 
     /// Add a new object to the region. This will update the list of objects
-    /// and change the bounding box to include
+    /// and change the bounding box to include it. The acceleration structure
+    /// is invalidated until `recalculate_bounds()` is called.
     pub fn push(&mut self, obj: Box<dyn Hittable + Sync>) {
         // update bounding_box
-        self.bounding_box.expand(obj.bounds());
+        let bounds = obj.bounds();
+        if bounds.is_finite() {
+            self.bounding_box.expand(bounds);
+            self.bounded.push(self.objects.len());
+        } else {
+            self.unbounded.push(self.objects.len());
+        }
         self.objects.push(obj);
+        self.bvh = Bvh::default();
+        self.lights.clear();
+        self.material_ids.clear();
     }
 
     /// Gets the color for a ray that hasn't hit any objects.
@@ -65,56 +131,185 @@ impl Region {
     /// This doesn't really belong here. It should be a property
     /// of the global world only, not every logical region.
     pub fn background_color(&self, r: &Ray) -> Color {
-        let unit_direction = vector::unit_vector(&r.dir);
-        let t = 0.5 * (unit_direction.y + 1.0);
-        color::lerp(color::WHITE, self.background_color, t)
+        match self.background {
+            Background::Sky => {
+                let unit_direction = vector::unit_vector(&r.dir);
+                let t = 0.5 * (unit_direction.y + 1.0);
+                color::lerp(color::WHITE, self.background_color, t)
+            }
+            Background::Black => color::BLACK,
+            Background::Solid(c) => c,
+        }
     }
 
-    /// Recalculate the bounding box for this region
-    pub fn recalculate_bounds(&mut self) {
-        for obj in self.objects.iter() {
-            self.bounding_box.expand(obj.bounds());
+    /// The same as `hit()`, but also returns the index of the object that
+    /// was hit.
+    pub fn hit_object(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<(usize, HitRecord<'_>)> {
+        let mut object = 0;
+        let mut closest_so_far = t_max;
+        let mut rec = None;
+
+        for &index in &self.unbounded {
+            if let Some(hit) = self.objects[index].hit(r, t_min, closest_so_far) {
+                closest_so_far = hit.t;
+                object = index;
+                rec = Some(hit);
+            }
         }
+
+        if !self.bounding_box.hit(r, closest_so_far) {
+            return rec.map(|rec| (object, rec));
+        }
+
+        if !self.bvh.is_empty() {
+            // the bvh only hands back hits closer than the ones before, so
+            // the last object that was hit is the closest
+            let mut bvh_object = None;
+            let hit = self
+                .bvh
+                .hit(r, t_min, closest_so_far, |primitive, t_min, t_max| {
+                    let index = self.bounded[primitive];
+                    let hit = self.objects[index].hit(r, t_min, t_max);
+                    if hit.is_some() {
+                        bvh_object = Some(index);
+                    }
+                    hit
+                });
+            if let (Some(hit), Some(index)) = (hit, bvh_object) {
+                object = index;
+                rec = Some(hit);
+            }
+        } else {
+            for &index in &self.bounded {
+                if let Some(hit) = self.objects[index].hit(r, t_min, closest_so_far) {
+                    closest_so_far = hit.t;
+                    object = index;
+                    rec = Some(hit);
+                }
+            }
+        }
+
+        rec.map(|rec| (object, rec))
     }
-}
 
-/// The region implements the hittable trait as well.
-#[typetag::serde]
-impl Hittable for Region {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        // if we don't hit the world bounding box, return right away
-        if !self.bounding_box.hit(r, t_max) {
+    /// Whether the region has any lights that can be sampled directly.
+    pub fn has_lights(&self) -> bool {
+        !self.lights.is_empty()
+    }
+
+    /// Returns a random direction from the origin toward one of the
+    /// lights, picked uniformly. Returns None if there are no lights or the
+    /// chosen light can't be sampled from the origin.
+    pub fn sample_light_direction(
+        &self,
+        origin: &Point3,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Vec3> {
+        if self.lights.is_empty() {
             return None;
         }
 
-        // otherwise, loop through all the objects contained within.
-        let mut closest_so_far = t_max;
+        let light =
+            ((sampler.get_1d() * self.lights.len() as f32) as usize).min(self.lights.len() - 1);
+        self.objects[self.lights[light]].random_direction(origin, sampler)
+    }
 
-        let mut rec: Option<HitRecord> = None;
+    /// The probability density, with respect to solid angle, of
+    /// `sample_light_direction()` returning the direction from the origin.
+    pub fn light_pdf(&self, origin: &Point3, dir: &Vec3) -> f32 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
 
-        for hittable in &self.objects {
-            if let Some(hit) = hittable.hit(r, t_min, closest_so_far) {
-                closest_so_far = hit.t;
-                rec = Some(hit);
-            }
+        let sum: f32 = self
+            .lights
+            .iter()
+            .map(|light| self.objects[*light].pdf_value(origin, dir))
+            .sum();
+        sum / self.lights.len() as f32
+    }
+
+    /// Recalculate the bounding box for this region, rebuild the
+    /// acceleration structure over its objects and collect its lights.
+    pub fn recalculate_bounds(&mut self) {
+        let bounds: Vec<Aabb> = self.objects.iter().map(|obj| obj.bounds()).collect();
+        let (bounded, unbounded): (Vec<usize>, Vec<usize>) =
+            (0..bounds.len()).partition(|i| bounds[*i].is_finite());
+
+        self.bounding_box = Aabb::empty();
+        for i in bounded.iter() {
+            self.bounding_box.expand(bounds[*i]);
         }
-        rec
+
+        let bounded_bounds: Vec<Aabb> = bounded.iter().map(|i| bounds[*i]).collect();
+        self.bvh = Bvh::new(&bounded_bounds);
+        self.bounded = bounded;
+        self.unbounded = unbounded;
+
+        self.lights = (0..self.objects.len())
+            .filter(|i| self.objects[*i].is_emissive())
+            .collect();
+
+        self.material_ids = self
+            .objects
+            .iter()
+            .flat_map(|obj| obj.materials())
+            .map(|material| (material_address(material), material.id()))
+            .collect();
+    }
+
+    /// The id of the material, see `Material::id()`. It's looked up for
+    /// the materials of the objects, and only calculated for any others.
+    pub fn material_id(&self, material: &Material) -> u32 {
+        match self.material_ids.get(&material_address(material)) {
+            Some(id) => *id,
+            None => material.id(),
+        }
+    }
+}
+
+fn material_address(material: &Material) -> usize {
+    material as *const Material as usize
+}
+
+/// The region implements the hittable trait as well.
+#[typetag::serde]
+impl Hittable for Region {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.hit_object(r, t_min, t_max).map(|(_, rec)| rec)
     }
 
     fn bounds(&self) -> Aabb {
+        if !self.unbounded.is_empty() {
+            return Aabb::infinite();
+        }
+
         // the bounding box has been updated on insertion of the objects, so
         // just return it.
         self.bounding_box
     }
+
+    fn materials(&self) -> Vec<&Material> {
+        self.objects
+            .iter()
+            .flat_map(|obj| obj.materials())
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::renderer::sampler::IndependentSampler;
 
-    use crate::renderer::core::vector::Vec3;
+    use crate::renderer::core::vector::{Point3, Vec3};
     use crate::renderer::scene::hittable::Hittable;
     use crate::renderer::scene::materials::Material;
+    use crate::renderer::scene::textures::Texture;
+
+    static MOCK_MATERIAL: Material = Material::Lambert {
+        albedo: Texture::Constant(color::WHITE),
+    };
 
     #[derive(Serialize, Deserialize)]
     struct MockObject {
@@ -126,17 +321,17 @@ mod tests {
 
     #[typetag::serde]
     impl Hittable for MockObject {
-        fn hit(&self, _r: &Ray, _t_min: f32, _t_max: f32) -> Option<HitRecord> {
+        fn hit(&self, _r: &Ray, _t_min: f32, _t_max: f32) -> Option<HitRecord<'_>> {
             assert!(self.expect);
 
             Some(HitRecord {
                 p: Point3::new(0.0, 0.0, 0.0),
                 normal: Vec3::new(0.0, 0.0, 0.0),
+                geometric_normal: Vec3::new(0.0, 0.0, 0.0),
                 t: f32::INFINITY,
                 front_face: false,
-                material: &Material::Lambert {
-                    albedo: color::WHITE,
-                },
+                uv: (0.0, 0.0),
+                material: &MOCK_MATERIAL,
             })
         }
 
@@ -178,6 +373,89 @@ mod tests {
         assert_eq!(r.bounding_box.box_max.z, 16.0);
     }
 
+    #[test]
+    fn test_region_background() {
+        let mut r = Region::new(Color::new(0.1, 0.2, 0.3));
+        let up = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+
+        let c = r.background_color(&up);
+        assert_eq!(c.x, 0.1);
+        assert_eq!(c.y, 0.2);
+        assert_eq!(c.z, 0.3);
+
+        r.background = Background::Black;
+        let c = r.background_color(&up);
+        assert_eq!(c.x, 0.0);
+        assert_eq!(c.y, 0.0);
+        assert_eq!(c.z, 0.0);
+
+        r.background = Background::Solid(Color::new(0.4, 0.5, 0.6));
+        let c = r.background_color(&up);
+        assert_eq!(c.x, 0.4);
+        assert_eq!(c.y, 0.5);
+        assert_eq!(c.z, 0.6);
+
+        let b: Background = serde_json::from_str("\"black\"").unwrap();
+        assert!(matches!(b, Background::Black));
+    }
+
+    #[test]
+    fn test_region_lights() {
+        use crate::renderer::scene::objects::sphere::Sphere;
+
+        let mut r = Region::new(Color::new(0.1, 0.1, 0.1));
+        r.push(Box::new(Sphere {
+            center: Point3::new(0.0, 0.0, -10.0),
+            radius: 1.0,
+            material: Material::Lambert {
+                albedo: color::WHITE.into(),
+            },
+        }));
+        r.recalculate_bounds();
+        assert!(!r.has_lights());
+
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        assert!(r
+            .sample_light_direction(&origin, &mut IndependentSampler::new(0))
+            .is_none());
+
+        r.push(Box::new(Sphere {
+            center: Point3::new(0.0, 10.0, 0.0),
+            radius: 1.0,
+            material: Material::Emissive {
+                color: color::WHITE,
+                intensity: 4.0,
+            },
+        }));
+        r.push(Box::new(Sphere {
+            center: Point3::new(10.0, 0.0, 0.0),
+            radius: 1.0,
+            material: Material::Emissive {
+                color: color::WHITE,
+                intensity: 4.0,
+            },
+        }));
+        r.recalculate_bounds();
+        assert!(r.has_lights());
+
+        // each light gets picked half of the time, which halves its pdf
+        for _ in 0..100 {
+            let dir = r
+                .sample_light_direction(&origin, &mut IndependentSampler::new(0))
+                .unwrap();
+            let light = if dir.y > dir.x {
+                &r.objects[1]
+            } else {
+                &r.objects[2]
+            };
+            let pdf = r.light_pdf(&origin, &dir);
+            assert!((pdf - 0.5 * light.pdf_value(&origin, &dir)).abs() < 1e-3);
+        }
+
+        // toward the sphere that isn't a light
+        assert_eq!(r.light_pdf(&origin, &Vec3::new(0.0, 0.0, -1.0)), 0.0);
+    }
+
     #[test]
     fn test_region_hit() {
         let mock_obj = MockObject {
@@ -205,5 +483,74 @@ mod tests {
 
         let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
         assert!(r.hit(&ray, 0.001, f32::INFINITY).is_some());
+
+        // the same hit has to come back through the acceleration structure
+        r.recalculate_bounds();
+        assert!(r.hit(&ray, 0.001, f32::INFINITY).is_some());
+    }
+
+    #[test]
+    fn test_region_material_id() {
+        use crate::renderer::scene::objects::sphere::Sphere;
+
+        let mut r = Region::new(Color::new(0.1, 0.1, 0.1));
+        r.push(Box::new(Sphere {
+            center: Point3::new(0.0, 0.0, -5.0),
+            radius: 1.0,
+            material: Material::Dielectric { ior: 1.5 },
+        }));
+        r.recalculate_bounds();
+
+        let material = r.objects[0].materials()[0];
+        assert_eq!(r.material_ids.len(), 1);
+        assert_eq!(r.material_id(material), material.id());
+        assert_eq!(r.material_id(&MOCK_MATERIAL), MOCK_MATERIAL.id());
+    }
+
+    #[test]
+    fn test_region_unbounded() {
+        use crate::renderer::scene::objects::plane::Plane;
+        use crate::renderer::scene::objects::sphere::Sphere;
+
+        let mut r = Region::new(Color::new(0.1, 0.1, 0.1));
+        r.push(Box::new(Plane {
+            point: Point3::new(0.0, -1.0, 0.0),
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            material: Material::Lambert {
+                albedo: color::WHITE.into(),
+            },
+        }));
+        r.push(Box::new(Sphere {
+            center: Point3::new(0.0, 0.0, -5.0),
+            radius: 1.0,
+            material: Material::Lambert {
+                albedo: color::WHITE.into(),
+            },
+        }));
+
+        // the plane stays out of the bounding box
+        assert_eq!(r.bounding_box.box_min.y, -1.0);
+        assert_eq!(r.bounding_box.box_max.z, -4.0);
+        assert!(!r.bounds().is_finite());
+
+        let down = Ray::new(Point3::new(10.0, 0.0, 10.0), Vec3::new(0.0, -1.0, 0.0));
+        let ahead = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let below = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, -1.0, -5.0));
+        for _ in 0..2 {
+            let (object, rec) = r.hit_object(&down, 0.001, f32::INFINITY).unwrap();
+            assert_eq!(object, 0);
+            assert_eq!(rec.t, 1.0);
+
+            let (object, rec) = r.hit_object(&ahead, 0.001, f32::INFINITY).unwrap();
+            assert_eq!(object, 1);
+            assert_eq!(rec.t, 4.0);
+
+            // the sphere is in front of the plane
+            let (object, _) = r.hit_object(&below, 0.001, f32::INFINITY).unwrap();
+            assert_eq!(object, 1);
+
+            // the same hits have to come back through the hierarchy
+            r.recalculate_bounds();
+        }
     }
 }
@@ -1,39 +1,49 @@
 use crate::renderer::core::{color::Color, vector::Vec3};
-use crate::renderer::scene::{camera::CameraConfig, objects::sphere::Sphere};
+use crate::renderer::scene::camera::CameraConfig;
+use crate::renderer::scene::objects::{plane::Plane, sphere::Sphere};
 
 use super::materials::Material;
 use super::world::Region;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-/// Returns a procedurally generated region.
-pub fn random_scene() -> Region {
+/// Returns a procedurally generated region. The same seed always
+/// generates the same region.
+pub fn random_scene(seed: u64) -> Region {
+    let mut rng = StdRng::seed_from_u64(seed);
     let mut world = Region::new(Color::new(0.5, 0.7, 0.9));
 
     let ground = Material::Lambert {
-        albedo: Color::new(0.5, 0.5, 0.5),
+        albedo: Color::new(0.5, 0.5, 0.5).into(),
     };
-    world.push(Box::new(Sphere {
-        center: Vec3::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
+    world.push(Box::new(Plane {
+        point: Vec3::new(0.0, 0.0, 0.0),
+        normal: Vec3::new(0.0, 1.0, 0.0),
         material: ground,
     }));
 
     for a in -11..11 {
         for b in -11..11 {
-            let rand = rand::thread_rng().gen_range(0.0..1.0);
+            let rand = rng.gen_range(0.0..1.0);
 
             let center = Vec3::new(a as f32 + 0.9 * rand, 0.2, b as f32 + 0.9 * rand);
 
             if (center - Vec3::new(4.0, 0.2, 0.0)).length_squared() > 0.81 {
                 let sphere_mat = if rand < 0.8 {
                     // lambert
-                    let albedo = Vec3::random_range(0.0, 1.0) * Vec3::random_range(0.0, 1.0);
-                    Material::Lambert { albedo }
+                    let albedo = Vec3::random_range(&mut rng, 0.0, 1.0)
+                        * Vec3::random_range(&mut rng, 0.0, 1.0);
+                    Material::Lambert {
+                        albedo: albedo.into(),
+                    }
                 } else if rand < 0.95 {
-                    let albedo = Vec3::random_range(0.5, 1.0);
-                    let fuzz = rand::thread_rng().gen_range(0.0..1.0);
+                    let albedo = Vec3::random_range(&mut rng, 0.5, 1.0);
+                    let fuzz = rng.gen_range(0.0..1.0);
 
-                    Material::Metal { albedo, fuzz }
+                    Material::Metal {
+                        albedo: albedo.into(),
+                        fuzz: fuzz.into(),
+                    }
                 } else {
                     Material::Dielectric { ior: 1.5 }
                 };
@@ -55,7 +65,7 @@ pub fn random_scene() -> Region {
     }));
 
     let material = Material::Lambert {
-        albedo: Vec3::new(0.4, 0.2, 0.1),
+        albedo: Vec3::new(0.4, 0.2, 0.1).into(),
     };
     world.push(Box::new(Sphere {
         center: Vec3::new(-4.0, 1.0, 0.0),
@@ -64,8 +74,8 @@ pub fn random_scene() -> Region {
     }));
 
     let material = Material::Metal {
-        albedo: Vec3::new(0.7, 0.6, 0.5),
-        fuzz: 0.0,
+        albedo: Vec3::new(0.7, 0.6, 0.5).into(),
+        fuzz: 0.0.into(),
     };
     world.push(Box::new(Sphere {
         center: Vec3::new(4.0, 1.0, 0.0),
@@ -80,7 +90,11 @@ pub fn random_scene() -> Region {
         up: Vec3::new(0.0, 1.0, 0.0),
         aperture: 0.1,
         focal_distance: 10.0,
+        shutter_open: 0.0,
+        shutter_close: 0.0,
     };
 
+    world.recalculate_bounds();
+
     world
 }
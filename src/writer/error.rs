@@ -0,0 +1,28 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum WriterError {
+    /// A file extension that we have yet to add support for.
+    #[error("Unsupported output file extension")]
+    FileExtension,
+
+    /// The buffer doesn't have three floats for every pixel in the image.
+    #[error("The image buffer doesn't match the image dimensions.")]
+    BufferSize,
+
+    /// Extra layers for a format that only holds a single image.
+    #[error("The output file format doesn't support layers.")]
+    Layers,
+
+    /// Represents an error from encoding a png or hdr file.
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+
+    /// Represents an error from encoding an OpenEXR file.
+    #[error(transparent)]
+    Exr(#[from] exr::error::Error),
+
+    // Represents any other io error
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+}
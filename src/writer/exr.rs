@@ -0,0 +1,180 @@
+use super::{check_buffer, BoxResult, ImageLayer, ImageWriter};
+use crate::writer::error::WriterError;
+
+use exr::prelude::{
+    write_rgb_file, AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes,
+    SmallVec, WritableImage,
+};
+use log::error;
+
+pub struct ExrImageWriter {
+    filename: String,
+}
+
+impl ExrImageWriter {
+    pub fn new(filename: &str) -> ExrImageWriter {
+        ExrImageWriter {
+            filename: filename.to_string(),
+        }
+    }
+}
+
+/// Writes an OpenEXR file with 32 bit float R, G and B channels. This
+/// keeps all of the information in the render for compositing.
+impl ImageWriter for ExrImageWriter {
+    fn write_image(&self, pixels: &[f32], bounds: (u32, u32)) -> BoxResult<()> {
+        check_buffer(&self.filename, pixels, bounds)?;
+
+        let w = bounds.0 as usize;
+        write_rgb_file(&self.filename, w, bounds.1 as usize, |x, y| {
+            let i = (y * w + x) * 3;
+            (pixels[i], pixels[i + 1], pixels[i + 2])
+        })?;
+
+        Ok(())
+    }
+
+    fn supports_layers(&self) -> bool {
+        true
+    }
+
+    /// Writes all of the layers into a single part of the file, with the
+    /// channels of the image as R, G and B and the channels of the layers
+    /// prefixed with the name of the layer, like depth.Z. This is how
+    /// compositors like Nuke group the channels into layers.
+    fn write_layers(
+        &self,
+        pixels: &[f32],
+        layers: &[ImageLayer],
+        bounds: (u32, u32),
+    ) -> BoxResult<()> {
+        if layers
+            .iter()
+            .any(|layer| layer.pixels.len() != pixels.len())
+        {
+            error!("A layer doesn't match the dimensions {:?}", bounds);
+            return Err(WriterError::BufferSize);
+        }
+        check_buffer(&self.filename, pixels, bounds)?;
+
+        // the channels are stored one after the other
+        let channel = |name: String, pixels: &[f32], offset: usize| {
+            let samples = pixels.iter().skip(offset).step_by(3).copied().collect();
+            AnyChannel::new(name.as_str(), FlatSamples::F32(samples))
+        };
+
+        let mut channels = SmallVec::new();
+        for (offset, name) in ["R", "G", "B"].iter().enumerate() {
+            channels.push(channel(name.to_string(), pixels, offset));
+        }
+        for layer in layers.iter() {
+            for (offset, name) in layer.channels.iter().take(3).enumerate() {
+                channels.push(channel(
+                    format!("{}.{}", layer.name, name),
+                    layer.pixels,
+                    offset,
+                ));
+            }
+        }
+
+        let image = Image::from_layer(Layer::new(
+            (bounds.0 as usize, bounds.1 as usize),
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(channels),
+        ));
+        image.write().to_file(&self.filename)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exr::prelude::{read, read_first_rgba_layer_from_file, ReadChannels, ReadLayers};
+    use std::path::PathBuf;
+
+    // the process id keeps test runs of different checkouts apart
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rustyrender_{}_{}.exr", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_write_exr() {
+        let path = temp_file("test_write");
+        let filename = path.to_str().unwrap();
+
+        // values above 1 have to survive the round trip
+        let pixels = [0.5, 1.0, 2.0, 0.0, 0.25, 16.0];
+        ExrImageWriter::new(filename)
+            .write_image(&pixels, (2, 1))
+            .unwrap();
+
+        let image = read_first_rgba_layer_from_file(
+            filename,
+            |resolution, _| vec![0.0; resolution.width() * resolution.height() * 3],
+            |buffer: &mut Vec<f32>, position, (r, g, b, _a): (f32, f32, f32, f32)| {
+                let i = position.x() * 3;
+                buffer[i] = r;
+                buffer[i + 1] = g;
+                buffer[i + 2] = b;
+            },
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(image.layer_data.channel_data.pixels, pixels.to_vec());
+    }
+
+    #[test]
+    fn test_write_exr_layers() {
+        let path = temp_file("test_write_layers");
+        let filename = path.to_str().unwrap();
+
+        let pixels = [0.5, 1.0, 2.0, 0.0, 0.25, 16.0];
+        let depth = [3.0, 3.0, 3.0, 7.5, 7.5, 7.5];
+        let normals = [0.0, 1.0, 0.0, -1.0, 0.0, 0.0];
+        let layers = [
+            ImageLayer {
+                name: "depth",
+                channels: &["Z"],
+                pixels: &depth,
+            },
+            ImageLayer {
+                name: "normal",
+                channels: &["X", "Y", "Z"],
+                pixels: &normals,
+            },
+        ];
+        ExrImageWriter::new(filename)
+            .write_layers(&pixels, &layers, (2, 1))
+            .unwrap();
+
+        let image = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .from_file(filename)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let channel = |name: &str| -> Vec<f32> {
+            let channel = image
+                .layer_data
+                .channel_data
+                .list
+                .iter()
+                .find(|channel| channel.name.to_string() == name)
+                .unwrap();
+            channel.sample_data.values_as_f32().collect()
+        };
+        assert_eq!(image.layer_data.channel_data.list.len(), 7);
+        assert_eq!(channel("G"), vec![1.0, 0.25]);
+        assert_eq!(channel("depth.Z"), vec![3.0, 7.5]);
+        assert_eq!(channel("normal.X"), vec![0.0, -1.0]);
+        assert_eq!(channel("normal.Y"), vec![1.0, 0.0]);
+    }
+}
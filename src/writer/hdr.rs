@@ -0,0 +1,37 @@
+use super::{check_buffer, BoxResult, ImageWriter};
+
+use image::codecs::hdr::HdrEncoder;
+use image::Rgb;
+use std::fs::File;
+use std::io::BufWriter;
+
+pub struct HdrImageWriter {
+    filename: String,
+}
+
+impl HdrImageWriter {
+    pub fn new(filename: &str) -> HdrImageWriter {
+        HdrImageWriter {
+            filename: filename.to_string(),
+        }
+    }
+}
+
+/// Writes a Radiance rgbe hdr file. The format shares an exponent between
+/// the channels, so it has less precision than OpenEXR, but is widely
+/// supported.
+impl ImageWriter for HdrImageWriter {
+    fn write_image(&self, pixels: &[f32], bounds: (u32, u32)) -> BoxResult<()> {
+        check_buffer(&self.filename, pixels, bounds)?;
+
+        let data: Vec<Rgb<f32>> = pixels
+            .chunks_exact(3)
+            .map(|p| Rgb([p[0].max(0.0), p[1].max(0.0), p[2].max(0.0)]))
+            .collect();
+
+        let output = BufWriter::new(File::create(&self.filename)?);
+        HdrEncoder::new(output).encode(&data, bounds.0 as usize, bounds.1 as usize)?;
+
+        Ok(())
+    }
+}
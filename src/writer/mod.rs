@@ -0,0 +1,203 @@
+pub mod error;
+mod exr;
+mod hdr;
+mod pfm;
+mod png;
+
+use crate::renderer::post::tonemap::ToneMapping;
+use crate::writer::error::WriterError;
+
+use self::exr::ExrImageWriter;
+use self::hdr::HdrImageWriter;
+use self::pfm::PfmImageWriter;
+use self::png::PngImageWriter;
+
+use log::{error, info};
+use std::ffi::OsStr;
+use std::path::Path;
+
+type BoxResult<T> = Result<T, WriterError>;
+
+/// Factory method for creating an image writer based on the file type.
+/// Add a new arm to the extension matching block to add support for
+/// different file types.
+///
+/// This module supports 8 bit png files, and OpenEXR, Radiance hdr and
+/// pfm files which keep the full floating point range of the render.
+///
+/// # Arguments
+///
+/// * `filename` - The name of the image file to write.
+/// * `tone_mapping` - How to convert the render for the 8 bit formats. The
+///   high dynamic range formats store the linear values as they are.
+///
+/// # Examples
+///
+/// ```
+/// use rustyrender::renderer::post::tonemap::ToneMapping;
+/// use rustyrender::writer::ImageWriterFactory;
+/// let output_file_path = "output/image.exr";
+/// let image_writer =
+///     ImageWriterFactory::get_file_writer(output_file_path, &ToneMapping::default()).unwrap();
+/// ```
+///
+/// # Errors
+///
+/// * `FileExtensionError` - A file extension that we have yet to add support for.
+///
+pub struct ImageWriterFactory;
+
+impl ImageWriterFactory {
+    pub fn get_file_writer(
+        filename: &str,
+        tone_mapping: &ToneMapping,
+    ) -> BoxResult<Box<dyn ImageWriter>> {
+        match Path::new(filename)
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(|ext| ext.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("png") => Ok(Box::new(PngImageWriter::new(filename, tone_mapping))),
+            Some("exr") => Ok(Box::new(ExrImageWriter::new(filename))),
+            Some("hdr") => Ok(Box::new(HdrImageWriter::new(filename))),
+            Some("pfm") => Ok(Box::new(PfmImageWriter::new(filename))),
+            _ => {
+                error!("Unknown file extension on the output file {}", filename);
+                Err(WriterError::FileExtension)
+            }
+        }
+    }
+}
+
+/// Whether the file is one of the high dynamic range formats, which store
+/// the floating point values of the image as they are.
+pub fn is_hdr(filename: &str) -> bool {
+    matches!(
+        Path::new(filename)
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(|ext| ext.to_ascii_lowercase())
+            .as_deref(),
+        Some("exr") | Some("hdr") | Some("pfm")
+    )
+}
+
+/// An extra layer of an image, for the formats that can hold more than
+/// one.
+///
+/// Fields:
+/// * `name` - The name of the layer.
+/// * `channels` - The names of the channels of the layer, for the first
+///   values of each pixel.
+/// * `pixels` - The values of the layer, three floats per pixel like the
+///   image.
+pub struct ImageLayer<'a> {
+    pub name: &'a str,
+    pub channels: &'a [&'a str],
+    pub pixels: &'a [f32],
+}
+
+pub trait ImageWriter {
+    /// Writes the image to the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `pixels` - The linear floating point rgb pixels, three floats per
+    ///   pixel in rows from the top of the image.
+    /// * `bounds` - The width and height of the image.
+    fn write_image(&self, pixels: &[f32], bounds: (u32, u32)) -> BoxResult<()>;
+
+    /// Whether the format can hold extra layers, see `write_layers()`.
+    fn supports_layers(&self) -> bool {
+        false
+    }
+
+    /// Writes the image to the file along with the extra layers.
+    ///
+    /// # Errors
+    ///
+    /// * `Layers` - There are layers and the format doesn't support them.
+    fn write_layers(
+        &self,
+        pixels: &[f32],
+        layers: &[ImageLayer],
+        bounds: (u32, u32),
+    ) -> BoxResult<()> {
+        if !layers.is_empty() {
+            error!("The output format doesn't support layers");
+            return Err(WriterError::Layers);
+        }
+
+        self.write_image(pixels, bounds)
+    }
+}
+
+/// Checks that the buffer holds the whole image and logs the file being
+/// written. Shared by all of the writers.
+fn check_buffer(filename: &str, pixels: &[f32], bounds: (u32, u32)) -> BoxResult<()> {
+    if pixels.len() != bounds.0 as usize * bounds.1 as usize * 3 {
+        error!("Image buffer doesn't match the dimensions {:?}", bounds);
+        return Err(WriterError::BufferSize);
+    }
+
+    info!("Writing image file {}", filename);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writer_factory() {
+        for file in ["a.png", "a.exr", "a.hdr", "a.pfm", "dir/b.EXR"].iter() {
+            assert!(ImageWriterFactory::get_file_writer(file, &ToneMapping::default()).is_ok());
+        }
+
+        for file in ["a.json", "a", "a.jpg"].iter() {
+            assert!(matches!(
+                ImageWriterFactory::get_file_writer(file, &ToneMapping::default()),
+                Err(WriterError::FileExtension)
+            ));
+        }
+
+        assert!(is_hdr("dir/b.EXR"));
+        assert!(is_hdr("a.pfm"));
+        assert!(!is_hdr("a.png"));
+        assert!(!is_hdr("a"));
+    }
+
+    #[test]
+    fn test_writer_layers() {
+        let pixels = vec![0.0; 2 * 2 * 3];
+        let layer = ImageLayer {
+            name: "depth",
+            channels: &["Z"],
+            pixels: &pixels,
+        };
+
+        let writer =
+            ImageWriterFactory::get_file_writer("unused.png", &ToneMapping::default()).unwrap();
+        assert!(!writer.supports_layers());
+        assert!(matches!(
+            writer.write_layers(&pixels, &[layer], (2, 2)),
+            Err(WriterError::Layers)
+        ));
+
+        let writer =
+            ImageWriterFactory::get_file_writer("unused.exr", &ToneMapping::default()).unwrap();
+        assert!(writer.supports_layers());
+    }
+
+    #[test]
+    fn test_writer_buffer_size() {
+        let writer =
+            ImageWriterFactory::get_file_writer("unused.pfm", &ToneMapping::default()).unwrap();
+        let pixels = vec![0.0; 2 * 2 * 3 - 1];
+        assert!(matches!(
+            writer.write_image(&pixels, (2, 2)),
+            Err(WriterError::BufferSize)
+        ));
+    }
+}
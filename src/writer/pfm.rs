@@ -0,0 +1,69 @@
+use super::{check_buffer, BoxResult, ImageWriter};
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+pub struct PfmImageWriter {
+    filename: String,
+}
+
+impl PfmImageWriter {
+    pub fn new(filename: &str) -> PfmImageWriter {
+        PfmImageWriter {
+            filename: filename.to_string(),
+        }
+    }
+}
+
+/// Writes a color Portable Float Map. It's the raw floats with a small
+/// text header, little endian and with the rows stored from the bottom of
+/// the image up.
+impl ImageWriter for PfmImageWriter {
+    fn write_image(&self, pixels: &[f32], bounds: (u32, u32)) -> BoxResult<()> {
+        check_buffer(&self.filename, pixels, bounds)?;
+
+        let mut output = BufWriter::new(File::create(&self.filename)?);
+        write_pfm(&mut output, pixels, bounds)?;
+        output.flush()?;
+
+        Ok(())
+    }
+}
+
+fn write_pfm<W: Write>(output: &mut W, pixels: &[f32], bounds: (u32, u32)) -> BoxResult<()> {
+    // a negative scale marks the data as little endian
+    write!(output, "PF\n{} {}\n-1.0\n", bounds.0, bounds.1)?;
+
+    let row = bounds.0 as usize * 3;
+    for line in pixels.chunks_exact(row).rev() {
+        for value in line {
+            output.write_all(&value.to_le_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_pfm() {
+        // two rows of a single pixel each
+        let pixels = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let mut output = Vec::new();
+        write_pfm(&mut output, &pixels, (1, 2)).unwrap();
+
+        let header = b"PF\n1 2\n-1.0\n";
+        assert_eq!(&output[..header.len()], header);
+
+        let data: Vec<f32> = output[header.len()..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+
+        // the bottom row comes first
+        assert_eq!(data, vec![4.0, 5.0, 6.0, 1.0, 2.0, 3.0]);
+    }
+}
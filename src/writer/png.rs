@@ -0,0 +1,38 @@
+use super::{check_buffer, BoxResult, ImageWriter};
+
+use crate::renderer::post::tonemap::ToneMapping;
+
+use image::png::PngEncoder;
+use image::ColorType;
+use std::fs::File;
+
+pub struct PngImageWriter {
+    filename: String,
+    tone_mapping: ToneMapping,
+}
+
+impl PngImageWriter {
+    pub fn new(filename: &str, tone_mapping: &ToneMapping) -> PngImageWriter {
+        PngImageWriter {
+            filename: filename.to_string(),
+            tone_mapping: *tone_mapping,
+        }
+    }
+}
+
+/// Writes an 8 bit png file. The pixels are tone mapped and converted to
+/// sRGB.
+impl ImageWriter for PngImageWriter {
+    fn write_image(&self, pixels: &[f32], bounds: (u32, u32)) -> BoxResult<()> {
+        check_buffer(&self.filename, pixels, bounds)?;
+
+        let mut ldr_pixels = vec![0; pixels.len()];
+        self.tone_mapping.convert_buffer(pixels, &mut ldr_pixels);
+
+        let output = File::create(&self.filename)?;
+        let encoder = PngEncoder::new(output);
+        encoder.encode(&ldr_pixels, bounds.0, bounds.1, ColorType::Rgb8)?;
+
+        Ok(())
+    }
+}